@@ -1,3 +1,8 @@
-
-from .core import PyMongoEmb,Collection
+from .core import PyMongoEmb,Collection,ReturnDocument,Transaction
 from .redb import PyRedb
+from .mongo_emb import (ObjectId, Int32, Decimal128, Binary, Timestamp, Code, Symbol, MinKey,
+                        MaxKey, Undefined, DBPointer, RawDocument, Cursor, CodecOptions,
+                        to_extended_json, from_extended_json, InsertOneResult, InsertManyResult, UpdateResult,
+                        DeleteResult, BulkWriteResult, BulkWriteError, InsertOne, UpdateOne,
+                        UpdateMany, ReplaceOne, DeleteOne, DeleteMany, IndexModel,
+                        ClosedError, DuplicateKeyError, WriteConflictError)
@@ -2,18 +2,48 @@ from .mongo_emb import PyDatabase, PyCollection
 from typing import List
 
 
+class ReturnDocument:
+    """Which version of the document find_one_and_update/find_one_and_replace return."""
+    BEFORE = False
+    AFTER = True
+
+
 class PyMongoEmb:
 
-    def __init__(self, path: str) -> None:
+    def __init__(self, path: str, tz_aware: bool = False, tzinfo=None, strict: bool = False,
+                 ttl_interval: float = 60.0) -> None:
         self._path = path
-        self.__rust_db = PyDatabase(self._path)
+        self._tz_aware = tz_aware
+        self._tzinfo = tzinfo
+        self._strict = strict
+        self._ttl_interval = ttl_interval
+        self.__rust_db = PyDatabase(self._path, self._tz_aware, self._tzinfo, self._strict,
+                                    self._ttl_interval)
+
+    @classmethod
+    def open_temporary(cls, tz_aware: bool = False, tzinfo=None, strict: bool = False,
+                    ttl_interval: float = 60.0):
+        """A throwaway database for tests, written to a temporary directory removed on close."""
+        db = cls.__new__(cls)
+        db._path = None
+        db._tz_aware = tz_aware
+        db._tzinfo = tzinfo
+        db._strict = strict
+        db._ttl_interval = ttl_interval
+        db.__rust_db = PyDatabase.open_temporary(tz_aware, tzinfo, strict, ttl_interval)
+        return db
 
     def __enter__(self):
-        self.__rust_db = PyDatabase(self._path)
         return self
 
     def __exit__(self, exc_type, exc_val, exc_tb):
-        pass
+        self.close()
+
+    def close(self):
+        self.__rust_db.close()
+
+    def save(self, path: str):
+        return self.__rust_db.save(path)
 
     def __getitem__(self, name):
         return self.collection(name)
@@ -32,6 +62,54 @@ class PyMongoEmb:
     def list_collection_names(self):
         return self.__rust_db.list_collection_names()
 
+    def stats(self):
+        return self.__rust_db.stats()
+
+    def drop_collection(self, name):
+        return self.__rust_db.drop_collection(name)
+
+    def rename_collection(self, old, new, drop_target=False):
+        return self.__rust_db.rename_collection(old, new, drop_target)
+
+    @property
+    def codec_options(self):
+        return self.__rust_db.codec_options
+
+    def register_type(self, python_type, encode, decode=None, subtype=None, tag=None):
+        return self.__rust_db.register_type(python_type, encode, decode, subtype, tag)
+
+    def purge_expired(self):
+        return self.__rust_db.purge_expired()
+
+    def start_transaction(self):
+        return Transaction(self.__rust_db.start_transaction())
+
+
+class Transaction:
+    """Commits when the with block exits cleanly, aborts when it raises."""
+
+    def __init__(self, rust_transaction) -> None:
+        self.__rust_transaction = rust_transaction
+
+    def __enter__(self):
+        self.__rust_transaction.__enter__()
+        return self
+
+    def __exit__(self, exc_type, exc_val, exc_tb):
+        self.__rust_transaction.__exit__(exc_type, exc_val, exc_tb)
+
+    def __getitem__(self, name):
+        return self.collection(name)
+
+    def collection(self, name):
+        return Collection(self.__rust_transaction.collection(name))
+
+    def commit_transaction(self):
+        return self.__rust_transaction.commit_transaction()
+
+    def abort_transaction(self):
+        return self.__rust_transaction.abort_transaction()
+
 
 class Collection:
     def __init__(self, rust_collection) -> None:
@@ -40,17 +118,60 @@ class Collection:
     def name(self):
         return self.__rust_collection.name()
 
+    def close(self):
+        self.__rust_collection.close()
+
+    def drop(self):
+        return self.__rust_collection.drop()
+
+    def stats(self):
+        return self.__rust_collection.stats()
+
+    @property
+    def codec_options(self):
+        return self.__rust_collection.codec_options
+
+    def register_type(self, python_type, encode, decode=None, subtype=None, tag=None):
+        return self.__rust_collection.register_type(python_type, encode, decode, subtype, tag)
+
     def insert_one(self, entry: dict):
         return self.__rust_collection.insert_one(entry)
 
     def insert_many(self, entry: dict):
         return self.__rust_collection.insert_many(entry)
 
-    def find_one(self, filter: dict):
-        return self.__rust_collection.find_one(filter)
+    def insert_raw_many(self, docs: List[bytes]):
+        return self.__rust_collection.insert_raw_many(docs)
+
+    def insert_many_json(self, text: str):
+        return self.__rust_collection.insert_many_json(text)
+
+    def find_raw(self, filter: dict):
+        return self.__rust_collection.find_raw(filter)
+
+    def find_one(self, filter: dict, projection=None, sort=None, skip: int = 0):
+        return self.__rust_collection.find_one(filter, projection, sort, skip)
+
+    def find(self, filter: dict, projection=None, sort=None, skip: int = 0, limit: int = 0,
+             as_json: bool = False, canonical: bool = False, batch_size: int = 0):
+        return self.__rust_collection.find(filter, projection, sort, skip, limit, as_json, canonical,
+                                           batch_size)
+
+    def find_one_and_update(self, filter: dict, update: dict, projection=None, sort=None,
+                            upsert: bool = False, return_document=ReturnDocument.BEFORE):
+        return self.__rust_collection.find_one_and_update(filter, update, projection, sort, upsert,
+                                                          return_document)
 
-    def find(self, filter: dict):
-        return self.__rust_collection.find(filter)
+    def find_one_and_replace(self, filter: dict, replacement: dict, projection=None, sort=None,
+                             upsert: bool = False, return_document=ReturnDocument.BEFORE):
+        return self.__rust_collection.find_one_and_replace(filter, replacement, projection, sort,
+                                                           upsert, return_document)
+
+    def find_one_and_delete(self, filter: dict, projection=None, sort=None):
+        return self.__rust_collection.find_one_and_delete(filter, projection, sort)
+
+    def distinct(self, key: str, filter: dict = None):
+        return self.__rust_collection.distinct(key, filter)
 
     def update_many(self, filter: dict, update_doc: dict, upsert=False):
         if upsert is False:
@@ -62,6 +183,29 @@ class Collection:
             return self.__rust_collection.update_one(filter, update_doc)
         return self.__rust_collection.upsert(filter, update_doc)
 
+    def replace_one(self, filter: dict, replacement: dict, upsert: bool = False):
+        return self.__rust_collection.replace_one(filter, replacement, upsert)
+
+    def bulk_write(self, requests: list, ordered: bool = True):
+        return self.__rust_collection.bulk_write(requests, ordered)
+
+    def create_index(self, keys, unique: bool = False, name: str = None,
+                     expire_after_seconds: int = None, sparse: bool = False):
+        return self.__rust_collection.create_index(keys, unique, name, expire_after_seconds,
+                                                   sparse)
+
+    def create_indexes(self, indexes: list):
+        return self.__rust_collection.create_indexes(indexes)
+
+    def drop_index(self, index):
+        return self.__rust_collection.drop_index(index)
+
+    def drop_indexes(self):
+        return self.__rust_collection.drop_indexes()
+
+    def list_indexes(self):
+        return self.__rust_collection.list_indexes()
+
     def delete_many(self, filter: dict):
         return self.__rust_collection.delete_many(filter)
 
@@ -69,7 +213,13 @@ class Collection:
         return self.__rust_collection.delete_one(filter)
 
     def len(self):
-        return self.__rust_collection.count_documents()
+        return self.__rust_collection.estimated_document_count()
+
+    def count_documents(self, filter: dict, skip: int = None, limit: int = None):
+        return self.__rust_collection.count_documents(filter, skip, limit)
+
+    def estimated_document_count(self):
+        return self.__rust_collection.estimated_document_count()
 
     def aggregate(self, pipeline: List[dict]):
         return self.__rust_collection.aggregate(pipeline)
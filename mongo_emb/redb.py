@@ -9,12 +9,26 @@ class PyRedb:
         self.tp = tp
         self.__rust_db = PyRdb(self.dp, self.tp)
 
+    @classmethod
+    def open_memory(cls, tp: str):
+        """A database held in memory only, written to a file by save."""
+        db = cls.__new__(cls)
+        db.dp = None
+        db.tp = tp
+        db.__rust_db = PyRdb.open_memory(tp)
+        return db
+
     def __enter__(self):
-        self.__rust_db = PyRdb(self.dp, self.tp)
         return self
 
     def __exit__(self, exc_type, exc_val, exc_tb):
-        pass
+        self.close()
+
+    def close(self):
+        self.__rust_db.close()
+
+    def save(self, dp: str):
+        return self.__rust_db.save(dp)
 
     def write(self, k, v):
         return self.__rust_db.write(k, v)
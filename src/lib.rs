@@ -1,12 +1,36 @@
 use pyo3::prelude::*;
 
 mod mongo;
+// Kept as it was written, its existing lints are not enforced by `clippy -D warnings`
+#[allow(
+    dead_code,
+    unused_imports,
+    unused_variables,
+    clippy::redundant_static_lifetimes,
+    clippy::single_component_path_imports
+)]
 mod redb;
 
 use redb::py_rdb::PyRdb;
 
+use mongo::py_bson_types::{
+    PyBinary, PyCode, PyDbPointer, PyDecimal128, PyMaxKey, PyMinKey, PyObjectId,
+    PyRawDocument, PySymbol, PyTimestamp, PyUndefined, int32_type,
+};
+use mongo::py_bulk_write::{
+    PyDeleteMany, PyDeleteOne, PyInsertOne, PyReplaceOne, PyUpdateMany, PyUpdateOne,
+};
+use mongo::py_codec_options::PyCodecOptions;
+use mongo::py_cursor::PyCursor;
 use mongo::py_database::PyCollection;
 use mongo::py_database::PyDatabase;
+use mongo::py_errors::{BulkWriteError, ClosedError, DuplicateKeyError, WriteConflictError};
+use mongo::py_extended_json::{from_extended_json, to_extended_json};
+use mongo::py_index::PyIndexModel;
+use mongo::py_results::{
+    PyBulkWriteResult, PyDeleteResult, PyInsertManyResult, PyInsertOneResult, PyUpdateResult,
+};
+use mongo::py_transaction::PyTransaction;
 
 #[pymodule]
 fn mongo_emb(m: &Bound<'_, PyModule>) -> PyResult<()> {
@@ -14,7 +38,40 @@ fn mongo_emb(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<PyDatabase>()?;
 
     m.add_class::<PyCollection>()?;
+    m.add_class::<PyTransaction>()?;
+    m.add_class::<PyCursor>()?;
+    m.add_class::<PyCodecOptions>()?;
+    m.add_class::<PyObjectId>()?;
+    m.add("Int32", int32_type(m.py())?)?;
+    m.add_class::<PyDecimal128>()?;
+    m.add_class::<PyBinary>()?;
+    m.add_class::<PyTimestamp>()?;
+    m.add_class::<PyCode>()?;
+    m.add_class::<PySymbol>()?;
+    m.add_class::<PyMinKey>()?;
+    m.add_class::<PyMaxKey>()?;
+    m.add_class::<PyUndefined>()?;
+    m.add_class::<PyDbPointer>()?;
+    m.add_class::<PyRawDocument>()?;
+    m.add_class::<PyInsertOneResult>()?;
+    m.add_class::<PyInsertManyResult>()?;
+    m.add_class::<PyUpdateResult>()?;
+    m.add_class::<PyDeleteResult>()?;
+    m.add_class::<PyBulkWriteResult>()?;
+    m.add_class::<PyInsertOne>()?;
+    m.add_class::<PyUpdateOne>()?;
+    m.add_class::<PyUpdateMany>()?;
+    m.add_class::<PyReplaceOne>()?;
+    m.add_class::<PyDeleteOne>()?;
+    m.add_class::<PyDeleteMany>()?;
+    m.add_class::<PyIndexModel>()?;
     m.add_class::<PyRdb>()?;
+    m.add_function(wrap_pyfunction!(to_extended_json, m)?)?;
+    m.add_function(wrap_pyfunction!(from_extended_json, m)?)?;
+    m.add("BulkWriteError", m.py().get_type::<BulkWriteError>())?;
+    m.add("ClosedError", m.py().get_type::<ClosedError>())?;
+    m.add("DuplicateKeyError", m.py().get_type::<DuplicateKeyError>())?;
+    m.add("WriteConflictError", m.py().get_type::<WriteConflictError>())?;
 
     Ok(())
 }
@@ -0,0 +1,233 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyType;
+use std::sync::{Arc, RwLock};
+
+/// Key under which a tagged custom value stores its tag, see [`CodecMarker::Tag`].
+pub const TAG_KEY: &str = "__type__";
+/// Key under which a tagged custom value stores the encoded value.
+pub const TAG_VALUE_KEY: &str = "__value__";
+
+/// How a value produced by a custom encoder is recognised again on read.
+pub enum CodecMarker {
+    /// Stored as whatever the encoder returned, it cannot be decoded back.
+    Plain,
+    /// Stored as BSON binary with this user-defined subtype (128-255).
+    Subtype(u8),
+    /// Stored as `{"__type__": tag, "__value__": encoded}`.
+    Tag(String),
+}
+
+/// A user supplied encoder (and optional decoder) for one Python type.
+pub struct TypeCodec {
+    pub python_type: Py<PyType>,
+    pub encoder: Py<PyAny>,
+    pub decoder: Option<Py<PyAny>>,
+    pub marker: CodecMarker,
+}
+
+/// Custom type codecs, consulted by the BSON translator before the built-in conversions.
+/// A collection registry has the database registry as parent, its own codecs win.
+#[derive(Default)]
+pub struct CodecRegistry {
+    // Replaced as a whole on registration so lookups never hold the lock while calling Python
+    codecs: RwLock<Arc<Vec<Arc<TypeCodec>>>>,
+    parent: Option<Arc<CodecRegistry>>,
+}
+
+impl CodecRegistry {
+    pub fn with_parent(parent: Arc<CodecRegistry>) -> CodecRegistry {
+        CodecRegistry {
+            codecs: RwLock::default(),
+            parent: Some(parent),
+        }
+    }
+
+    /// Register `encoder` for instances of `python_type`, replacing an earlier codec for the same type.
+    pub fn register(
+        &self,
+        python_type: &Bound<'_, PyType>,
+        encoder: Py<PyAny>,
+        decoder: Option<Py<PyAny>>,
+        subtype: Option<u8>,
+        tag: Option<String>,
+    ) -> PyResult<()> {
+        let marker = match (subtype, tag) {
+            (Some(_), Some(_)) => {
+                return Err(PyValueError::new_err(
+                    "a codec takes either a subtype or a tag, not both",
+                ));
+            }
+            (Some(subtype), None) if subtype < 128 => {
+                return Err(PyValueError::new_err(format!(
+                    "subtype {} is reserved, custom codecs must use 128-255",
+                    subtype
+                )));
+            }
+            (Some(subtype), None) => CodecMarker::Subtype(subtype),
+            (None, Some(tag)) => CodecMarker::Tag(tag),
+            (None, None) if decoder.is_some() => {
+                return Err(PyValueError::new_err(
+                    "a decoder needs a subtype or a tag to recognise stored values",
+                ));
+            }
+            (None, None) => CodecMarker::Plain,
+        };
+
+        let mut codecs = self
+            .codecs
+            .write()
+            .map_err(|e| PyValueError::new_err(format!("Failed to lock: {}", e)))?;
+        let mut updated: Vec<Arc<TypeCodec>> = codecs
+            .iter()
+            .filter(|codec| !codec.python_type.is(python_type))
+            .cloned()
+            .collect();
+        if let Some(other) = updated.iter().find(|codec| codec.marker.same_key(&marker)) {
+            return Err(PyValueError::new_err(format!(
+                "{} is already used by the codec for '{}'",
+                marker,
+                other.python_type.bind(python_type.py()).name()?
+            )));
+        }
+        updated.push(Arc::new(TypeCodec {
+            python_type: python_type.clone().unbind(),
+            encoder,
+            decoder,
+            marker,
+        }));
+        *codecs = Arc::new(updated);
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.local().is_empty() && self.parent.as_ref().is_none_or(|parent| parent.is_empty())
+    }
+
+    /// The codec whose type `obj` is an instance of, in registration order.
+    pub fn encoder_for(&self, obj: &Bound<'_, PyAny>) -> PyResult<Option<Arc<TypeCodec>>> {
+        for codec in self.local().iter() {
+            if obj.is_instance(codec.python_type.bind(obj.py()))? {
+                return Ok(Some(codec.clone()));
+            }
+        }
+        match &self.parent {
+            Some(parent) => parent.encoder_for(obj),
+            None => Ok(None),
+        }
+    }
+
+    pub fn decoder_for_subtype(&self, subtype: u8) -> Option<Arc<TypeCodec>> {
+        self.decoder_for(&|marker| matches!(marker, CodecMarker::Subtype(s) if *s == subtype))
+    }
+
+    pub fn decoder_for_tag(&self, tag: &str) -> Option<Arc<TypeCodec>> {
+        self.decoder_for(&|marker| matches!(marker, CodecMarker::Tag(t) if t == tag))
+    }
+
+    fn decoder_for(&self, matches: &dyn Fn(&CodecMarker) -> bool) -> Option<Arc<TypeCodec>> {
+        self.local()
+            .iter()
+            .find(|codec| codec.decoder.is_some() && matches(&codec.marker))
+            .cloned()
+            .or_else(|| self.parent.as_ref()?.decoder_for(matches))
+    }
+
+    fn local(&self) -> Arc<Vec<Arc<TypeCodec>>> {
+        match self.codecs.read() {
+            Ok(codecs) => codecs.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+}
+
+impl CodecMarker {
+    fn same_key(&self, other: &CodecMarker) -> bool {
+        match (self, other) {
+            (CodecMarker::Subtype(a), CodecMarker::Subtype(b)) => a == b,
+            (CodecMarker::Tag(a), CodecMarker::Tag(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for CodecMarker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodecMarker::Plain => write!(f, "plain encoding"),
+            CodecMarker::Subtype(subtype) => write!(f, "subtype {}", subtype),
+            CodecMarker::Tag(tag) => write!(f, "tag '{}'", tag),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::types::{PyFloat, PyInt, PyString};
+
+    fn identity(py: Python) -> Py<PyAny> {
+        py.eval(c"lambda value: value", None, None)
+            .unwrap()
+            .unbind()
+    }
+
+    #[test]
+    fn register_checks_markers() {
+        Python::attach(|py| {
+            let registry = CodecRegistry::default();
+            let int_type = py.get_type::<PyInt>();
+            let decoder = Some(identity(py));
+            for (subtype, tag) in [(Some(128), Some("t".to_string())), (Some(5), None)] {
+                assert!(
+                    registry
+                        .register(&int_type, identity(py), None, subtype, tag)
+                        .is_err()
+                );
+            }
+            assert!(
+                registry
+                    .register(&int_type, identity(py), decoder, None, None)
+                    .is_err()
+            );
+            registry
+                .register(&int_type, identity(py), None, Some(128), None)
+                .unwrap();
+            let float_type = py.get_type::<PyFloat>();
+            assert!(
+                registry
+                    .register(&float_type, identity(py), None, Some(128), None)
+                    .is_err()
+            );
+        });
+    }
+
+    #[test]
+    fn collection_codecs_win_over_the_parent() {
+        Python::attach(|py| {
+            let parent = Arc::new(CodecRegistry::default());
+            let str_type = py.get_type::<PyString>();
+            parent
+                .register(
+                    &str_type,
+                    identity(py),
+                    Some(identity(py)),
+                    None,
+                    Some("a".into()),
+                )
+                .unwrap();
+            let child = CodecRegistry::with_parent(parent.clone());
+            assert!(!child.is_empty());
+            assert!(child.decoder_for_tag("a").is_some());
+
+            child
+                .register(&str_type, identity(py), Some(identity(py)), Some(200), None)
+                .unwrap();
+            let value = PyString::new(py, "x");
+            let codec = child.encoder_for(value.as_any()).unwrap().unwrap();
+            assert!(matches!(codec.marker, CodecMarker::Subtype(200)));
+            assert!(child.decoder_for_subtype(200).is_some());
+            assert!(parent.decoder_for_subtype(200).is_none());
+        });
+    }
+}
@@ -0,0 +1,853 @@
+use polodb_core::bson::{Bson, Document};
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList, PyTuple};
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+
+/// Sort keys in priority order, with 1 for ascending and -1 for descending.
+pub type SortSpec = Vec<(String, i8)>;
+
+/// Parse a pymongo style sort: a key name, a list of `(key, direction)` pairs or a dict.
+pub fn convert_py_obj_to_sort(py_obj: &Bound<'_, PyAny>) -> PyResult<SortSpec> {
+    if let Ok(key) = py_obj.extract::<String>() {
+        return Ok(vec![(key, 1)]);
+    }
+    let pairs: Vec<(String, Bound<'_, PyAny>)> = if let Ok(dict) = py_obj.cast::<PyDict>() {
+        dict.iter()
+            .map(|(key, direction)| Ok((key.extract::<String>()?, direction)))
+            .collect::<PyResult<_>>()?
+    } else if let Ok(list) = py_obj.cast::<PyList>() {
+        list.iter()
+            .map(|item| {
+                let pair = item
+                    .cast::<PyTuple>()
+                    .map_err(|_| PyTypeError::new_err("sort: expected (key, direction) pairs"))?;
+                Ok((pair.get_item(0)?.extract::<String>()?, pair.get_item(1)?))
+            })
+            .collect::<PyResult<_>>()?
+    } else {
+        return Err(PyTypeError::new_err(
+            "sort: expected a key, a list of (key, direction) pairs or a dict",
+        ));
+    };
+    pairs
+        .into_iter()
+        .map(|(key, direction)| match direction.extract::<i64>() {
+            Ok(1) => Ok((key, 1)),
+            Ok(-1) => Ok((key, -1)),
+            _ => Err(PyValueError::new_err(format!(
+                "sort.{}: direction must be 1 or -1, got {}",
+                key, direction
+            ))),
+        })
+        .collect()
+}
+
+/// Stable sort with MongoDB semantics: keys are compared in order, a missing field sorts like
+/// null and an array sorts by its smallest element ascending or its largest element descending.
+pub fn sort_documents(docs: Vec<Document>, sort: &SortSpec) -> Vec<Document> {
+    let mut keyed: Vec<(Vec<Bson>, Document)> = docs
+        .into_iter()
+        .map(|doc| {
+            let keys = sort
+                .iter()
+                .map(|(path, direction)| sort_key(&doc, path, *direction))
+                .collect();
+            (keys, doc)
+        })
+        .collect();
+    keyed.sort_by(|(a, _), (b, _)| {
+        for ((a, b), (_, direction)) in a.iter().zip(b).zip(sort) {
+            let ordering = compare_bson(a, b);
+            if ordering != Ordering::Equal {
+                return if *direction < 0 {
+                    ordering.reverse()
+                } else {
+                    ordering
+                };
+            }
+        }
+        Ordering::Equal
+    });
+    keyed.into_iter().map(|(_, doc)| doc).collect()
+}
+
+fn sort_key(doc: &Document, path: &str, direction: i8) -> Bson {
+    let values = path_values(doc, path);
+    let candidates = values.iter().flat_map(|value| match value {
+        Bson::Array(items) => items.iter().collect::<Vec<_>>(),
+        other => vec![*other],
+    });
+    let key = if direction < 0 {
+        candidates.max_by(|a, b| compare_bson(a, b))
+    } else {
+        candidates.min_by(|a, b| compare_bson(a, b))
+    };
+    key.cloned().unwrap_or(Bson::Null)
+}
+
+/// Every value reached by a dotted `path`, descending into arrays of documents along the way
+/// and accepting numeric segments as array indexes.
+pub fn path_values<'a>(doc: &'a Document, path: &str) -> Vec<&'a Bson> {
+    let segments: Vec<&str> = path.split('.').collect();
+    let mut values = Vec::new();
+    if let Some(value) = doc.get(segments[0]) {
+        collect_path_values(value, &segments[1..], &mut values);
+    }
+    values
+}
+
+fn collect_path_values<'a>(value: &'a Bson, segments: &[&str], values: &mut Vec<&'a Bson>) {
+    let Some((segment, rest)) = segments.split_first() else {
+        values.push(value);
+        return;
+    };
+    match value {
+        Bson::Document(doc) => {
+            if let Some(child) = doc.get(*segment) {
+                collect_path_values(child, rest, values);
+            }
+        }
+        Bson::Array(items) => {
+            if let Some(item) = segment.parse::<usize>().ok().and_then(|i| items.get(i)) {
+                collect_path_values(item, rest, values);
+            }
+            for item in items {
+                if let Bson::Document(doc) = item
+                    && let Some(child) = doc.get(*segment)
+                {
+                    collect_path_values(child, rest, values);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The distinct values found at a path, kept in MongoDB sort order. Numbers that compare equal,
+/// like 1 and 1.0, count as one value.
+#[derive(Default)]
+pub struct DistinctValues(BTreeSet<OrderedBson>);
+
+impl DistinctValues {
+    /// Add the values of `path` in `doc`, every element of an array counting on its own.
+    pub fn add(&mut self, doc: &Document, path: &str) {
+        for value in path_values(doc, path) {
+            match value {
+                Bson::Array(items) => {
+                    for item in items {
+                        self.0.insert(OrderedBson(item.clone()));
+                    }
+                }
+                value => {
+                    self.0.insert(OrderedBson(value.clone()));
+                }
+            }
+        }
+    }
+
+    pub fn into_values(self) -> Vec<Bson> {
+        self.0.into_iter().map(|value| value.0).collect()
+    }
+}
+
+struct OrderedBson(Bson);
+
+impl Ord for OrderedBson {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_bson(&self.0, &other.0)
+    }
+}
+
+impl PartialOrd for OrderedBson {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for OrderedBson {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OrderedBson {}
+
+/// Total order over BSON values following MongoDB's comparison order across types.
+pub fn compare_bson(a: &Bson, b: &Bson) -> Ordering {
+    let (rank_a, rank_b) = (type_rank(a), type_rank(b));
+    if rank_a != rank_b {
+        return rank_a.cmp(&rank_b);
+    }
+    match (a, b) {
+        (Bson::Int32(_) | Bson::Int64(_), Bson::Int32(_) | Bson::Int64(_)) => {
+            as_i64(a).cmp(&as_i64(b))
+        }
+        (Bson::Int32(_) | Bson::Int64(_), Bson::Double(f)) => compare_i64_f64(as_i64(a), *f),
+        (Bson::Double(f), Bson::Int32(_) | Bson::Int64(_)) => {
+            compare_i64_f64(as_i64(b), *f).reverse()
+        }
+        _ if rank_a == 3 => compare_f64(as_f64(a), as_f64(b)),
+        (Bson::String(a) | Bson::Symbol(a), Bson::String(b) | Bson::Symbol(b)) => a.cmp(b),
+        (Bson::Document(a), Bson::Document(b)) => {
+            for ((key_a, value_a), (key_b, value_b)) in a.iter().zip(b.iter()) {
+                let ordering = type_rank(value_a)
+                    .cmp(&type_rank(value_b))
+                    .then_with(|| key_a.cmp(key_b))
+                    .then_with(|| compare_bson(value_a, value_b));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            a.len().cmp(&b.len())
+        }
+        (Bson::Array(a), Bson::Array(b)) => {
+            for (item_a, item_b) in a.iter().zip(b) {
+                let ordering = compare_bson(item_a, item_b);
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            a.len().cmp(&b.len())
+        }
+        (Bson::Binary(a), Bson::Binary(b)) => a
+            .bytes
+            .len()
+            .cmp(&b.bytes.len())
+            .then_with(|| u8::from(a.subtype).cmp(&u8::from(b.subtype)))
+            .then_with(|| a.bytes.cmp(&b.bytes)),
+        (Bson::ObjectId(a), Bson::ObjectId(b)) => a.cmp(b),
+        (Bson::Boolean(a), Bson::Boolean(b)) => a.cmp(b),
+        (Bson::DateTime(a), Bson::DateTime(b)) => a.cmp(b),
+        (Bson::Timestamp(a), Bson::Timestamp(b)) => {
+            (a.time, a.increment).cmp(&(b.time, b.increment))
+        }
+        (Bson::RegularExpression(a), Bson::RegularExpression(b)) => {
+            (&a.pattern, &a.options).cmp(&(&b.pattern, &b.options))
+        }
+        (Bson::JavaScriptCode(a), Bson::JavaScriptCode(b)) => a.cmp(b),
+        (Bson::JavaScriptCodeWithScope(a), Bson::JavaScriptCodeWithScope(b)) => a
+            .code
+            .cmp(&b.code)
+            .then_with(|| compare_bson(&a.scope.clone().into(), &b.scope.clone().into())),
+        _ => a.to_string().cmp(&b.to_string()),
+    }
+}
+
+fn type_rank(value: &Bson) -> u8 {
+    match value {
+        Bson::MinKey => 0,
+        Bson::Null | Bson::Undefined => 1,
+        Bson::Int32(_) | Bson::Int64(_) | Bson::Double(_) | Bson::Decimal128(_) => 3,
+        Bson::String(_) | Bson::Symbol(_) => 4,
+        Bson::Document(_) => 5,
+        Bson::Array(_) => 6,
+        Bson::Binary(_) => 7,
+        Bson::ObjectId(_) => 8,
+        Bson::Boolean(_) => 9,
+        Bson::DateTime(_) => 10,
+        Bson::Timestamp(_) => 11,
+        Bson::RegularExpression(_) => 12,
+        Bson::DbPointer(_) => 13,
+        Bson::JavaScriptCode(_) => 14,
+        Bson::JavaScriptCodeWithScope(_) => 15,
+        Bson::MaxKey => 16,
+    }
+}
+
+fn as_i64(value: &Bson) -> i64 {
+    match value {
+        Bson::Int32(i) => *i as i64,
+        Bson::Int64(i) => *i,
+        _ => 0,
+    }
+}
+
+fn as_f64(value: &Bson) -> f64 {
+    match value {
+        Bson::Int32(i) => *i as f64,
+        Bson::Int64(i) => *i as f64,
+        Bson::Double(f) => *f,
+        Bson::Decimal128(d) => d.to_string().parse().unwrap_or(f64::NAN),
+        _ => f64::NAN,
+    }
+}
+
+// NaN sorts before every other number, as in MongoDB
+fn compare_f64(a: f64, b: f64) -> Ordering {
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        (false, false) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+    }
+}
+
+// Exact, a cast to f64 rounds integers beyond 2^53 and would make the order non-transitive
+fn compare_i64_f64(i: i64, f: f64) -> Ordering {
+    // -2^63 and 2^63 are exact doubles, everything in between truncates to a valid i64
+    const BOUND: f64 = 9_223_372_036_854_775_808.0;
+    if f.is_nan() {
+        Ordering::Greater
+    } else if f >= BOUND {
+        Ordering::Less
+    } else if f < -BOUND {
+        Ordering::Greater
+    } else {
+        let whole = f.trunc();
+        i.cmp(&(whole as i64))
+            .then_with(|| compare_f64(0.0, f - whole))
+    }
+}
+
+/// A parsed inclusion or exclusion projection.
+pub struct Projection {
+    include: bool,
+    include_id: bool,
+    fields: ProjectionTree,
+}
+
+#[derive(Default)]
+struct ProjectionTree(HashMap<String, ProjectionField>);
+
+enum ProjectionField {
+    Whole,
+    Nested(ProjectionTree),
+}
+
+/// Parse a projection given as a dict of `field: 1|0` (dotted paths allowed) or a list of
+/// field names to include. `_id` is kept unless it is explicitly excluded.
+pub fn convert_py_obj_to_projection(py_obj: &Bound<'_, PyAny>) -> PyResult<Projection> {
+    let entries: Vec<(String, bool)> = if let Ok(dict) = py_obj.cast::<PyDict>() {
+        dict.iter()
+            .map(|(key, flag)| {
+                let key = key.extract::<String>()?;
+                match flag.extract::<i64>() {
+                    Ok(flag) => Ok((key, flag != 0)),
+                    Err(_) => Err(PyValueError::new_err(format!(
+                        "projection.{}: expected 1 or 0, got {}",
+                        key, flag
+                    ))),
+                }
+            })
+            .collect::<PyResult<_>>()?
+    } else if let Ok(list) = py_obj.cast::<PyList>() {
+        list.iter()
+            .map(|key| Ok((key.extract::<String>()?, true)))
+            .collect::<PyResult<_>>()?
+    } else {
+        return Err(PyTypeError::new_err(
+            "projection: expected a dict or a list of field names",
+        ));
+    };
+
+    let mut include_id = true;
+    let mut include = None;
+    let mut fields = ProjectionTree::default();
+    for (path, flag) in entries {
+        if path == "_id" {
+            include_id = flag;
+            continue;
+        }
+        if *include.get_or_insert(flag) != flag {
+            return Err(PyValueError::new_err(format!(
+                "projection.{}: cannot mix inclusion and exclusion",
+                path
+            )));
+        }
+        fields.insert(&path)?;
+    }
+    Ok(Projection {
+        // Only `{"_id": 0}` given: an exclusion projection
+        include: include.unwrap_or(false),
+        include_id,
+        fields,
+    })
+}
+
+impl ProjectionTree {
+    fn insert(&mut self, path: &str) -> PyResult<()> {
+        let collision = || PyValueError::new_err(format!("projection.{}: path collision", path));
+        let (head, rest) = match path.split_once('.') {
+            Some((head, rest)) => (head, Some(rest)),
+            None => (path, None),
+        };
+        match (self.0.get_mut(head), rest) {
+            (None, None) => {
+                self.0.insert(head.to_string(), ProjectionField::Whole);
+            }
+            (None, Some(rest)) => {
+                let mut nested = ProjectionTree::default();
+                nested.insert(rest)?;
+                self.0
+                    .insert(head.to_string(), ProjectionField::Nested(nested));
+            }
+            (Some(ProjectionField::Nested(nested)), Some(rest)) => nested.insert(rest)?,
+            (Some(_), _) => return Err(collision()),
+        }
+        Ok(())
+    }
+}
+
+impl Projection {
+    pub fn apply(&self, doc: Document) -> Document {
+        if self.include {
+            include_fields(doc, &self.fields, self.include_id)
+        } else {
+            let mut projected = exclude_fields(doc, &self.fields);
+            if !self.include_id {
+                projected.remove("_id");
+            }
+            projected
+        }
+    }
+}
+
+fn include_fields(doc: Document, tree: &ProjectionTree, keep_id: bool) -> Document {
+    let mut projected = Document::new();
+    for (key, value) in doc {
+        if keep_id && key == "_id" {
+            projected.insert(key, value);
+            continue;
+        }
+        match tree.0.get(&key) {
+            Some(ProjectionField::Whole) => {
+                projected.insert(key, value);
+            }
+            Some(ProjectionField::Nested(nested)) => match value {
+                Bson::Document(sub) => {
+                    projected.insert(key, include_fields(sub, nested, false));
+                }
+                Bson::Array(items) => {
+                    let items: Vec<Bson> = items
+                        .into_iter()
+                        .filter_map(|item| match item {
+                            Bson::Document(sub) => Some(include_fields(sub, nested, false).into()),
+                            _ => None,
+                        })
+                        .collect();
+                    projected.insert(key, items);
+                }
+                _ => {}
+            },
+            None => {}
+        }
+    }
+    projected
+}
+
+fn exclude_fields(doc: Document, tree: &ProjectionTree) -> Document {
+    let mut projected = Document::new();
+    for (key, value) in doc {
+        match tree.0.get(&key) {
+            Some(ProjectionField::Whole) => {}
+            Some(ProjectionField::Nested(nested)) => {
+                let value = match value {
+                    Bson::Document(sub) => exclude_fields(sub, nested).into(),
+                    Bson::Array(items) => Bson::Array(
+                        items
+                            .into_iter()
+                            .map(|item| match item {
+                                Bson::Document(sub) => exclude_fields(sub, nested).into(),
+                                other => other,
+                            })
+                            .collect(),
+                    ),
+                    other => other,
+                };
+                projected.insert(key, value);
+            }
+            None => {
+                projected.insert(key, value);
+            }
+        }
+    }
+    projected
+}
+
+/// Options shared by `find` and `find_one`, a `limit` of 0 means no limit.
+#[derive(Default)]
+pub struct FindOptions {
+    pub projection: Option<Projection>,
+    pub sort: Option<SortSpec>,
+    pub skip: u64,
+    pub limit: u64,
+}
+
+impl FindOptions {
+    pub fn from_py(
+        projection: Option<&Bound<'_, PyAny>>,
+        sort: Option<&Bound<'_, PyAny>>,
+        skip: u64,
+        limit: u64,
+    ) -> PyResult<FindOptions> {
+        Ok(FindOptions {
+            projection: projection.map(convert_py_obj_to_projection).transpose()?,
+            sort: sort.map(convert_py_obj_to_sort).transpose()?,
+            skip,
+            limit,
+        })
+    }
+}
+
+/// Parse `return_document`: a bool (true for the document after the change) or "before"/"after".
+pub fn convert_py_obj_to_return_document(py_obj: Option<&Bound<'_, PyAny>>) -> PyResult<bool> {
+    let Some(py_obj) = py_obj else {
+        return Ok(false);
+    };
+    if let Ok(after) = py_obj.extract::<bool>() {
+        return Ok(after);
+    }
+    match py_obj.extract::<String>()?.to_lowercase().as_str() {
+        "before" => Ok(false),
+        "after" => Ok(true),
+        other => Err(PyValueError::new_err(format!(
+            "return_document: expected 'before' or 'after', got '{}'",
+            other
+        ))),
+    }
+}
+
+/// An update must consist of `$` operators only.
+pub fn validate_update(update: &Document) -> PyResult<()> {
+    if update.is_empty() {
+        return Err(PyValueError::new_err("update: must not be empty"));
+    }
+    match update.keys().find(|key| !key.starts_with('$')) {
+        Some(key) => Err(PyValueError::new_err(format!(
+            "update.{}: update only works with $ operators",
+            key
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// A replacement is a whole document and must not contain `$` operators.
+pub fn validate_replacement(replacement: &Document) -> PyResult<()> {
+    match replacement.keys().find(|key| key.starts_with('$')) {
+        Some(key) => Err(PyValueError::new_err(format!(
+            "replacement.{}: replacement must not contain $ operators",
+            key
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// The document inserted by an upsert before its update is applied: the equality conditions of
+/// `filter`, including `$eq` and the branches of `$and`, with dotted paths expanded.
+pub fn upsert_seed(filter: &Document) -> Document {
+    let mut seed = Document::new();
+    collect_equalities(filter, &mut seed);
+    seed
+}
+
+fn collect_equalities(filter: &Document, seed: &mut Document) {
+    for (key, value) in filter {
+        if key == "$and" {
+            if let Bson::Array(branches) = value {
+                for branch in branches {
+                    if let Bson::Document(branch) = branch {
+                        collect_equalities(branch, seed);
+                    }
+                }
+            }
+            continue;
+        }
+        if key.starts_with('$') {
+            continue;
+        }
+        let value = match value {
+            Bson::Document(condition) if condition.keys().any(|k| k.starts_with('$')) => {
+                match condition.get("$eq") {
+                    Some(value) => value,
+                    None => continue,
+                }
+            }
+            value => value,
+        };
+        set_path(seed, key, value.clone());
+    }
+}
+
+fn set_path(doc: &mut Document, path: &str, value: Bson) {
+    match path.split_once('.') {
+        Some((head, rest)) => {
+            if !matches!(doc.get(head), Some(Bson::Document(_))) {
+                doc.insert(head, Document::new());
+            }
+            if let Some(Bson::Document(child)) = doc.get_mut(head) {
+                set_path(child, rest, value);
+            }
+        }
+        None => {
+            doc.insert(path, value);
+        }
+    }
+}
+
+/// `replacement` with `_id` first, taken from the document it replaces. Changing `_id` is an error.
+pub fn replacement_with_id(replacement: Document, id: Option<&Bson>) -> PyResult<Document> {
+    let mut replaced = Document::new();
+    match (id, replacement.get("_id")) {
+        (Some(id), Some(new_id)) if new_id != id => {
+            return Err(PyValueError::new_err(
+                "replacement._id: the _id of a document cannot be changed",
+            ));
+        }
+        (Some(id), _) => {
+            replaced.insert("_id", id.clone());
+        }
+        (None, Some(new_id)) => {
+            replaced.insert("_id", new_id.clone());
+        }
+        (None, None) => {}
+    }
+    for (key, value) in replacement {
+        if key != "_id" {
+            replaced.insert(key, value);
+        }
+    }
+    Ok(replaced)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polodb_core::bson::doc;
+
+    fn ids(docs: &[Document]) -> Vec<i32> {
+        docs.iter().map(|doc| doc.get_i32("_id").unwrap()).collect()
+    }
+
+    fn projection(py: Python, code: &std::ffi::CStr) -> PyResult<Projection> {
+        convert_py_obj_to_projection(&py.eval(code, None, None).unwrap())
+    }
+
+    #[test]
+    fn sort_parsing() {
+        Python::attach(|py| {
+            let parse =
+                |code: &std::ffi::CStr| convert_py_obj_to_sort(&py.eval(code, None, None).unwrap());
+            assert_eq!(parse(c"'a'").unwrap(), vec![("a".to_string(), 1)]);
+            assert_eq!(
+                parse(c"[('a', -1), ('b', 1)]").unwrap(),
+                vec![("a".to_string(), -1), ("b".to_string(), 1)]
+            );
+            assert_eq!(parse(c"{'a': -1}").unwrap(), vec![("a".to_string(), -1)]);
+            assert!(parse(c"{'a': 2}").is_err());
+            assert!(parse(c"['a']").is_err());
+            assert!(parse(c"3").is_err());
+        });
+    }
+
+    #[test]
+    fn sort_follows_mongodb_order() {
+        let docs = vec![
+            doc! {"_id": 1, "a": 2, "b": "x"},
+            doc! {"_id": 2},
+            doc! {"_id": 3, "a": [5, 1]},
+            doc! {"_id": 4, "a": 2.5},
+            doc! {"_id": 5, "a": 2, "b": "a"},
+            doc! {"_id": 6, "a": "text"},
+        ];
+        let ascending = vec![("a".to_string(), 1), ("b".to_string(), 1)];
+        assert_eq!(
+            ids(&sort_documents(docs.clone(), &ascending)),
+            vec![2, 3, 5, 1, 4, 6]
+        );
+        let descending = vec![("a".to_string(), -1)];
+        assert_eq!(
+            ids(&sort_documents(docs, &descending)),
+            vec![6, 3, 4, 1, 5, 2]
+        );
+    }
+
+    #[test]
+    fn path_values_descend_into_arrays() {
+        let doc = doc! {"a": [{"b": 1}, {"b": 2}, {"c": 3}], "d": {"e": [4, 5]}};
+        assert_eq!(
+            path_values(&doc, "a.b"),
+            vec![&Bson::Int32(1), &Bson::Int32(2)]
+        );
+        assert_eq!(path_values(&doc, "a.1.b"), vec![&Bson::Int32(2)]);
+        assert_eq!(path_values(&doc, "d.e.0"), vec![&Bson::Int32(4)]);
+        assert!(path_values(&doc, "x.y").is_empty());
+    }
+
+    #[test]
+    fn inclusion_projection() {
+        Python::attach(|py| {
+            let doc = doc! {"_id": 1, "a": 1, "b": {"c": 2, "d": 3}, "e": [{"c": 4, "f": 5}, 6]};
+            let projected = projection(py, c"{'a': 1, 'b.c': 1, 'e.c': 1}")
+                .unwrap()
+                .apply(doc.clone());
+            assert_eq!(
+                projected,
+                doc! {"_id": 1, "a": 1, "b": {"c": 2}, "e": [{"c": 4}]}
+            );
+            let projected = projection(py, c"['a']").unwrap().apply(doc.clone());
+            assert_eq!(projected, doc! {"_id": 1, "a": 1});
+            let projected = projection(py, c"{'a': 1, '_id': 0}").unwrap().apply(doc);
+            assert_eq!(projected, doc! {"a": 1});
+        });
+    }
+
+    #[test]
+    fn exclusion_projection() {
+        Python::attach(|py| {
+            let doc = doc! {"_id": 1, "a": 1, "b": {"c": 2, "d": 3}, "e": [{"c": 4}, 6]};
+            let projected = projection(py, c"{'a': 0, 'b.c': 0, 'e.c': 0}")
+                .unwrap()
+                .apply(doc.clone());
+            assert_eq!(projected, doc! {"_id": 1, "b": {"d": 3}, "e": [{}, 6]});
+            let projected = projection(py, c"{'_id': 0}").unwrap().apply(doc);
+            assert!(!projected.contains_key("_id"));
+            assert_eq!(projected.len(), 3);
+        });
+    }
+
+    #[test]
+    fn invalid_projections() {
+        Python::attach(|py| {
+            assert!(projection(py, c"{'a': 1, 'b': 0}").is_err());
+            assert!(projection(py, c"{'a': 1, 'a.b': 1}").is_err());
+            assert!(projection(py, c"{'a': 'yes'}").is_err());
+            assert!(projection(py, c"'a'").is_err());
+        });
+    }
+
+    #[test]
+    fn return_document_parsing() {
+        Python::attach(|py| {
+            let parse = |code: &std::ffi::CStr| {
+                convert_py_obj_to_return_document(Some(&py.eval(code, None, None).unwrap()))
+            };
+            assert!(!convert_py_obj_to_return_document(None).unwrap());
+            assert!(parse(c"True").unwrap());
+            assert!(!parse(c"'Before'").unwrap());
+            assert!(parse(c"'after'").unwrap());
+            assert!(parse(c"'later'").is_err());
+        });
+    }
+
+    #[test]
+    fn update_validation() {
+        assert!(validate_update(&doc! {"$set": {"a": 1}}).is_ok());
+        assert!(validate_update(&doc! {}).is_err());
+        assert!(validate_update(&doc! {"$set": {"a": 1}, "b": 2}).is_err());
+    }
+
+    #[test]
+    fn upsert_seed_keeps_equalities() {
+        let filter = doc! {
+            "a": 1,
+            "b.c": "x",
+            "d": {"$eq": 2},
+            "e": {"$gt": 3},
+            "$and": [{"f": true}, {"g": {"$in": [1]}}],
+            "$or": [{"h": 1}],
+        };
+        assert_eq!(
+            upsert_seed(&filter),
+            doc! {"a": 1, "b": {"c": "x"}, "d": 2, "f": true}
+        );
+    }
+
+    #[test]
+    fn replacement_keeps_the_id_first() {
+        assert!(validate_replacement(&doc! {"a": {"$set": 1}}).is_ok());
+        assert!(validate_replacement(&doc! {"a": 1, "$set": {"b": 1}}).is_err());
+        let id = Bson::Int32(1);
+        assert_eq!(
+            replacement_with_id(doc! {"a": 1, "_id": 1}, Some(&id)).unwrap(),
+            doc! {"_id": 1, "a": 1}
+        );
+        assert_eq!(
+            replacement_with_id(doc! {"a": 1}, None).unwrap(),
+            doc! {"a": 1}
+        );
+        assert!(replacement_with_id(doc! {"_id": 2}, Some(&id)).is_err());
+    }
+
+    #[test]
+    fn distinct_values_are_ordered_and_deduplicated() {
+        let mut values = DistinctValues::default();
+        for doc in [
+            doc! {"a": {"b": [3, 1]}},
+            doc! {"a": {"b": 1.0}},
+            doc! {"a": [{"b": "x"}, {"b": 2_i64}]},
+            doc! {"a": {"c": 1}},
+            doc! {"a": {"b": null}},
+        ] {
+            values.add(&doc, "a.b");
+        }
+        assert_eq!(
+            values.into_values(),
+            vec![
+                Bson::Null,
+                Bson::Int32(1),
+                Bson::Int64(2),
+                Bson::Int32(3),
+                Bson::String("x".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn bson_comparison() {
+        assert_eq!(
+            compare_bson(&Bson::Int32(1), &Bson::Double(1.0)),
+            Ordering::Equal
+        );
+        assert_eq!(
+            compare_bson(&Bson::Int64(2), &Bson::Double(1.5)),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare_bson(&Bson::Int32(9), &Bson::String("1".to_string())),
+            Ordering::Less
+        );
+        assert_eq!(compare_bson(&Bson::Null, &Bson::Int32(0)), Ordering::Less);
+    }
+
+    #[test]
+    fn wide_integers_compare_exactly_with_doubles() {
+        let big = 1i64 << 53;
+        let close = [
+            Bson::Int64(big),
+            Bson::Double(big as f64),
+            Bson::Int64(big + 1),
+        ];
+        // 2^53 + 1 rounds to 2^53 as a double, but still sorts after it
+        assert_eq!(compare_bson(&close[0], &close[1]), Ordering::Equal);
+        assert_eq!(compare_bson(&close[1], &close[2]), Ordering::Less);
+        assert_eq!(compare_bson(&close[2], &close[1]), Ordering::Greater);
+        assert_eq!(
+            compare_bson(&Bson::Int64(i64::MAX), &Bson::Double(i64::MAX as f64)),
+            Ordering::Less
+        );
+        assert_eq!(
+            compare_bson(&Bson::Int64(i64::MIN), &Bson::Double(i64::MIN as f64)),
+            Ordering::Equal
+        );
+        assert_eq!(
+            compare_bson(&Bson::Int64(-3), &Bson::Double(-2.5)),
+            Ordering::Less
+        );
+        assert_eq!(
+            compare_bson(&Bson::Int32(0), &Bson::Double(f64::NAN)),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare_bson(&Bson::Double(f64::NEG_INFINITY), &Bson::Int64(i64::MIN)),
+            Ordering::Less
+        );
+        let mut values = DistinctValues::default();
+        values.add(&doc! {"v": close.to_vec()}, "v");
+        assert_eq!(
+            values.into_values(),
+            vec![Bson::Int64(big), Bson::Int64(big + 1)]
+        );
+    }
+}
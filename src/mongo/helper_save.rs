@@ -0,0 +1,168 @@
+use crate::mongo::py_errors::write_error;
+use crate::mongo::py_index::{INDEX_CATALOG, create_index, list_indexes};
+use polodb_core::bson::{Document, doc};
+use polodb_core::{CollectionT, Database, Transaction};
+use pyo3::exceptions::{PyFileExistsError, PyOSError};
+use pyo3::prelude::*;
+use std::path::Path;
+
+/// Write a copy of `source` to a new database at `path`, every collection with its documents
+/// and indexes. The copy is made in one transaction on each side, so it is consistent even
+/// while others write.
+pub fn save_database(source: &Database, path: &Path) -> PyResult<()> {
+    if path.exists() {
+        return Err(PyFileExistsError::new_err(format!(
+            "Save error: {} already exists",
+            path.display()
+        )));
+    }
+    let modify_error = |e: polodb_core::Error| write_error("Save", e);
+    let names = source.list_collection_names().map_err(modify_error)?;
+    let target = Database::open_path(path).map_err(|e| PyOSError::new_err(e.to_string()))?;
+    let copied = copy_collections(source, &target, &names, modify_error);
+    drop(target);
+    if copied.is_err() {
+        // Leave nothing half written behind
+        let _ = std::fs::remove_dir_all(path);
+    }
+    copied
+}
+
+fn copy_collections(
+    source: &Database,
+    target: &Database,
+    names: &[String],
+    modify_error: impl Fn(polodb_core::Error) -> PyErr + Copy,
+) -> PyResult<()> {
+    for name in names.iter().filter(|name| *name != INDEX_CATALOG) {
+        target.create_collection(name).map_err(modify_error)?;
+    }
+    let read_txn = source.start_transaction().map_err(modify_error)?;
+    let write_txn = target.start_transaction().map_err(modify_error)?;
+    let copied = names
+        .iter()
+        .filter(|name| *name != INDEX_CATALOG)
+        .try_for_each(|name| copy_collection(&read_txn, &write_txn, name, name, modify_error));
+    let _ = read_txn.rollback();
+    match copied {
+        Ok(()) => write_txn.commit().map_err(modify_error),
+        Err(err) => {
+            let _ = write_txn.rollback();
+            Err(err)
+        }
+    }
+}
+
+/// Copy the documents and indexes of `from`, read in `source`, to `to` in `target`.
+pub fn copy_collection(
+    source: &Transaction,
+    target: &Transaction,
+    from: &str,
+    to: &str,
+    modify_error: impl Fn(polodb_core::Error) -> PyErr + Copy,
+) -> PyResult<()> {
+    let mut cursor = source
+        .collection::<Document>(from)
+        .find(doc! {})
+        .run()
+        .map_err(modify_error)?;
+    let collection = target.collection::<Document>(to);
+    while cursor.advance().map_err(modify_error)? {
+        let doc = cursor.deserialize_current().map_err(modify_error)?;
+        collection.insert_one(doc).map_err(modify_error)?;
+    }
+    for spec in list_indexes(source, from, modify_error)? {
+        create_index(target, to, &spec, modify_error)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mongo::py_index::{IndexSpec, create_collection, drop_collection};
+    use crate::mongo::test_dir::TestDir;
+
+    #[test]
+    fn rename_by_copy_is_one_transaction() {
+        let dir = TestDir::new("save_rename");
+        let database = dir.open();
+        let modify_error = |e: polodb_core::Error| write_error("Test", e);
+        let txn = database.start_transaction().unwrap();
+        let spec = IndexSpec {
+            name: "sku_1".to_string(),
+            field: "sku".to_string(),
+            unique: true,
+            expire_after_seconds: None,
+            sparse: false,
+        };
+        create_index(&txn, "stock", &spec, modify_error).unwrap();
+        txn.collection::<Document>("stock")
+            .insert_many(vec![
+                doc! {"_id": 1, "sku": "a"},
+                doc! {"_id": 2, "sku": "b"},
+            ])
+            .unwrap();
+        create_collection(&txn, "empty", modify_error).unwrap();
+        txn.commit().unwrap();
+        drop(txn);
+
+        // Rolled back, nothing is left of the targets
+        let txn = database.start_transaction().unwrap();
+        for (from, to) in [("stock", "stock_2"), ("empty", "empty_2")] {
+            create_collection(&txn, to, modify_error).unwrap();
+            copy_collection(&txn, &txn, from, to, modify_error).unwrap();
+            drop_collection(&txn, from, modify_error).unwrap();
+        }
+        txn.rollback().unwrap();
+        drop(txn);
+        let names = |database: &Database| {
+            let mut names = database.list_collection_names().unwrap();
+            names.retain(|name| name != INDEX_CATALOG);
+            names.sort();
+            names
+        };
+        assert_eq!(names(&database), vec!["empty", "stock"]);
+
+        let txn = database.start_transaction().unwrap();
+        for (from, to) in [("stock", "stock_2"), ("empty", "empty_2")] {
+            create_collection(&txn, to, modify_error).unwrap();
+            copy_collection(&txn, &txn, from, to, modify_error).unwrap();
+            drop_collection(&txn, from, modify_error).unwrap();
+        }
+        txn.commit().unwrap();
+        drop(txn);
+        assert_eq!(names(&database), vec!["empty_2", "stock_2"]);
+        let txn = database.start_transaction().unwrap();
+        assert!(list_indexes(&txn, "stock_2", modify_error).unwrap() == vec![spec]);
+        assert_eq!(
+            txn.collection::<Document>("stock_2")
+                .count_documents()
+                .unwrap(),
+            2
+        );
+        drop(txn);
+    }
+
+    #[test]
+    fn save_copies_every_collection() {
+        let dir = TestDir::new("save_source");
+        let target = TestDir::new("save_target");
+        let target = target.path();
+        let database = dir.open();
+        database
+            .collection::<Document>("items")
+            .insert_one(doc! {"_id": 1})
+            .unwrap();
+        database.create_collection("empty").unwrap();
+        save_database(&database, target).unwrap();
+        Python::attach(|py| {
+            let err = save_database(&database, target).unwrap_err();
+            assert!(err.is_instance_of::<PyFileExistsError>(py));
+        });
+        let saved = Database::open_path(target).unwrap();
+        let mut names = saved.list_collection_names().unwrap();
+        names.sort();
+        assert_eq!(names, vec!["empty", "items"]);
+    }
+}
@@ -0,0 +1,336 @@
+use crate::mongo::py_index::{ID_INDEX_NAME, INDEX_CATALOG, IndexSpec};
+use polodb_core::bson::{Bson, Document, doc};
+use polodb_core::{CollectionT, Transaction};
+use pyo3::exceptions::PyOSError;
+use pyo3::prelude::*;
+use std::collections::HashMap;
+use std::ops::{Add, Sub};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+/// The number of documents in a collection and their BSON size, or how much a write changed
+/// them.
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct CollectionSize {
+    pub count: i64,
+    pub size: i64,
+}
+
+impl CollectionSize {
+    /// The size of the single document `doc`.
+    pub fn of(doc: &Document) -> CollectionSize {
+        CollectionSize {
+            count: 1,
+            size: encoded_len(doc, &mut Vec::new()) as i64,
+        }
+    }
+}
+
+impl Add for CollectionSize {
+    type Output = CollectionSize;
+
+    fn add(self, other: CollectionSize) -> CollectionSize {
+        CollectionSize {
+            count: self.count + other.count,
+            size: self.size + other.size,
+        }
+    }
+}
+
+impl Sub for CollectionSize {
+    type Output = CollectionSize;
+
+    fn sub(self, other: CollectionSize) -> CollectionSize {
+        CollectionSize {
+            count: self.count - other.count,
+            size: self.size - other.size,
+        }
+    }
+}
+
+/// How a transaction changed the size of collections, by name. None for a collection that was
+/// dropped or renamed, which is counted again.
+#[derive(Default)]
+pub struct SizeChanges(HashMap<String, Option<CollectionSize>>);
+
+impl SizeChanges {
+    pub fn add(&mut self, collection: &str, change: CollectionSize) {
+        if let Some(total) = self
+            .0
+            .entry(collection.to_string())
+            .or_insert(Some(CollectionSize::default()))
+        {
+            *total = *total + change;
+        }
+    }
+
+    pub fn recount(&mut self, collection: &str) {
+        self.0.insert(collection.to_string(), None);
+    }
+
+    /// The change made to `collection`, None when it was not changed.
+    pub fn get(&self, collection: &str) -> Option<Option<CollectionSize>> {
+        self.0.get(collection).copied()
+    }
+
+    pub fn merge(&mut self, other: SizeChanges) {
+        for (collection, change) in other.0 {
+            match change {
+                Some(change) => self.add(&collection, change),
+                None => self.recount(&collection),
+            }
+        }
+    }
+}
+
+/// The size of the collections of a database. A collection is counted the first time it is
+/// asked for, with a full scan, and from then on kept current by the `SizeChanges` of the
+/// transactions that commit.
+#[derive(Default)]
+pub struct SizeCounters(Mutex<HashMap<String, CollectionSize>>);
+
+impl SizeCounters {
+    /// The size of `collection`, from `count` when it is not known yet. The counters stay
+    /// locked meanwhile, so a transaction committing during the count is not counted twice.
+    pub fn get_or_count(
+        &self,
+        collection: &str,
+        count: impl FnOnce() -> PyResult<CollectionSize>,
+    ) -> PyResult<CollectionSize> {
+        let mut sizes = self.lock();
+        if let Some(size) = sizes.get(collection) {
+            return Ok(*size);
+        }
+        let size = count()?;
+        sizes.insert(collection.to_string(), size);
+        Ok(size)
+    }
+
+    /// Apply the changes of a transaction that committed.
+    pub fn apply(&self, changes: SizeChanges) {
+        apply_changes(&mut self.lock(), changes);
+    }
+
+    /// Commit a transaction with `commit` and apply its `changes` when it succeeds, without a
+    /// count in between.
+    pub fn commit<E>(
+        &self,
+        changes: SizeChanges,
+        commit: impl FnOnce() -> Result<(), E>,
+    ) -> Result<(), E> {
+        let mut sizes = self.lock();
+        commit()?;
+        apply_changes(&mut sizes, changes);
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, CollectionSize>> {
+        // The counts a panicking thread left may be wrong, they are all taken again
+        self.0.lock().unwrap_or_else(|e| {
+            self.0.clear_poison();
+            let mut sizes = e.into_inner();
+            sizes.clear();
+            sizes
+        })
+    }
+}
+
+fn apply_changes(sizes: &mut HashMap<String, CollectionSize>, changes: SizeChanges) {
+    for (collection, change) in changes.0 {
+        match change {
+            Some(change) => {
+                // A collection not counted yet will include the change when it is
+                if let Some(size) = sizes.get_mut(&collection) {
+                    *size = *size + change;
+                }
+            }
+            None => {
+                sizes.remove(&collection);
+            }
+        }
+    }
+}
+
+/// Count the documents of `collection` as `txn` sees them and measure them, reading every one.
+pub fn measure_collection(
+    txn: &Transaction,
+    collection: &str,
+    modify_error: impl Fn(polodb_core::Error) -> PyErr,
+) -> PyResult<CollectionSize> {
+    let mut cursor = txn
+        .collection::<Document>(collection)
+        .find(doc! {})
+        .run()
+        .map_err(&modify_error)?;
+    let mut size = CollectionSize::default();
+    while cursor.advance().map_err(&modify_error)? {
+        size = size + CollectionSize::of(&cursor.deserialize_current().map_err(&modify_error)?);
+    }
+    Ok(size)
+}
+
+/// `PyDatabase.stats`: the size of the files at `path`, and the number of collections and
+/// documents, the latter from `size_of` each collection. RocksDB, under polodb, keeps no free
+/// page list, so `freePages` is None.
+pub fn database_stats(
+    path: &Path,
+    names: &[String],
+    mut size_of: impl FnMut(&str) -> PyResult<CollectionSize>,
+) -> PyResult<Document> {
+    let mut collections = 0;
+    let mut objects = 0;
+    for name in names.iter().filter(|name| *name != INDEX_CATALOG) {
+        collections += 1;
+        objects += size_of(name)?.count;
+    }
+    let file_size = directory_size(path)
+        .map_err(|e| PyOSError::new_err(format!("Stats error: {}: {}", path.display(), e)))?;
+    Ok(doc! {
+        "path": path.to_string_lossy().into_owned(),
+        "fileSize": file_size as i64,
+        "collections": collections as i64,
+        "objects": objects,
+        "freePages": Bson::Null,
+    })
+}
+
+/// `PyCollection.stats` of `collection`, whose documents measure `size`. polodb cannot tell
+/// the size of an index, so `totalIndexSize` and each of `indexSizes` are None.
+pub fn collection_stats(collection: &str, size: CollectionSize, specs: &[IndexSpec]) -> Document {
+    let mut index_sizes = doc! {ID_INDEX_NAME: Bson::Null};
+    for spec in specs {
+        index_sizes.insert(spec.name.clone(), Bson::Null);
+    }
+    doc! {
+        "ns": collection,
+        "count": size.count,
+        "size": size.size,
+        "avgObjSize": size.size.checked_div(size.count).unwrap_or(0),
+        "nindexes": specs.len() as i64 + 1,
+        "totalIndexSize": Bson::Null,
+        "indexSizes": index_sizes,
+    }
+}
+
+fn encoded_len(doc: &Document, buf: &mut Vec<u8>) -> usize {
+    buf.clear();
+    match doc.to_writer(&mut *buf) {
+        Ok(()) => buf.len(),
+        Err(_) => 0,
+    }
+}
+
+fn directory_size(path: &Path) -> std::io::Result<u64> {
+    let metadata = std::fs::metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        size += match directory_size(&entry?.path()) {
+            Ok(entry_size) => entry_size,
+            // RocksDB removes files as it compacts
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e),
+        };
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mongo::py_errors::write_error;
+    use crate::mongo::test_dir::TestDir;
+
+    #[test]
+    fn counters_follow_committed_changes() {
+        let counters = SizeCounters::default();
+        let size = |count| CollectionSize {
+            count,
+            size: count * 10,
+        };
+        let get = |name: &str, count: i64| counters.get_or_count(name, || Ok(size(count))).unwrap();
+        assert_eq!(get("a", 3), size(3));
+        // Counted once
+        assert_eq!(get("a", 0), size(3));
+
+        let changes = || {
+            let mut changes = SizeChanges::default();
+            changes.add("a", size(2));
+            changes.add("a", size(-1));
+            changes.add("b", size(5));
+            changes
+        };
+        assert!(counters.commit(changes(), || Err("failed")).is_err());
+        assert_eq!(get("a", 0), size(3));
+        counters.commit(changes(), || Ok::<_, ()>(())).unwrap();
+        assert_eq!(get("a", 0), size(4));
+        // Not counted before the commit, so the count includes it
+        assert_eq!(get("b", 7), size(7));
+
+        let mut changes = SizeChanges::default();
+        changes.add("a", size(1));
+        changes.recount("a");
+        counters.apply(changes);
+        assert_eq!(get("a", 9), size(9));
+    }
+
+    #[test]
+    fn collection_sizes_are_bson_sizes() {
+        let dir = TestDir::new("stats");
+        let database = dir.open();
+        let modify_error = |e: polodb_core::Error| write_error("Test", e);
+        let txn = database.start_transaction().unwrap();
+        let docs = vec![doc! {"_id": 1, "sku": "a"}, doc! {"_id": 2}];
+        txn.collection::<Document>("stock")
+            .insert_many(docs.clone())
+            .unwrap();
+        let mut buf = Vec::new();
+        let size = encoded_len(&docs[0], &mut buf) + encoded_len(&docs[1], &mut buf);
+        let measured = measure_collection(&txn, "stock", modify_error).unwrap();
+        assert_eq!(
+            measured,
+            CollectionSize {
+                count: 2,
+                size: size as i64
+            }
+        );
+        assert_eq!(
+            measured,
+            CollectionSize::of(&docs[0]) + CollectionSize::of(&docs[1])
+        );
+
+        let spec = IndexSpec {
+            name: "sku_1".to_string(),
+            field: "sku".to_string(),
+            unique: false,
+            expire_after_seconds: None,
+            sparse: false,
+        };
+        let stats = collection_stats("stock", measured, &[spec]);
+        assert_eq!(stats.get_i64("count").unwrap(), 2);
+        assert_eq!(stats.get_i64("size").unwrap(), size as i64);
+        assert_eq!(stats.get_i64("avgObjSize").unwrap(), size as i64 / 2);
+        assert_eq!(stats.get_i64("nindexes").unwrap(), 2);
+        assert_eq!(
+            stats.get_document("indexSizes").unwrap(),
+            &doc! {"_id_": Bson::Null, "sku_1": Bson::Null}
+        );
+        assert_eq!(stats.get("totalIndexSize"), Some(&Bson::Null));
+
+        let empty = collection_stats("missing", CollectionSize::default(), &[]);
+        assert_eq!(empty.get_i64("avgObjSize").unwrap(), 0);
+
+        let names = vec!["stock".to_string(), INDEX_CATALOG.to_string()];
+        let stats = database_stats(dir.path(), &names, |name| {
+            measure_collection(&txn, name, modify_error)
+        })
+        .unwrap();
+        assert_eq!(stats.get_i64("collections").unwrap(), 1);
+        assert_eq!(stats.get_i64("objects").unwrap(), 2);
+        assert!(stats.get_i64("fileSize").unwrap() > 0);
+        assert_eq!(stats.get("freePages"), Some(&Bson::Null));
+        txn.rollback().unwrap();
+    }
+}
@@ -0,0 +1,262 @@
+use crate::mongo::helper_stats::{CollectionSize, SizeChanges};
+use crate::mongo::py_database::SharedDatabase;
+use crate::mongo::py_errors::write_error;
+use crate::mongo::py_index::list_ttl_indexes;
+use polodb_core::bson::{Bson, DateTime, Document, doc};
+use polodb_core::{CollectionT, Transaction};
+use pyo3::exceptions::PyOSError;
+use pyo3::prelude::*;
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// How many documents `purge_expired` deletes per transaction. The database lock is released
+/// between batches, so a large purge does not hold up other callers.
+const PURGE_BATCH_SIZE: usize = 1000;
+
+/// Delete every document a TTL index says has expired, in transactions of `PURGE_BATCH_SIZE`
+/// documents. A document expires `expireAfterSeconds` after the date in its indexed field,
+/// documents without a date there are kept, and so are the ones an open transaction has written,
+/// until a later purge. A failing batch leaves the earlier ones deleted. Returns how many were
+/// deleted.
+pub fn purge_expired(shared: &SharedDatabase) -> PyResult<u64> {
+    let modify_error = |e: polodb_core::Error| write_error("Purge expired", e);
+    let now = DateTime::now();
+    let mut deleted = 0;
+    loop {
+        let batch = shared.with_database(|database| {
+            // Deleting a document another transaction wrote would block on it, see
+            // `PyCollection::write_transaction`
+            let sessions = shared.writing_sessions(None);
+            let claimed = |collection: &str, id: &Bson| {
+                sessions
+                    .iter()
+                    .any(|session| session.has_written(collection, id))
+            };
+            let txn = database.start_transaction().map_err(modify_error)?;
+            let mut changes = SizeChanges::default();
+            match delete_expired(
+                &txn,
+                now,
+                PURGE_BATCH_SIZE,
+                &claimed,
+                &mut changes,
+                modify_error,
+            ) {
+                Ok(batch) => {
+                    txn.commit().map_err(modify_error)?;
+                    shared.sizes().apply(changes);
+                    Ok(batch)
+                }
+                Err(err) => {
+                    let _ = txn.rollback();
+                    Err(err)
+                }
+            }
+        })?;
+        deleted += batch as u64;
+        if batch < PURGE_BATCH_SIZE {
+            return Ok(deleted);
+        }
+    }
+}
+
+/// Delete up to `limit` expired documents, leaving the ones `claimed` says an open transaction
+/// has written. Returns how many were deleted.
+fn delete_expired(
+    txn: &Transaction,
+    now: DateTime,
+    limit: usize,
+    claimed: &dyn Fn(&str, &Bson) -> bool,
+    changes: &mut SizeChanges,
+    modify_error: impl Fn(polodb_core::Error) -> PyErr + Copy,
+) -> PyResult<usize> {
+    let mut deleted = 0;
+    for (name, spec) in list_ttl_indexes(txn, modify_error)? {
+        let Some(seconds) = spec.expire_after_seconds else {
+            continue;
+        };
+        if deleted == limit {
+            break;
+        }
+        let cutoff = DateTime::from_millis(
+            now.timestamp_millis()
+                .saturating_sub(seconds.saturating_mul(1000)),
+        );
+        let collection = txn.collection::<Document>(&name);
+        // polodb orders values of different types by their type number, the lower bound leaves
+        // out the ones ordered before dates
+        let mut cursor = collection
+            .find(doc! {&spec.field: {"$gte": DateTime::MIN, "$lte": cutoff}})
+            .run()
+            .map_err(modify_error)?;
+        let mut expired = Vec::new();
+        let mut expired_size = CollectionSize::default();
+        while deleted + expired.len() < limit && cursor.advance().map_err(modify_error)? {
+            let doc = cursor.deserialize_current().map_err(modify_error)?;
+            let id = doc.get("_id").cloned().unwrap_or(Bson::Null);
+            if !claimed(&name, &id) {
+                expired.push(id);
+                expired_size = expired_size + CollectionSize::of(&doc);
+            }
+        }
+        if !expired.is_empty() {
+            deleted += collection
+                .delete_many(doc! {"_id": {"$in": expired}})
+                .map_err(modify_error)?
+                .deleted_count as usize;
+            changes.add(&name, CollectionSize::default() - expired_size);
+        }
+    }
+    Ok(deleted)
+}
+
+/// Background thread calling `purge_expired` every `interval`, stopped when dropped. It only
+/// holds a weak reference, so it never keeps the database open.
+pub struct TtlMonitor {
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl TtlMonitor {
+    pub fn start(database: Weak<SharedDatabase>, interval: Duration) -> PyResult<TtlMonitor> {
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let thread_stop = stop.clone();
+        let thread = std::thread::Builder::new()
+            .name("mongo_emb-ttl".to_string())
+            .spawn(move || {
+                let (stopped, wake) = &*thread_stop;
+                loop {
+                    let Ok(guard) = stopped.lock() else {
+                        return;
+                    };
+                    let Ok((guard, _)) = wake.wait_timeout_while(guard, interval, |stop| !*stop)
+                    else {
+                        return;
+                    };
+                    if *guard {
+                        return;
+                    }
+                    drop(guard);
+                    let Some(database) = database.upgrade() else {
+                        return;
+                    };
+                    // A failed round is retried on the next one, there is no caller to tell
+                    let _ = purge_expired(&database);
+                }
+            })
+            .map_err(|e| PyOSError::new_err(format!("Failed to start the TTL monitor: {}", e)))?;
+        Ok(TtlMonitor {
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for TtlMonitor {
+    fn drop(&mut self) {
+        let (stopped, wake) = &*self.stop;
+        if let Ok(mut stop) = stopped.lock() {
+            *stop = true;
+        }
+        wake.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mongo::py_index::{IndexSpec, create_index};
+    use crate::mongo::test_dir::TestDir;
+
+    #[test]
+    fn expired_documents_are_deleted() {
+        let dir = TestDir::new("ttl");
+        let database = dir.open();
+        let modify_error = |e: polodb_core::Error| write_error("Test", e);
+        let txn = database.start_transaction().unwrap();
+        let spec = IndexSpec {
+            name: "at_1".to_string(),
+            field: "at".to_string(),
+            unique: false,
+            expire_after_seconds: Some(10),
+            sparse: false,
+        };
+        create_index(&txn, "sessions", &spec, modify_error).unwrap();
+        let sessions = txn.collection::<Document>("sessions");
+        sessions
+            .insert_many(vec![
+                doc! {"_id": 1, "at": DateTime::from_millis(0)},
+                doc! {"_id": 2, "at": DateTime::from_millis(5_000)},
+                doc! {"_id": 3, "at": "not a date"},
+                doc! {"_id": 4},
+                doc! {"_id": 5, "at": true},
+                doc! {"_id": 6, "at": 7},
+            ])
+            .unwrap();
+
+        let now = DateTime::from_millis(15_000);
+        let unclaimed = |_: &str, _: &Bson| false;
+        let mut changes = SizeChanges::default();
+        assert_eq!(
+            delete_expired(&txn, now, 10, &unclaimed, &mut changes, modify_error).unwrap(),
+            2
+        );
+        let expired = CollectionSize::of(&doc! {"_id": 1, "at": DateTime::from_millis(0)})
+            + CollectionSize::of(&doc! {"_id": 2, "at": DateTime::from_millis(5_000)});
+        assert_eq!(
+            changes.get("sessions"),
+            Some(Some(CollectionSize::default() - expired))
+        );
+        assert_eq!(sessions.count_documents().unwrap(), 4);
+        assert_eq!(
+            delete_expired(&txn, now, 10, &unclaimed, &mut changes, modify_error).unwrap(),
+            0
+        );
+        drop(sessions);
+    }
+
+    #[test]
+    fn expired_documents_are_deleted_in_batches_around_claimed_ones() {
+        let dir = TestDir::new("ttl_batches");
+        let database = dir.open();
+        let modify_error = |e: polodb_core::Error| write_error("Test", e);
+        let txn = database.start_transaction().unwrap();
+        let spec = IndexSpec {
+            name: "at_1".to_string(),
+            field: "at".to_string(),
+            unique: false,
+            expire_after_seconds: Some(0),
+            sparse: false,
+        };
+        create_index(&txn, "sessions", &spec, modify_error).unwrap();
+        let sessions = txn.collection::<Document>("sessions");
+        sessions
+            .insert_many((1..=5).map(|id| doc! {"_id": id, "at": DateTime::from_millis(0)}))
+            .unwrap();
+
+        let now = DateTime::from_millis(1_000);
+        let claimed =
+            |collection: &str, id: &Bson| collection == "sessions" && *id == Bson::Int32(1);
+        let mut changes = SizeChanges::default();
+        let mut batch =
+            || delete_expired(&txn, now, 3, &claimed, &mut changes, modify_error).unwrap();
+        assert_eq!(batch(), 3);
+        assert_eq!(batch(), 1);
+        assert_eq!(batch(), 0);
+        assert_eq!(
+            sessions
+                .find(doc! {})
+                .run()
+                .unwrap()
+                .next()
+                .unwrap()
+                .unwrap(),
+            doc! {"_id": 1, "at": DateTime::from_millis(0)}
+        );
+        drop(sessions);
+    }
+}
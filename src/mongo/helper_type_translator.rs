@@ -1,207 +1,1280 @@
-use polodb_core::bson::{Bson, Document};
-use polodb_core::results;
+use crate::mongo::codec_registry::{CodecMarker, CodecRegistry, TAG_KEY, TAG_VALUE_KEY, TypeCodec};
+use crate::mongo::py_bson_types::{
+    PyBinary, PyCode, PyDbPointer, PyDecimal128, PyMaxKey, PyMinKey, PyObjectId, PyRawDocument,
+    PySymbol, PyTimestamp, PyUndefined, int32_value, new_int32,
+};
+use polodb_core::bson::spec::BinarySubtype;
+use polodb_core::bson::{
+    Binary, Bson, DateTime, Decimal128, Document, JavaScriptCodeWithScope, RawDocumentBuf, Regex,
+    Timestamp, doc,
+};
+use pyo3::exceptions::{PyOverflowError, PyTypeError, PyValueError};
 use pyo3::prelude::*;
+use pyo3::sync::PyOnceLock;
 use pyo3::types::PyDict;
-use pyo3::types::{PyAny, PyBool, PyBytes, PyFloat, PyList, PyString};
-
-pub fn convert_py_list_to_vec_document<'a>(py_list_obj: &'a Py<PyAny>) -> Vec<Document> {
-    Python::attach(|py| {
-        // Try to downcast the PyAny to a PyList
-        if let Ok(py_list) = py_list_obj.cast_bound::<PyList>(py) {
-            // If downcast is successful, return an iterator over the list's items
-            let iter = py_list.iter().map(|item| {
-                // let py_obj: Py<PyAny> = item.to_object(item.py());
-                let py_obj2 = item.into_pyobject(py).unwrap();
-                // Convert each item (expected to be a dictionary) into a BSON document
-
-                convert_py_obj_to_document(py_obj2.as_unbound()).unwrap()
-            });
-            Vec::from_iter(iter)
-        } else {
-            Vec::from_iter(std::iter::empty())
+use pyo3::types::{
+    PyAny, PyBool, PyByteArray, PyBytes, PyFloat, PyFrozenSet, PyInt, PyList, PyMemoryView, PySet,
+    PyString, PyTuple, PyType,
+};
+use std::sync::Arc;
+
+/// Python `re` flags and the BSON regex options they map to, in BSON (alphabetical) order.
+const REGEX_FLAGS: [(&str, char); 4] = [
+    ("IGNORECASE", 'i'),
+    ("MULTILINE", 'm'),
+    ("DOTALL", 's'),
+    ("VERBOSE", 'x'),
+];
+
+/// Options controlling how Python objects and BSON values are converted into each other.
+#[derive(Default)]
+pub struct CodecOptions {
+    /// Return timezone-aware datetimes (in UTC unless `tzinfo` is set).
+    pub tz_aware: bool,
+    /// Timezone that datetimes are converted to on read, implies `tz_aware`.
+    pub tzinfo: Option<Py<PyAny>>,
+    /// Only convert values that read back as the same type: tuples, sets, dates, subclasses of
+    /// built-in types and objects that merely implement `__index__` or `__float__` are rejected,
+    /// and Decimal128 values are read back as `Decimal128`.
+    pub strict: bool,
+    /// Custom codecs for types the translator does not know.
+    pub codecs: Arc<CodecRegistry>,
+}
+
+impl CodecOptions {
+    /// The same options with another codec registry, used to give a collection its own codecs.
+    pub fn with_codecs(&self, py: Python, codecs: Arc<CodecRegistry>) -> CodecOptions {
+        CodecOptions {
+            tz_aware: self.tz_aware,
+            tzinfo: self.tzinfo.as_ref().map(|tzinfo| tzinfo.clone_ref(py)),
+            strict: self.strict,
+            codecs,
         }
-    })
+    }
+}
+
+pub fn convert_py_list_to_vec_document(
+    py_list: &Bound<'_, PyAny>,
+    path: &str,
+    opts: &CodecOptions,
+) -> PyResult<Vec<Document>> {
+    let list = py_list.cast::<PyList>().map_err(|_| {
+        PyTypeError::new_err(format!(
+            "{}: expected a list of documents, got '{}'",
+            path,
+            type_name(py_list)
+        ))
+    })?;
+    list.iter()
+        .enumerate()
+        .map(|(index, item)| {
+            convert_py_obj_to_document(&item, &format!("{}[{}]", path, index), opts)
+        })
+        .collect()
+}
+
+pub fn convert_py_obj_to_document(
+    py_obj: &Bound<'_, PyAny>,
+    path: &str,
+    opts: &CodecOptions,
+) -> PyResult<Document> {
+    if let Ok(raw) = py_obj.cast::<PyRawDocument>() {
+        return raw_document_to_document(&raw.get().raw, path);
+    }
+    let dict = py_obj.cast::<PyDict>().map_err(|_| {
+        PyTypeError::new_err(format!(
+            "{}: expected a dict, got '{}'",
+            path,
+            type_name(py_obj)
+        ))
+    })?;
+    convert_py_dict_to_document(dict, path, opts)
 }
 
-pub fn convert_py_obj_to_document(py_obj: &Py<PyAny>) -> PyResult<Document> {
-    Python::attach(|py| {
-        // Try to extract as a String and convert to BSON
-        //    let mut doc: Document = Document::new();
-        if let Ok(dict) = py_obj.cast_bound::<PyDict>(py) {
-            // let dict_ref = dict.borrow(); // Convert Py<PyDict> to &PyDict
-            let mut doc = Document::new();
-            for (key, value) in dict.iter() {
-                // Use `iter()` on the `PyDict`
-                let key: String = key.extract()?; // Extract the key as a string
-                let bson_value =
-                    convert_py_obj_to_bson(value.into_pyobject(py).unwrap().as_unbound())?; // Convert value to BSON
-                doc.insert(key, bson_value);
+/// Decode raw BSON into a document, e.g. for `insert_raw_many` or a `RawDocument` value.
+pub fn raw_document_to_document(raw: &RawDocumentBuf, path: &str) -> PyResult<Document> {
+    raw.to_document()
+        .map_err(|e| PyValueError::new_err(format!("{}: invalid BSON document: {}", path, e)))
+}
+
+fn convert_py_dict_to_document(
+    dict: &Bound<'_, PyDict>,
+    path: &str,
+    opts: &CodecOptions,
+) -> PyResult<Document> {
+    let mut doc = Document::new();
+    for (key, value) in dict.iter() {
+        let key: String = key.extract().map_err(|_| {
+            PyTypeError::new_err(format!(
+                "{}: document keys must be str, got '{}'",
+                path,
+                type_name(&key)
+            ))
+        })?;
+        let bson_value = convert_py_obj_to_bson(&value, &format!("{}.{}", path, key), opts)?;
+        doc.insert(key, bson_value);
+    }
+    Ok(doc)
+}
+
+/// Convert a query filter, rewriting it into the form polodb evaluates: a bare regex value
+/// becomes a `$regex` condition and pymongo style `{"$regex": str, "$options": str}` pairs are
+/// folded into a single BSON regex.
+pub fn convert_py_obj_to_filter(
+    py_obj: &Bound<'_, PyAny>,
+    path: &str,
+    opts: &CodecOptions,
+) -> PyResult<Document> {
+    convert_py_obj_to_document(py_obj, path, opts).map(normalize_filter)
+}
+
+fn normalize_filter(filter: Document) -> Document {
+    let mut filter = fold_regex_options(filter);
+    let mut clauses = Vec::new();
+    let mut emptied = Vec::new();
+    for (key, value) in filter.iter_mut() {
+        let taken = std::mem::replace(value, Bson::Null);
+        *value = normalize_filter_value(key, taken);
+        if let Bson::Document(condition) = value
+            && !key.starts_with('$')
+            && is_condition(condition)
+        {
+            clauses.extend(lift_regex_items(key, condition));
+            if condition.is_empty() {
+                emptied.push(key.clone());
             }
-            Ok(doc)
         }
-        // If the type is not supported, return an error
-        else {
-            Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
-                "Unsupported Python type for BSON conversion",
-            ))
+    }
+    for key in emptied {
+        filter.remove(&key);
+    }
+    if !clauses.is_empty() {
+        match filter.get_mut("$and") {
+            Some(Bson::Array(and)) => and.extend(clauses),
+            _ => {
+                filter.insert("$and", clauses);
+            }
         }
-    })
+    }
+    filter
 }
 
-pub fn convert_py_obj_to_bson(py_obj: &Py<PyAny>) -> PyResult<Bson> {
-    Python::attach(|py| {
-        // Try to extract as a String and convert to BSON
-        if let Ok(rust_string) = py_obj.extract::<String>(py) {
-            Ok(Bson::String(rust_string))
+/// polodb compares the items of `$in`, `$nin` and `$all` by equality, so a regex among them
+/// would never match. They are taken out of the `condition` on `field` and returned as
+/// `$regex` conditions to add to `$and`: one of them or the other `$in` items must match, none
+/// of the `$nin` ones and all of the `$all` ones.
+fn lift_regex_items(field: &str, condition: &mut Document) -> Vec<Bson> {
+    let mut clauses = Vec::new();
+    for operator in ["$in", "$nin", "$all"] {
+        let Some(Bson::Array(items)) = condition.get(operator) else {
+            continue;
+        };
+        let (regexes, rest): (Vec<Bson>, Vec<Bson>) = items
+            .iter()
+            .cloned()
+            .partition(|item| matches!(item, Bson::RegularExpression(_)));
+        if regexes.is_empty() {
+            continue;
+        }
+        condition.remove(operator);
+        let matches = |regex: Bson| Bson::Document(doc! {field: {"$regex": regex}});
+        match operator {
+            "$in" => {
+                let mut alternatives: Vec<Bson> = regexes.into_iter().map(matches).collect();
+                if !rest.is_empty() {
+                    alternatives.push(Bson::Document(doc! {field: {"$in": rest}}));
+                }
+                clauses.push(match alternatives.len() {
+                    1 => alternatives.remove(0),
+                    _ => Bson::Document(doc! {"$or": alternatives}),
+                });
+            }
+            "$nin" => {
+                if !rest.is_empty() {
+                    condition.insert(operator, rest);
+                }
+                clauses.extend(
+                    regexes
+                        .into_iter()
+                        .map(|regex| Bson::Document(doc! {field: {"$not": {"$regex": regex}}})),
+                );
+            }
+            _ => {
+                if !rest.is_empty() {
+                    condition.insert(operator, rest);
+                }
+                clauses.extend(regexes.into_iter().map(matches));
+            }
         }
-        // Try to extract as a bool and convert to BSON
-        else if let Ok(rust_bool) = py_obj.extract::<bool>(py) {
-            Ok(Bson::Boolean(rust_bool))
+    }
+    clauses
+}
+
+/// Normalize the value of `key` in a filter, recursing into logical operators, into the
+/// operators of a field condition and into `$not` and `$elemMatch`.
+fn normalize_filter_value(key: &str, value: Bson) -> Bson {
+    match value {
+        Bson::RegularExpression(_) if !key.starts_with('$') || key == "$not" => {
+            Bson::Document(doc! {
+                "$regex": value
+            })
         }
-        // Try to extract as an int (i64) and convert to BSON
-        else if let Ok(rust_int) = py_obj.extract::<i64>(py) {
-            Ok(Bson::Int64(rust_int))
+        Bson::Array(items) if matches!(key, "$and" | "$or" | "$nor") => Bson::Array(
+            items
+                .into_iter()
+                .map(|item| match item {
+                    Bson::Document(sub_filter) => Bson::Document(normalize_filter(sub_filter)),
+                    other => other,
+                })
+                .collect(),
+        ),
+        // `$all` may hold `{"$elemMatch": ...}` conditions, other array values are literals
+        Bson::Array(items) if key == "$all" => Bson::Array(
+            items
+                .into_iter()
+                .map(|item| match item {
+                    Bson::Document(condition) if is_condition(&condition) => {
+                        Bson::Document(normalize_filter(condition))
+                    }
+                    other => other,
+                })
+                .collect(),
+        ),
+        Bson::Document(sub_filter) if matches!(key, "$not" | "$elemMatch") => {
+            Bson::Document(normalize_filter(sub_filter))
         }
-        // Try to extract as a float and convert to BSON double
-        else if let Ok(rust_float) = py_obj.extract::<f64>(py) {
-            Ok(Bson::Double(rust_float))
+        // A document of operators is a condition, any other document is matched literally
+        Bson::Document(condition) if !key.starts_with('$') && is_condition(&condition) => {
+            Bson::Document(normalize_filter(condition))
         }
-        // Try to extract as a dictionary and convert to BSON document
-        else if let Ok(dict) = py_obj.cast_bound::<PyDict>(py) {
-            let mut bson_doc = Document::new();
-            for (key, value) in dict.iter() {
-                let key_str: String = key.extract::<String>()?;
+        other => other,
+    }
+}
 
-                let bson_value =
-                    convert_py_obj_to_bson(value.into_pyobject(py).unwrap().as_unbound())?;
-                bson_doc.insert(key_str, bson_value);
-            }
-            Ok(Bson::Document(bson_doc))
-        }
-        // Try to extract as a list and convert to BSON array
-        else if let Ok(list) = py_obj.cast_bound::<PyList>(py) {
-            let mut bson_array = Vec::new();
-            for item in list.iter() {
-                let bson_item =
-                    convert_py_obj_to_bson(item.into_pyobject(py).unwrap().as_unbound())?;
-                bson_array.push(bson_item);
+fn is_condition(doc: &Document) -> bool {
+    doc.keys().next().is_some_and(|key| key.starts_with('$'))
+}
+
+/// Fold a pymongo style `{"$regex": str, "$options": str}` pair into a single BSON regex.
+fn fold_regex_options(mut condition: Document) -> Document {
+    if let Ok(pattern) = condition.get_str("$regex").map(str::to_string) {
+        let mut options: Vec<char> = condition
+            .get_str("$options")
+            .unwrap_or_default()
+            .chars()
+            .collect();
+        options.sort_unstable();
+        condition.remove("$options");
+        condition.insert(
+            "$regex",
+            Bson::RegularExpression(Regex {
+                pattern,
+                options: options.into_iter().collect(),
+            }),
+        );
+    }
+    condition
+}
+
+/// Convert any supported Python value into BSON. `path` names the value in error messages,
+/// e.g. `docs[3].address.zip: unsupported type 'Foo'`.
+pub fn convert_py_obj_to_bson(
+    py_obj: &Bound<'_, PyAny>,
+    path: &str,
+    opts: &CodecOptions,
+) -> PyResult<Bson> {
+    // Custom codecs come first so they can also take over subclasses of built-in types
+    if !opts.codecs.is_empty()
+        && let Some(codec) = opts.codecs.encoder_for(py_obj)?
+    {
+        return encode_custom_type(py_obj, &codec, path, opts);
+    }
+    if opts.strict {
+        check_strict_type(py_obj).map_err(|err| prefix_path(py_obj.py(), err, path))?;
+    }
+    // Containers recurse and report errors at the path of the offending element
+    if let Ok(dict) = py_obj.cast::<PyDict>() {
+        convert_py_dict_to_document(dict, path, opts).map(Bson::Document)
+    } else if let Ok(list) = py_obj.cast::<PyList>() {
+        convert_py_items_to_bson_array(list.iter(), path, opts)
+    } else if let Ok(tuple) = py_obj.cast::<PyTuple>() {
+        convert_py_items_to_bson_array(tuple.iter(), path, opts)
+    } else if let Ok(set) = py_obj.cast::<PySet>() {
+        convert_py_items_to_bson_array(set.iter(), path, opts)
+    } else if let Ok(frozenset) = py_obj.cast::<PyFrozenSet>() {
+        convert_py_items_to_bson_array(frozenset.iter(), path, opts)
+    } else if let Ok(raw) = py_obj.cast::<PyRawDocument>() {
+        raw_document_to_document(&raw.get().raw, path).map(Bson::Document)
+    } else if let Ok(code) = py_obj.cast::<PyCode>() {
+        let code = code.get();
+        match &code.scope {
+            Some(scope) => Ok(Bson::JavaScriptCodeWithScope(JavaScriptCodeWithScope {
+                code: code.code.clone(),
+                scope: convert_py_dict_to_document(
+                    scope.bind(py_obj.py()),
+                    &format!("{}.scope", path),
+                    opts,
+                )?,
+            })),
+            None => Ok(Bson::JavaScriptCode(code.code.clone())),
+        }
+    } else {
+        convert_py_scalar_to_bson(py_obj, opts).map_err(|err| prefix_path(py_obj.py(), err, path))
+    }
+}
+
+/// In strict mode, refuse values that would be read back as another type: tuples, sets,
+/// `bytearray`, `memoryview` and subclasses of the built-in types.
+fn check_strict_type(py_obj: &Bound<'_, PyAny>) -> PyResult<()> {
+    let read_back_as = if py_obj.is_instance_of::<PyTuple>()
+        || py_obj.is_instance_of::<PySet>()
+        || py_obj.is_instance_of::<PyFrozenSet>()
+    {
+        "list"
+    } else if py_obj.is_instance_of::<PyByteArray>() || py_obj.is_instance_of::<PyMemoryView>() {
+        "bytes"
+    } else if py_obj.is_instance_of::<PyDict>() && !py_obj.is_exact_instance_of::<PyDict>() {
+        "dict"
+    } else if py_obj.is_instance_of::<PyList>() && !py_obj.is_exact_instance_of::<PyList>() {
+        "list"
+    } else if py_obj.is_instance_of::<PyString>() && !py_obj.is_exact_instance_of::<PyString>() {
+        "str"
+    } else if py_obj.is_instance_of::<PyInt>()
+        && !py_obj.is_exact_instance_of::<PyInt>()
+        && !py_obj.is_instance_of::<PyBool>()
+        && int32_value(py_obj).is_none()
+    {
+        "int"
+    } else if py_obj.is_instance_of::<PyFloat>() && !py_obj.is_exact_instance_of::<PyFloat>() {
+        "float"
+    } else if py_obj.is_instance_of::<PyBytes>() && !py_obj.is_exact_instance_of::<PyBytes>() {
+        "bytes"
+    } else {
+        return Ok(());
+    };
+    Err(strict_error(py_obj, read_back_as))
+}
+
+fn strict_error(py_obj: &Bound<'_, PyAny>, read_back_as: &str) -> PyErr {
+    PyTypeError::new_err(format!(
+        "strict mode does not store '{}', it would be read back as {}",
+        type_name(py_obj),
+        read_back_as
+    ))
+}
+
+/// Put `path` in front of the message of `err`. The new exception has the same type and `err` as
+/// its `__cause__`; when that type cannot be built from a message alone, `err` itself is returned
+/// with `path` added as a note.
+pub fn prefix_path(py: Python, err: PyErr, path: &str) -> PyErr {
+    let err_type = err.get_type(py);
+    let message = format!("{}: {}", path, err.value(py));
+    match err_type.call1((message,)) {
+        Ok(value) if value.is_instance(&err_type).unwrap_or(false) => {
+            let prefixed = PyErr::from_value(value);
+            prefixed.set_cause(py, Some(err));
+            prefixed
+        }
+        _ => {
+            // `add_note` is only there from Python 3.11 on, older versions keep the bare error
+            let _ = err
+                .value(py)
+                .call_method1("add_note", (format!("at {}", path),));
+            err
+        }
+    }
+}
+
+fn encode_custom_type(
+    py_obj: &Bound<'_, PyAny>,
+    codec: &TypeCodec,
+    path: &str,
+    opts: &CodecOptions,
+) -> PyResult<Bson> {
+    let py = py_obj.py();
+    let python_type = codec.python_type.bind(py);
+    let encoded = codec
+        .encoder
+        .bind(py)
+        .call1((py_obj,))
+        .map_err(|err| prefix_path(py, err, path))?;
+    if encoded.is_instance(python_type)? {
+        return Err(PyTypeError::new_err(format!(
+            "{}: encoder for '{}' returned another '{}'",
+            path,
+            python_type.name()?,
+            python_type.name()?
+        )));
+    }
+    match &codec.marker {
+        CodecMarker::Plain => convert_py_obj_to_bson(&encoded, path, opts),
+        CodecMarker::Subtype(subtype) => {
+            let Ok(bytes) = encoded.cast::<PyBytes>() else {
+                return Err(PyTypeError::new_err(format!(
+                    "{}: encoder for '{}' must return bytes, got '{}'",
+                    path,
+                    python_type.name()?,
+                    type_name(&encoded)
+                )));
+            };
+            Ok(Bson::Binary(Binary {
+                subtype: BinarySubtype::from(*subtype),
+                bytes: bytes.as_bytes().to_vec(),
+            }))
+        }
+        CodecMarker::Tag(tag) => Ok(Bson::Document(doc! {
+            TAG_KEY: tag.as_str(),
+            TAG_VALUE_KEY: convert_py_obj_to_bson(&encoded, path, opts)?,
+        })),
+    }
+}
+
+/// Decode a value written by a custom codec, `None` when `bson` is not one.
+fn decode_custom_type(py: Python, bson: &Bson, opts: &CodecOptions) -> PyResult<Option<Py<PyAny>>> {
+    let (codec, value) = match bson {
+        Bson::Binary(bin) => match opts.codecs.decoder_for_subtype(bin.subtype.into()) {
+            Some(codec) => (codec, PyBytes::new(py, &bin.bytes).into_any().unbind()),
+            None => return Ok(None),
+        },
+        Bson::Document(doc) if doc.len() == 2 => {
+            let (Ok(tag), Some(value)) = (doc.get_str(TAG_KEY), doc.get(TAG_VALUE_KEY)) else {
+                return Ok(None);
+            };
+            match opts.codecs.decoder_for_tag(tag) {
+                Some(codec) => (codec, bson_to_py_obj(py, value, opts)?),
+                None => return Ok(None),
             }
-            Ok(Bson::Array(bson_array))
         }
-        // If the type is not supported, return an error
-        else {
-            Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
-                "Unsupported Python type for BSON conversion",
+        _ => return Ok(None),
+    };
+    match &codec.decoder {
+        Some(decoder) => decoder
+            .bind(py)
+            .call1((value,))
+            .map(|obj| Some(obj.unbind())),
+        None => Ok(None),
+    }
+}
+
+fn convert_py_items_to_bson_array<'py>(
+    items: impl Iterator<Item = Bound<'py, PyAny>>,
+    path: &str,
+    opts: &CodecOptions,
+) -> PyResult<Bson> {
+    let mut bson_array = Vec::new();
+    for (index, item) in items.enumerate() {
+        bson_array.push(convert_py_obj_to_bson(
+            &item,
+            &format!("{}[{}]", path, index),
+            opts,
+        )?);
+    }
+    Ok(Bson::Array(bson_array))
+}
+
+fn convert_py_scalar_to_bson(py_obj: &Bound<'_, PyAny>, opts: &CodecOptions) -> PyResult<Bson> {
+    let py = py_obj.py();
+    // None is stored as BSON null
+    if py_obj.is_none() {
+        Ok(Bson::Null)
+    }
+    // Wrappers such as ObjectId, Int32 or Timestamp map back to their exact BSON value
+    else if let Some(bson_value) = py_bson_type_to_bson(py_obj) {
+        Ok(bson_value)
+    }
+    // Try to extract as a String and convert to BSON
+    else if let Ok(rust_string) = py_obj.extract::<String>() {
+        Ok(Bson::String(rust_string))
+    }
+    // Try to extract as a bool and convert to BSON
+    else if let Ok(rust_bool) = py_obj.extract::<bool>() {
+        Ok(Bson::Boolean(rust_bool))
+    }
+    // Python ints become Int64, anything wider cannot be stored
+    else if let Ok(int) = py_obj.cast::<PyInt>() {
+        int.extract::<i64>().map(Bson::Int64).map_err(|_| {
+            PyOverflowError::new_err(format!(
+                "Python int {} does not fit in a 64-bit BSON integer",
+                int
             ))
-        }
+        })
+    }
+    // Try to convert a decimal.Decimal to BSON Decimal128, before it is taken as a float
+    else if let Some(bson_decimal) = py_decimal_to_bson(py, py_obj)? {
+        Ok(bson_decimal)
+    }
+    // Try to extract as a float and convert to BSON double
+    else if let Ok(py_float) = py_obj.cast::<PyFloat>() {
+        Ok(Bson::Double(py_float.value()))
+    }
+    // Outside strict mode, accept int-like (`__index__`) and float-like (`__float__`) objects
+    else if !opts.strict
+        && let Ok(rust_int) = py_obj.extract::<i64>()
+    {
+        Ok(Bson::Int64(rust_int))
+    } else if !opts.strict
+        && let Ok(rust_float) = py_obj.extract::<f64>()
+    {
+        Ok(Bson::Double(rust_float))
+    }
+    // Try to extract as bytes-like data and convert to BSON binary
+    else if let Ok(bytes) = py_obj.cast::<PyBytes>() {
+        Ok(generic_binary(bytes.as_bytes().to_vec()))
+    } else if let Ok(bytearray) = py_obj.cast::<PyByteArray>() {
+        Ok(generic_binary(bytearray.to_vec()))
+    } else if let Ok(memoryview) = py_obj.cast::<PyMemoryView>() {
+        let bytes = memoryview.call_method0("tobytes")?;
+        Ok(generic_binary(bytes.cast::<PyBytes>()?.as_bytes().to_vec()))
+    }
+    // Try to convert a datetime.datetime / datetime.date to BSON DateTime
+    else if let Some(bson_datetime) = py_datetime_to_bson(py, py_obj, opts.strict)? {
+        Ok(bson_datetime)
+    }
+    // Try to convert a uuid.UUID to BSON binary subtype 4
+    else if let Some(bson_uuid) = py_uuid_to_bson(py, py_obj)? {
+        Ok(bson_uuid)
+    }
+    // Try to convert a compiled re.Pattern to a BSON regex
+    else if let Some(bson_regex) = py_regex_to_bson(py, py_obj, opts.strict)? {
+        Ok(bson_regex)
+    }
+    // If the type is not supported, return an error
+    else {
+        Err(PyTypeError::new_err(format!(
+            "unsupported type '{}'",
+            type_name(py_obj)
+        )))
+    }
+}
+
+fn type_name(py_obj: &Bound<'_, PyAny>) -> String {
+    py_obj
+        .get_type()
+        .name()
+        .map(|name| name.to_string())
+        .unwrap_or_else(|_| "<unknown>".to_string())
+}
+
+/// Convert one of the BSON wrapper pyclasses into the exact BSON value it stands for.
+fn py_bson_type_to_bson(obj: &Bound<'_, PyAny>) -> Option<Bson> {
+    if let Ok(oid) = obj.cast::<PyObjectId>() {
+        Some(Bson::ObjectId(oid.get().inner))
+    } else if let Some(int32) = int32_value(obj) {
+        Some(Bson::Int32(int32))
+    } else if let Ok(binary) = obj.cast::<PyBinary>() {
+        Some(Bson::Binary(binary.get().into()))
+    } else if let Ok(timestamp) = obj.cast::<PyTimestamp>() {
+        let timestamp = timestamp.get();
+        Some(Bson::Timestamp(Timestamp {
+            time: timestamp.time,
+            increment: timestamp.inc,
+        }))
+    } else if let Ok(decimal) = obj.cast::<PyDecimal128>() {
+        Some(Bson::Decimal128(Decimal128::from_bytes(
+            decimal.get().bytes,
+        )))
+    } else if let Ok(symbol) = obj.cast::<PySymbol>() {
+        Some(Bson::Symbol(symbol.get().symbol.clone()))
+    } else if let Ok(db_pointer) = obj.cast::<PyDbPointer>() {
+        Some(Bson::DbPointer(db_pointer.get().inner.clone()))
+    } else if obj.is_instance_of::<PyMinKey>() {
+        Some(Bson::MinKey)
+    } else if obj.is_instance_of::<PyMaxKey>() {
+        Some(Bson::MaxKey)
+    } else if obj.is_instance_of::<PyUndefined>() {
+        Some(Bson::Undefined)
+    } else {
+        None
+    }
+}
+
+fn generic_binary(bytes: Vec<u8>) -> Bson {
+    Bson::Binary(Binary {
+        subtype: BinarySubtype::Generic,
+        bytes,
     })
 }
 
-pub fn delete_result_to_pydict(
-    py: Python,
-    delete_result: results::DeleteResult,
-) -> PyResult<Py<PyDict>> {
-    let py_dict = PyDict::new(py);
+static DECIMAL_TYPE: PyOnceLock<Py<PyType>> = PyOnceLock::new();
+static UUID_TYPE: PyOnceLock<Py<PyType>> = PyOnceLock::new();
 
-    // Insert matched_count and modified_count into the PyDict
-    py_dict.set_item("deleted_count", delete_result.deleted_count as i64)?;
+/// Convert a `decimal.Decimal` into a BSON Decimal128, or `None` when the object is not a Decimal.
+fn py_decimal_to_bson(py: Python, obj: &Bound<'_, PyAny>) -> PyResult<Option<Bson>> {
+    if !obj.is_instance(DECIMAL_TYPE.import(py, "decimal", "Decimal")?)? {
+        return Ok(None);
+    }
+    let text = obj.str()?.to_string();
+    let decimal = text.parse::<Decimal128>().map_err(|e| {
+        PyValueError::new_err(format!(
+            "Decimal {} cannot be stored as Decimal128: {}",
+            text, e
+        ))
+    })?;
+    Ok(Some(Bson::Decimal128(decimal)))
+}
 
-    Ok(py_dict.into())
+/// Build a `decimal.Decimal` from its string form.
+pub fn py_decimal_from_str(py: Python, text: &str) -> PyResult<Py<PyAny>> {
+    Ok(DECIMAL_TYPE
+        .import(py, "decimal", "Decimal")?
+        .call1((text,))?
+        .unbind())
 }
 
-pub fn update_result_to_pydict(
-    py: Python,
-    update_result: results::UpdateResult,
-) -> PyResult<Py<PyDict>> {
-    let py_dict = PyDict::new(py);
+/// Convert a `uuid.UUID` into BSON binary subtype 4, or `None` when the object is not a UUID.
+fn py_uuid_to_bson(py: Python, obj: &Bound<'_, PyAny>) -> PyResult<Option<Bson>> {
+    if !obj.is_instance(UUID_TYPE.import(py, "uuid", "UUID")?)? {
+        return Ok(None);
+    }
+    let bytes = obj.getattr("bytes")?;
+    Ok(Some(Bson::Binary(Binary {
+        subtype: BinarySubtype::Uuid,
+        bytes: bytes.cast::<PyBytes>()?.as_bytes().to_vec(),
+    })))
+}
 
-    // Insert matched_count and modified_count into the PyDict
-    py_dict.set_item("matched_count", update_result.matched_count as i64)?;
-    py_dict.set_item("modified_count", update_result.modified_count as i64)?;
+static PATTERN_TYPE: PyOnceLock<Py<PyType>> = PyOnceLock::new();
+static RE_COMPILE: PyOnceLock<Py<PyAny>> = PyOnceLock::new();
+static RE_FLAGS: PyOnceLock<Vec<(i64, char)>> = PyOnceLock::new();
+static UNICODE_FLAG: PyOnceLock<Py<PyAny>> = PyOnceLock::new();
 
-    Ok(py_dict.into())
+/// The value of each `re` flag in `REGEX_FLAGS`, with its BSON option.
+fn regex_flags(py: Python) -> PyResult<&'static [(i64, char)]> {
+    RE_FLAGS
+        .get_or_try_init(py, || {
+            let re_module = py.import("re")?;
+            REGEX_FLAGS
+                .iter()
+                .map(|(name, option)| Ok((re_module.getattr(*name)?.extract()?, *option)))
+                .collect()
+        })
+        .map(Vec::as_slice)
 }
-pub fn document_to_pydict(py: Python, doc: Document) -> PyResult<Py<PyDict>> {
+
+/// Convert a compiled `re.Pattern` into a BSON regex, or `None` when the object is not a pattern.
+/// `strict` refuses flags other than `re.UNICODE` that have no BSON option.
+fn py_regex_to_bson(py: Python, obj: &Bound<'_, PyAny>, strict: bool) -> PyResult<Option<Bson>> {
+    if !obj.is_instance(PATTERN_TYPE.import(py, "re", "Pattern")?)? {
+        return Ok(None);
+    }
+    let pattern: String = obj
+        .getattr("pattern")?
+        .extract()
+        .map_err(|_| PyTypeError::new_err("Only str regex patterns can be stored as BSON"))?;
+    let flags: i64 = obj.getattr("flags")?.extract()?;
+    let mut options = String::new();
+    let mut unmapped = flags & !UNICODE_FLAG.import(py, "re", "UNICODE")?.extract::<i64>()?;
+    for &(flag, option) in regex_flags(py)? {
+        if flags & flag != 0 {
+            options.push(option);
+            unmapped &= !flag;
+        }
+    }
+    if strict && unmapped != 0 {
+        return Err(PyValueError::new_err(format!(
+            "strict mode does not store regex flags {:#x}, they have no BSON option",
+            unmapped
+        )));
+    }
+    Ok(Some(Bson::RegularExpression(Regex { pattern, options })))
+}
+
+/// Compile a BSON regex into a `re.Pattern`, translating its options back into `re` flags.
+fn bson_regex_to_py(py: Python, regex: &Regex) -> PyResult<Py<PyAny>> {
+    let mut flags: i64 = 0;
+    for &(flag, option) in regex_flags(py)? {
+        if regex.options.contains(option) {
+            flags |= flag;
+        }
+    }
+    Ok(RE_COMPILE
+        .import(py, "re", "compile")?
+        .call1((regex.pattern.as_str(), flags))?
+        .unbind())
+}
+
+static DATETIME_TYPE: PyOnceLock<Py<PyType>> = PyOnceLock::new();
+static DATE_TYPE: PyOnceLock<Py<PyType>> = PyOnceLock::new();
+static TIME_TYPE: PyOnceLock<Py<PyType>> = PyOnceLock::new();
+static TIMEDELTA_TYPE: PyOnceLock<Py<PyType>> = PyOnceLock::new();
+static NAIVE_EPOCH: PyOnceLock<Py<PyAny>> = PyOnceLock::new();
+static UTC_EPOCH: PyOnceLock<Py<PyAny>> = PyOnceLock::new();
+
+/// The Unix epoch as a `datetime`, timezone-aware (UTC) or naive.
+fn epoch(py: Python<'_>, aware: bool) -> PyResult<&Bound<'_, PyAny>> {
+    let datetime_type = DATETIME_TYPE.import(py, "datetime", "datetime")?;
+    let epoch = if aware {
+        UTC_EPOCH.get_or_try_init(py, || {
+            let utc = py.import("datetime")?.getattr("timezone")?.getattr("utc")?;
+            PyResult::Ok(datetime_type.call1((1970, 1, 1, 0, 0, 0, 0, utc))?.unbind())
+        })?
+    } else {
+        NAIVE_EPOCH.get_or_try_init(py, || {
+            PyResult::Ok(datetime_type.call1((1970, 1, 1))?.unbind())
+        })?
+    };
+    Ok(epoch.bind(py))
+}
+
+/// Convert a `datetime.datetime` or `datetime.date` into a BSON DateTime with millisecond
+/// precision. Aware values are normalized to UTC, naive values are assumed to be UTC already.
+/// `strict` refuses dates and sub-millisecond datetimes, which would not read back the same.
+/// Returns `None` when the object is neither.
+fn py_datetime_to_bson(py: Python, obj: &Bound<'_, PyAny>, strict: bool) -> PyResult<Option<Bson>> {
+    let datetime_type = DATETIME_TYPE.import(py, "datetime", "datetime")?;
+    let value = if obj.is_instance(datetime_type)? {
+        if strict && obj.getattr("microsecond")?.extract::<i64>()? % 1_000 != 0 {
+            return Err(PyValueError::new_err(
+                "strict mode does not store datetimes with microseconds, BSON keeps milliseconds",
+            ));
+        }
+        obj.clone()
+    } else if obj.is_instance(DATE_TYPE.import(py, "datetime", "date")?)? {
+        if strict {
+            return Err(strict_error(obj, "a datetime"));
+        }
+        let midnight = TIME_TYPE.import(py, "datetime", "time")?.call0()?;
+        datetime_type.call_method1("combine", (obj, midnight))?
+    } else {
+        return Ok(None);
+    };
+
+    let aware = !value.call_method0("utcoffset")?.is_none();
+    let delta = value.sub(epoch(py, aware)?)?;
+    let days: i64 = delta.getattr("days")?.extract()?;
+    let seconds: i64 = delta.getattr("seconds")?.extract()?;
+    let microseconds: i64 = delta.getattr("microseconds")?.extract()?;
+    let millis = days * 86_400_000 + seconds * 1_000 + microseconds / 1_000;
+
+    Ok(Some(Bson::DateTime(DateTime::from_millis(millis))))
+}
+
+/// Convert a BSON DateTime into a `datetime.datetime`, honouring the timezone settings of `opts`.
+pub fn bson_datetime_to_py(py: Python, dt: &DateTime, opts: &CodecOptions) -> PyResult<Py<PyAny>> {
+    let delta = TIMEDELTA_TYPE.import(py, "datetime", "timedelta")?.call1((
+        0,
+        0,
+        0,
+        dt.timestamp_millis(),
+    ))?;
+    let value = epoch(py, true)?.add(delta)?;
+
+    let value = match &opts.tzinfo {
+        Some(tz) => value.call_method1("astimezone", (tz,))?,
+        None if opts.tz_aware => value,
+        None => {
+            let kwargs = PyDict::new(py);
+            kwargs.set_item("tzinfo", py.None())?;
+            value.call_method("replace", (), Some(&kwargs))?
+        }
+    };
+    Ok(value.unbind())
+}
+
+pub fn document_to_pydict(py: Python, doc: &Document, opts: &CodecOptions) -> PyResult<Py<PyDict>> {
     let py_dict = PyDict::new(py);
     for (key, value) in doc {
-        let py_value = bson_to_py_obj(py, &value);
+        let py_value = bson_to_py_obj(py, value, opts)?;
         py_dict.set_item(key, py_value)?;
     }
     Ok(py_dict.into())
 }
 
-pub fn bson_to_py_obj(py: Python, bson: &Bson) -> Py<PyAny> {
-    match bson {
+/// Serialize BSON as MongoDB Extended JSON v2, canonical mode keeps every numeric type while
+/// relaxed mode writes plain JSON numbers and ISO-8601 dates where it can.
+pub fn bson_to_extended_json(bson: Bson, canonical: bool) -> serde_json::Value {
+    if canonical {
+        bson.into_canonical_extjson()
+    } else {
+        bson.into_relaxed_extjson()
+    }
+}
+
+pub fn extended_json_to_bson(text: &str, path: &str) -> PyResult<Bson> {
+    json_value_to_bson(parse_json(text, path)?, path)
+}
+
+/// Parse documents from a JSON array or from one document per line, as written by `mongoexport`.
+pub fn extended_json_to_documents(text: &str, path: &str) -> PyResult<Vec<Document>> {
+    let values: Vec<serde_json::Value> = if text.trim_start().starts_with('[') {
+        serde_json::from_str(text)
+            .map_err(|e| PyValueError::new_err(format!("{}: invalid JSON: {}", path, e)))?
+    } else {
+        text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .enumerate()
+            .map(|(index, line)| parse_json(line, &format!("{}[{}]", path, index)))
+            .collect::<PyResult<_>>()?
+    };
+    values
+        .into_iter()
+        .enumerate()
+        .map(|(index, value)| {
+            let path = format!("{}[{}]", path, index);
+            match json_value_to_bson(value, &path)? {
+                Bson::Document(doc) => Ok(doc),
+                other => Err(PyTypeError::new_err(format!(
+                    "{}: expected a document, got {:?}",
+                    path,
+                    other.element_type()
+                ))),
+            }
+        })
+        .collect()
+}
+
+fn parse_json(text: &str, path: &str) -> PyResult<serde_json::Value> {
+    serde_json::from_str(text)
+        .map_err(|e| PyValueError::new_err(format!("{}: invalid JSON: {}", path, e)))
+}
+
+fn json_value_to_bson(value: serde_json::Value, path: &str) -> PyResult<Bson> {
+    Bson::try_from(value)
+        .map_err(|e| PyValueError::new_err(format!("{}: invalid Extended JSON: {}", path, e)))
+}
+
+pub fn bson_to_py_obj(py: Python, bson: &Bson, opts: &CodecOptions) -> PyResult<Py<PyAny>> {
+    if !opts.codecs.is_empty()
+        && let Some(decoded) = decode_custom_type(py, bson, opts)?
+    {
+        return Ok(decoded);
+    }
+    let py_obj = match bson {
         Bson::Null => py.None(),
-        Bson::Int32(i) => i.into_pyobject(py).unwrap().into(),
-        Bson::Int64(i) => i.into_pyobject(py).unwrap().into(),
-        Bson::Double(f) => PyFloat::new(py, *f).into_pyobject(py).unwrap().into(),
-        Bson::String(s) => PyString::new(py, s).into_pyobject(py).unwrap().into(),
-        // Bson::Boolean(b) => {
-        //     // PyBool::new(py, *b) -> &PyBool (borrowed), convert it with Py::from
-        //     // Create a &PyBool
-        //     let py_bool_ref = PyBool::new(py, *b).as_unbound().clone_ref(py);
-        //     // Coerce &PyBool to &PyAny by assignment
-        //     let py_any: &PyAny = py_bool_ref ;
-        //     py_bool_ref
-        // }
+        Bson::Int32(i) => new_int32(py, *i)?,
+        Bson::Int64(i) => i.into_pyobject(py)?.into_any().unbind(),
+        Bson::Double(f) => PyFloat::new(py, *f).into_any().unbind(),
+        Bson::String(s) => PyString::new(py, s).into_any().unbind(),
         Bson::Boolean(b) => PyBool::new(py, *b).as_unbound().clone_ref(py).into_any(),
         Bson::Array(arr) => {
-            // Create an empty PyList without specifying a slice
-            let py_list = PyList::empty(py); // Use empty method instead of new
+            let py_list = PyList::empty(py);
             for item in arr {
-                py_list.append(bson_to_py_obj(py, item)).unwrap();
+                py_list.append(bson_to_py_obj(py, item, opts)?)?;
             }
-            py_list.into_pyobject(py).unwrap().into()
+            py_list.into_any().unbind()
         }
-        Bson::Document(doc) => {
-            let py_dict = PyDict::new(py);
-            for (key, value) in doc.iter() {
-                py_dict.set_item(key, bson_to_py_obj(py, value)).unwrap();
-            }
-            py_dict.into_pyobject(py).unwrap().into()
+        Bson::Document(doc) => document_to_pydict(py, doc, opts)?.into_any(),
+        Bson::RegularExpression(regex) => bson_regex_to_py(py, regex)?,
+        // Handle JavaScript code
+        Bson::JavaScriptCode(code) => Py::new(
+            py,
+            PyCode {
+                code: code.clone(),
+                scope: None,
+            },
+        )?
+        .into_any(),
+        Bson::JavaScriptCodeWithScope(code_with_scope) => Py::new(
+            py,
+            PyCode {
+                code: code_with_scope.code.clone(),
+                scope: Some(document_to_pydict(py, &code_with_scope.scope, opts)?),
+            },
+        )?
+        .into_any(),
+        Bson::Timestamp(ts) => Py::new(
+            py,
+            PyTimestamp {
+                time: ts.time,
+                inc: ts.increment,
+            },
+        )?
+        .into_any(),
+        Bson::Binary(bin) if bin.subtype == BinarySubtype::Uuid && bin.bytes.len() == 16 => {
+            let kwargs = PyDict::new(py);
+            kwargs.set_item("bytes", PyBytes::new(py, &bin.bytes))?;
+            UUID_TYPE
+                .import(py, "uuid", "UUID")?
+                .call((), Some(&kwargs))?
+                .unbind()
         }
-        Bson::RegularExpression(regex) => {
-            let re_module = py.import("re").unwrap();
-            re_module
-                .call_method1("compile", (regex.pattern.as_str(),))
-                .unwrap()
-                .into_pyobject(py)
-                .unwrap()
-                .into()
+        Bson::Binary(bin) if bin.subtype == BinarySubtype::Generic => {
+            PyBytes::new(py, &bin.bytes).into_any().unbind()
         }
-        // Handle JavaScript code
-        Bson::JavaScriptCode(code) => PyString::new(py, code).into_pyobject(py).unwrap().into(),
-        Bson::Timestamp(ts) => (ts.time, ts.increment).into_pyobject(py).unwrap().into(),
-        Bson::Binary(bin) => PyBytes::new(py, &bin.bytes)
-            .into_pyobject(py)
-            .unwrap()
-            .into(),
-        Bson::ObjectId(oid) => PyString::new(py, &oid.to_hex())
-            .into_pyobject(py)
-            .unwrap()
-            .into(),
-        Bson::DateTime(dt) => {
-            let timestamp = dt.timestamp_millis() / 1000;
-            let datetime = py.import("datetime").unwrap().getattr("datetime").unwrap();
-            datetime
-                .call1((timestamp,))
-                .unwrap()
-                .into_pyobject(py)
-                .unwrap()
-                .into()
-        }
-        Bson::Symbol(s) => PyString::new(py, s).into_pyobject(py).unwrap().into(),
+        // Other subtypes keep their subtype so they are written back unchanged
+        Bson::Binary(bin) => Py::new(py, PyBinary::from(bin))?.into_any(),
+        Bson::Decimal128(decimal) if opts.strict => {
+            Py::new(py, PyDecimal128::from(*decimal))?.into_any()
+        }
+        Bson::Decimal128(decimal) => {
+            // decimal.Decimal has no NaN payloads, keep those values as Decimal128
+            let text = decimal.to_string();
+            if text
+                .parse::<Decimal128>()
+                .is_ok_and(|parsed| parsed == *decimal)
+            {
+                py_decimal_from_str(py, &text)?
+            } else {
+                Py::new(py, PyDecimal128::from(*decimal))?.into_any()
+            }
+        }
+        Bson::ObjectId(oid) => Py::new(py, PyObjectId::from(*oid))?.into_any(),
+        Bson::DateTime(dt) => bson_datetime_to_py(py, dt, opts)?,
+        Bson::Symbol(s) => Py::new(py, PySymbol { symbol: s.clone() })?.into_any(),
 
         // Handle undefined value (deprecated)
-        Bson::Undefined => py.None(),
+        Bson::Undefined => Py::new(py, PyUndefined)?.into_any(),
+
+        Bson::MaxKey => Py::new(py, PyMaxKey)?.into_any(),
+
+        Bson::MinKey => Py::new(py, PyMinKey)?.into_any(),
 
-        // Handle MaxKey (convert to None)
-        Bson::MaxKey => py.None(),
+        // Handle DBPointer (deprecated)
+        Bson::DbPointer(db_pointer) => Py::new(
+            py,
+            PyDbPointer {
+                inner: db_pointer.clone(),
+            },
+        )?
+        .into_any(),
+    };
+    Ok(py_obj)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
 
-        // Handle MinKey (convert to None)
-        Bson::MinKey => py.None(),
+    /// Evaluate `expr` with the standard modules the translator knows about in scope.
+    fn eval<'py>(py: Python<'py>, expr: &CStr) -> Bound<'py, PyAny> {
+        let globals = PyDict::new(py);
+        for module in ["datetime", "decimal", "re", "uuid"] {
+            globals
+                .set_item(module, py.import(module).unwrap())
+                .unwrap();
+        }
+        py.eval(expr, Some(&globals), None).unwrap()
+    }
+
+    fn to_bson(value: &Bound<'_, PyAny>, opts: &CodecOptions) -> PyResult<Bson> {
+        convert_py_obj_to_bson(value, "value", opts)
+    }
+
+    fn to_py<'py>(py: Python<'py>, bson: &Bson, opts: &CodecOptions) -> Bound<'py, PyAny> {
+        bson_to_py_obj(py, bson, opts).unwrap().into_bound(py)
+    }
+
+    #[test]
+    fn naive_datetime_is_stored_as_utc_millis() {
+        Python::attach(|py| {
+            let value = eval(py, c"datetime.datetime(2024, 5, 1, 12, 30, 15, 123456)");
+            let bson = to_bson(&value, &CodecOptions::default()).unwrap();
+            assert_eq!(bson, Bson::DateTime(DateTime::from_millis(1714566615123)));
+        });
+    }
+
+    #[test]
+    fn aware_datetime_is_normalized_to_utc() {
+        Python::attach(|py| {
+            let value = eval(
+                py,
+                c"datetime.datetime(2024, 5, 1, 14, 30, 15, 123000, \
+                  datetime.timezone(datetime.timedelta(hours=2)))",
+            );
+            let bson = to_bson(&value, &CodecOptions::default()).unwrap();
+            assert_eq!(bson, Bson::DateTime(DateTime::from_millis(1714566615123)));
+        });
+    }
+
+    #[test]
+    fn date_is_stored_as_midnight() {
+        Python::attach(|py| {
+            let value = eval(py, c"datetime.date(2024, 5, 1)");
+            let bson = to_bson(&value, &CodecOptions::default()).unwrap();
+            assert_eq!(bson, Bson::DateTime(DateTime::from_millis(1714521600000)));
+        });
+    }
+
+    #[test]
+    fn datetime_is_read_back_naive_aware_or_converted() {
+        Python::attach(|py| {
+            let bson = Bson::DateTime(DateTime::from_millis(1714566615123));
+
+            let naive = to_py(py, &bson, &CodecOptions::default());
+            let expected = eval(py, c"datetime.datetime(2024, 5, 1, 12, 30, 15, 123000)");
+            assert!(naive.eq(expected).unwrap());
+            assert!(naive.getattr("tzinfo").unwrap().is_none());
+
+            let aware_opts = CodecOptions {
+                tz_aware: true,
+                ..Default::default()
+            };
+            let aware = to_py(py, &bson, &aware_opts);
+            let expected = eval(
+                py,
+                c"datetime.datetime(2024, 5, 1, 12, 30, 15, 123000, datetime.timezone.utc)",
+            );
+            assert!(aware.eq(expected).unwrap());
+
+            let tokyo_opts = CodecOptions {
+                tzinfo: Some(eval(py, c"datetime.timezone(datetime.timedelta(hours=9))").unbind()),
+                ..Default::default()
+            };
+            let tokyo = to_py(py, &bson, &tokyo_opts);
+            assert_eq!(tokyo.getattr("hour").unwrap().extract::<i64>().unwrap(), 21);
+            assert_eq!(to_bson(&tokyo, &tokyo_opts).unwrap(), bson);
+        });
+    }
+
+    #[test]
+    fn none_bytes_and_sequences_are_converted() {
+        Python::attach(|py| {
+            let opts = CodecOptions::default();
+            let value = eval(
+                py,
+                c"[None, b'ab', bytearray(b'ab'), memoryview(b'ab'), (1, 2), {3}, frozenset({4})]",
+            );
+            let binary = generic_binary(b"ab".to_vec());
+            assert_eq!(
+                to_bson(&value, &opts).unwrap(),
+                Bson::Array(vec![
+                    Bson::Null,
+                    binary.clone(),
+                    binary.clone(),
+                    binary,
+                    Bson::Array(vec![Bson::Int64(1), Bson::Int64(2)]),
+                    Bson::Array(vec![Bson::Int64(3)]),
+                    Bson::Array(vec![Bson::Int64(4)]),
+                ])
+            );
+        });
+    }
+
+    #[test]
+    fn null_and_generic_binary_are_read_as_none_and_bytes() {
+        Python::attach(|py| {
+            let opts = CodecOptions::default();
+            assert!(to_py(py, &Bson::Null, &opts).is_none());
+            let bytes = to_py(py, &generic_binary(b"ab".to_vec()), &opts);
+            assert_eq!(bytes.cast::<PyBytes>().unwrap().as_bytes(), b"ab");
+        });
+    }
+
+    #[test]
+    fn object_id_round_trips() {
+        Python::attach(|py| {
+            let opts = CodecOptions::default();
+            let oid = polodb_core::bson::oid::ObjectId::new();
+            let value = to_py(py, &Bson::ObjectId(oid), &opts);
+            assert!(value.is_instance_of::<PyObjectId>());
+            assert_eq!(to_bson(&value, &opts).unwrap(), Bson::ObjectId(oid));
+        });
+    }
+
+    #[test]
+    fn decimal_uuid_and_int32_round_trip() {
+        Python::attach(|py| {
+            let opts = CodecOptions::default();
+            let decimal = eval(py, c"decimal.Decimal('1234.5600')");
+            let bson = to_bson(&decimal, &opts).unwrap();
+            assert_eq!(bson, Bson::Decimal128("1234.5600".parse().unwrap()));
+            assert!(to_py(py, &bson, &opts).eq(&decimal).unwrap());
+
+            let uuid = eval(py, c"uuid.UUID('12345678-1234-5678-1234-567812345678')");
+            let bson = to_bson(&uuid, &opts).unwrap();
+            let Bson::Binary(binary) = &bson else {
+                panic!("expected binary, got {:?}", bson);
+            };
+            assert_eq!(binary.subtype, BinarySubtype::Uuid);
+            assert!(to_py(py, &bson, &opts).eq(&uuid).unwrap());
+
+            let int32 = new_int32(py, 7).unwrap().into_bound(py);
+            assert_eq!(to_bson(&int32, &opts).unwrap(), Bson::Int32(7));
+            // Read back as an int that is written as Int32 again
+            let read = to_py(py, &Bson::Int32(7), &opts);
+            assert!(read.is_instance_of::<PyInt>());
+            assert!(read.eq(7).unwrap());
+            assert_eq!(to_bson(&read, &opts).unwrap(), Bson::Int32(7));
+        });
+    }
+
+    #[test]
+    fn int_wider_than_64_bits_raises_overflow_error() {
+        Python::attach(|py| {
+            let err = to_bson(&eval(py, c"2 ** 64"), &CodecOptions::default()).unwrap_err();
+            assert!(err.is_instance_of::<PyOverflowError>(py));
+            assert!(err.to_string().contains("value:"));
+        });
+    }
+
+    #[test]
+    fn regex_flags_map_to_bson_options() {
+        Python::attach(|py| {
+            let opts = CodecOptions::default();
+            let pattern = eval(py, c"re.compile('a.c', re.I | re.S)");
+            let bson = to_bson(&pattern, &opts).unwrap();
+            let expected = Regex {
+                pattern: "a.c".to_string(),
+                options: "is".to_string(),
+            };
+            assert_eq!(bson, Bson::RegularExpression(expected));
+            assert!(to_py(py, &bson, &opts).eq(&pattern).unwrap());
+
+            // str patterns always carry re.UNICODE, it is not a BSON option
+            let plain = to_bson(&eval(py, c"re.compile('a')"), &opts).unwrap();
+            let expected = Regex {
+                pattern: "a".to_string(),
+                options: String::new(),
+            };
+            assert_eq!(plain, Bson::RegularExpression(expected));
+        });
+    }
+
+    #[test]
+    fn filters_are_normalized_under_every_operator() {
+        let regex = |options: &str| {
+            Bson::RegularExpression(Regex {
+                pattern: "^a".to_string(),
+                options: options.to_string(),
+            })
+        };
+        let filter = doc! {
+            "name": regex("i"),
+            "$nor": [{"city": {"$regex": "^a", "$options": "mi"}}],
+            "tags": {"$elemMatch": {"$regex": "^a", "$options": "i"}},
+            "title": {"$not": regex("")},
+            "scores": {"$all": [{"$elemMatch": {"label": regex("")}}]},
+            "literal": {"name": regex("")},
+            "city": {"$in": [regex("i"), "Oslo"]},
+            "country": {"$in": [regex("")]},
+            "street": {"$nin": [regex("i"), "Main"], "$ne": "High"},
+            "labels": {"$all": [regex(""), "red"]},
+        };
+        assert_eq!(
+            normalize_filter(filter),
+            doc! {
+                "name": {"$regex": regex("i")},
+                "$nor": [{"city": {"$regex": regex("im")}}],
+                "tags": {"$elemMatch": {"$regex": regex("i")}},
+                "title": {"$not": {"$regex": regex("")}},
+                "scores": {"$all": [{"$elemMatch": {"label": {"$regex": regex("")}}}]},
+                "literal": {"name": regex("")},
+                "street": {"$ne": "High", "$nin": ["Main"]},
+                "labels": {"$all": ["red"]},
+                "$and": [
+                    {"$or": [{"city": {"$regex": regex("i")}}, {"city": {"$in": ["Oslo"]}}]},
+                    {"country": {"$regex": regex("")}},
+                    {"street": {"$not": {"$regex": regex("i")}}},
+                    {"labels": {"$regex": regex("")}},
+                ],
+            }
+        );
+        // Added to an `$and` already there
+        assert_eq!(
+            normalize_filter(doc! {"$and": [{"a": 1}], "b": {"$in": [regex("")]}}),
+            doc! {"$and": [{"a": 1}, {"b": {"$regex": regex("")}}]}
+        );
+    }
+
+    #[test]
+    fn decimal128_keeps_values_decimal_cannot_hold() {
+        Python::attach(|py| {
+            let opts = CodecOptions::default();
+            // A NaN with a payload, which decimal.Decimal would read as a plain NaN
+            let mut bytes = [0u8; 16];
+            bytes[0] = 1;
+            bytes[15] = 0x7c;
+            let bson = Bson::Decimal128(Decimal128::from_bytes(bytes));
+            let value = to_py(py, &bson, &opts);
+            assert!(value.is_instance_of::<PyDecimal128>());
+            assert_eq!(to_bson(&value, &opts).unwrap(), bson);
+
+            let strict = CodecOptions {
+                strict: true,
+                ..Default::default()
+            };
+            let bson = Bson::Decimal128("1.50".parse().unwrap());
+            let value = to_py(py, &bson, &strict);
+            assert!(value.is_instance_of::<PyDecimal128>());
+            assert_eq!(to_bson(&value, &strict).unwrap(), bson);
+        });
+    }
+
+    #[test]
+    fn strict_mode_refuses_values_read_back_as_another_type() {
+        Python::attach(|py| {
+            let strict = CodecOptions {
+                strict: true,
+                ..Default::default()
+            };
+            for expr in [
+                c"(1, 2)",
+                c"{1}",
+                c"bytearray(b'a')",
+                c"datetime.date(2024, 1, 1)",
+                c"type('Name', (str,), {})('a')",
+                c"__import__('enum').IntEnum('Level', 'LOW')(1)",
+                c"{'nested': [frozenset()]}",
+            ] {
+                let err = to_bson(&eval(py, expr), &strict).unwrap_err();
+                assert!(err.is_instance_of::<PyTypeError>(py), "{}", err);
+                assert!(err.to_string().contains("strict mode"), "{}", err);
+            }
+            for expr in [
+                c"datetime.datetime(2024, 1, 1, microsecond=1)",
+                c"re.compile('a', re.ASCII)",
+            ] {
+                let err = to_bson(&eval(py, expr), &strict).unwrap_err();
+                assert!(err.is_instance_of::<PyValueError>(py), "{}", err);
+            }
+            let exact = eval(py, c"[True, 1, 1.5, 'a', b'a', {'a': None}]");
+            assert!(to_bson(&exact, &strict).is_ok());
+        });
+    }
+
+    #[test]
+    fn code_scope_uses_the_callers_options() {
+        Python::attach(|py| {
+            let opts = CodecOptions {
+                tz_aware: true,
+                ..Default::default()
+            };
+            let bson = Bson::JavaScriptCodeWithScope(JavaScriptCodeWithScope {
+                code: "f()".to_string(),
+                scope: doc! {"at": DateTime::from_millis(0)},
+            });
+            let code = to_py(py, &bson, &opts);
+            let at = code.getattr("scope").unwrap().get_item("at").unwrap();
+            assert!(!at.getattr("tzinfo").unwrap().is_none());
+            assert_eq!(to_bson(&code, &opts).unwrap(), bson);
+        });
+    }
+
+    #[test]
+    fn errors_name_the_path_and_keep_the_original_as_cause() {
+        Python::attach(|py| {
+            let docs = eval(py, c"[{'a': 1}, {'a': {'b': [1, object()]}}]");
+            let err = convert_py_list_to_vec_document(&docs, "docs", &CodecOptions::default())
+                .unwrap_err();
+            assert!(err.is_instance_of::<PyTypeError>(py));
+            assert_eq!(
+                err.value(py).to_string(),
+                "docs[1].a.b[1]: unsupported type 'object'"
+            );
+            let cause = err.cause(py).unwrap();
+            assert_eq!(cause.value(py).to_string(), "unsupported type 'object'");
+
+            let err =
+                convert_py_list_to_vec_document(&eval(py, c"{}"), "docs", &Default::default())
+                    .unwrap_err();
+            assert!(err.is_instance_of::<PyTypeError>(py));
+        });
+    }
 
-        _ => py.None(), // Handle other BSON types as needed
+    #[test]
+    fn prefix_path_keeps_exceptions_it_cannot_rebuild() {
+        Python::attach(|py| {
+            let err = py
+                .run(
+                    c"class Strict(Exception):\n    def __init__(self, a, b):\n        super().__init__(a, b)\nraise Strict(1, 2)",
+                    None,
+                    None,
+                )
+                .unwrap_err();
+            let original = err.value(py).clone().unbind();
+            let prefixed = prefix_path(py, err, "doc.a");
+            assert!(prefixed.value(py).is(&original));
+        });
     }
 }
@@ -0,0 +1,640 @@
+use crate::mongo::helper_query::{SortSpec, replacement_with_id, sort_documents, upsert_seed};
+use crate::mongo::helper_stats::CollectionSize;
+use crate::mongo::py_cursor::CursorSource;
+use crate::mongo::py_errors::{DuplicateKeyError, write_error};
+use crate::mongo::py_index::{
+    ID_INDEX_NAME, IndexSpec, check_indexable, count_key_holders, element_type_name, is_indexable,
+    unique_key,
+};
+use polodb_core::bson::oid::ObjectId;
+use polodb_core::bson::{Bson, Document, doc};
+use polodb_core::results::{DeleteResult, InsertManyResult, UpdateResult};
+use polodb_core::{CollectionT, TransactionalCollection};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+/// The change `find_one_and_*` makes to the document it finds.
+pub enum Modification {
+    Update(Document),
+    Replace(Document),
+    Delete,
+}
+
+/// The `_id` of a document a write touched and its content before, None when it was inserted.
+type Undo = (Bson, Option<Document>);
+
+/// Checks the `_id` of a document before it is written, see `CollectionWriter::with_guard`.
+type Guard<'a> = &'a dyn Fn(&Bson) -> PyResult<()>;
+
+/// Writes to a collection inside a transaction. polodb's own unique indexes also fire when an
+/// indexed document is updated or deleted, so indexes are created plain and every write checks
+/// the documents it touched against the unique ones before the transaction commits.
+pub struct CollectionWriter<'a> {
+    pub collection: &'a TransactionalCollection<Document>,
+    pub indexes: &'a [IndexSpec],
+    /// Prefix of error messages, e.g. "Insert many"
+    pub context: &'a str,
+    // Set within `savepoint`
+    journal: RefCell<Option<Vec<Undo>>>,
+    // Called with the `_id` of each document before it is written, see `with_guard`
+    guard: Option<Guard<'a>>,
+    // How much the writes changed the collection, see `size_change`
+    size_change: Cell<CollectionSize>,
+}
+
+impl<'a> CollectionWriter<'a> {
+    pub fn new(
+        collection: &'a TransactionalCollection<Document>,
+        indexes: &'a [IndexSpec],
+        context: &'a str,
+    ) -> CollectionWriter<'a> {
+        CollectionWriter {
+            collection,
+            indexes,
+            context,
+            journal: RefCell::new(None),
+            guard: None,
+            size_change: Cell::default(),
+        }
+    }
+
+    /// Pass the `_id` of every document before it is inserted, changed or deleted to `guard`,
+    /// which fails the write by returning an error.
+    pub fn with_guard(mut self, guard: Guard<'a>) -> CollectionWriter<'a> {
+        self.guard = Some(guard);
+        self
+    }
+
+    /// Run `write`, and when it fails undo what it changed while keeping the rest of the
+    /// transaction. polodb has no savepoints, so the writes made meanwhile record the documents
+    /// they touch. The outer error means the undo itself failed.
+    pub fn savepoint<R>(&self, write: impl FnOnce() -> PyResult<R>) -> PyResult<PyResult<R>> {
+        self.journal.replace(Some(Vec::new()));
+        let size_change = self.size_change.get();
+        let result = write();
+        let journal = self.journal.take().unwrap_or_default();
+        if result.is_err() {
+            self.size_change.set(size_change);
+            for (id, before) in journal.into_iter().rev() {
+                self.collection
+                    .delete_one(doc! {"_id": id})
+                    .map_err(|e| self.error(e))?;
+                if let Some(before) = before {
+                    self.collection
+                        .insert_one(before)
+                        .map_err(|e| self.error(e))?;
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    pub fn error(&self, err: polodb_core::Error) -> PyErr {
+        write_error(self.context, err)
+    }
+
+    /// How much the writes so far changed the size of the collection, for `SizeCounters`.
+    pub fn size_change(&self) -> CollectionSize {
+        self.size_change.get()
+    }
+
+    pub fn insert_one(&self, doc: Document) -> PyResult<Bson> {
+        let (id, doc) = with_id(doc);
+        self.check_new_id(&id)?;
+        for spec in self.indexes {
+            check_indexable(&doc, self.collection.name(), spec, self.context)?;
+        }
+        // polodb stores the document before indexing it, so a failed insert may leave it behind
+        self.record(&id, None)?;
+        self.collection
+            .insert_one(&doc)
+            .map_err(|e| self.error(e))?;
+        self.check_unique(std::slice::from_ref(&doc))?;
+        self.resize(CollectionSize::of(&doc));
+        Ok(id)
+    }
+
+    /// Insert `docs` one by one, so a document repeating the `_id` of an earlier one in the
+    /// same batch is caught as well.
+    pub fn insert_many(&self, docs: Vec<Document>) -> PyResult<InsertManyResult> {
+        let mut inserted_ids = HashMap::with_capacity(docs.len());
+        for (index, doc) in docs.into_iter().enumerate() {
+            inserted_ids.insert(index, self.insert_one(doc)?);
+        }
+        Ok(InsertManyResult { inserted_ids })
+    }
+
+    /// Update the documents matching `filter`, the first one unless `many` is set. When nothing
+    /// matches and `upsert` is set, a document built from the filter's equality conditions is
+    /// inserted and updated, its `_id` is returned with the counts.
+    pub fn update(
+        &self,
+        filter: Document,
+        update: Document,
+        many: bool,
+        upsert: bool,
+    ) -> PyResult<(UpdateResult, Option<Bson>)> {
+        // Which documents an update changes is only known before it runs
+        let (ids, before) = self.matching(&filter, many)?;
+        let update_result = if many {
+            self.collection.update_many(filter.clone(), update.clone())
+        } else {
+            self.collection.update_one(filter.clone(), update.clone())
+        }
+        .map_err(|e| self.error(e))?;
+        let updated = self.read_back(&ids)?;
+        self.check_unique(&updated)?;
+        self.resize(measure(&updated) - before);
+        if update_result.matched_count > 0 || !upsert {
+            return Ok((update_result, None));
+        }
+        let id = self.insert_upserted(&filter, update)?;
+        Ok((update_result, Some(id)))
+    }
+
+    /// Replace the first document matching `filter`, or insert `replacement` when `upsert` is
+    /// set and nothing matches. Returns the counts and the `_id` of an upserted document.
+    pub fn replace(
+        &self,
+        filter: Document,
+        replacement: Document,
+        upsert: bool,
+    ) -> PyResult<(UpdateResult, Option<Bson>)> {
+        match self
+            .collection
+            .find_one(filter.clone())
+            .map_err(|e| self.error(e))?
+        {
+            Some(found) => {
+                let id = found.get("_id").cloned().unwrap_or(Bson::Null);
+                let replaced = replacement_with_id(replacement, Some(&id))?;
+                // Field order is not compared, a reordered but equal document is not modified
+                let modified = replaced != found;
+                if modified {
+                    self.replace_by_id(&id, replaced)?;
+                }
+                let update_result = UpdateResult {
+                    matched_count: 1,
+                    modified_count: modified as u64,
+                };
+                Ok((update_result, None))
+            }
+            None if upsert => {
+                let seed = upsert_seed(&filter);
+                let replaced = replacement_with_id(replacement, seed.get("_id"))?;
+                let id = self.insert_one(replaced)?;
+                Ok((UpdateResult::default(), Some(id)))
+            }
+            None => Ok((UpdateResult::default(), None)),
+        }
+    }
+
+    pub fn delete(&self, filter: Document, many: bool) -> PyResult<DeleteResult> {
+        let (_, before) = self.matching(&filter, many)?;
+        let delete_result = if many {
+            self.collection.delete_many(filter)
+        } else {
+            self.collection.delete_one(filter)
+        }
+        .map_err(|e| self.error(e))?;
+        self.resize(CollectionSize::default() - before);
+        Ok(delete_result)
+    }
+
+    /// Find the target of a `find_one_and_*` and change it, returning the document before or
+    /// after the change.
+    pub fn find_one_and_modify(
+        &self,
+        filter: Document,
+        modification: Modification,
+        sort: Option<&SortSpec>,
+        upsert: bool,
+        return_after: bool,
+    ) -> PyResult<Option<Document>> {
+        let found = match sort {
+            Some(sort) => {
+                let cursor = self
+                    .collection
+                    .find(filter.clone())
+                    .run()
+                    .map_err(|e| self.error(e))?;
+                let docs = CursorSource::query(cursor)
+                    .collect_documents()
+                    .map_err(|e| self.error(e))?;
+                sort_documents(docs, sort).into_iter().next()
+            }
+            None => self
+                .collection
+                .find_one(filter.clone())
+                .map_err(|e| self.error(e))?,
+        };
+
+        let id = match (found.as_ref(), modification) {
+            (Some(found), Modification::Update(update)) => {
+                let id = found.get("_id").cloned().unwrap_or(Bson::Null);
+                self.record(&id, Some(found))?;
+                self.collection
+                    .update_one(doc! {"_id": id.clone()}, update)
+                    .map_err(|e| self.error(e))?;
+                let updated = self.read_back(std::slice::from_ref(&id))?;
+                self.check_unique(&updated)?;
+                self.resize(measure(&updated) - CollectionSize::of(found));
+                id
+            }
+            (Some(found), Modification::Replace(replacement)) => {
+                let id = found.get("_id").cloned().unwrap_or(Bson::Null);
+                let replaced = replacement_with_id(replacement, Some(&id))?;
+                self.replace_by_id(&id, replaced)?;
+                id
+            }
+            (Some(found), Modification::Delete) => {
+                let id = found.get("_id").cloned().unwrap_or(Bson::Null);
+                self.delete(doc! {"_id": id.clone()}, false)?;
+                id
+            }
+            (None, _) if !upsert => return Ok(None),
+            (None, Modification::Update(update)) => self.insert_upserted(&filter, update)?,
+            (None, Modification::Replace(replacement)) => {
+                let seed = upsert_seed(&filter);
+                self.insert_one(replacement_with_id(replacement, seed.get("_id"))?)?
+            }
+            (None, Modification::Delete) => return Ok(None),
+        };
+
+        if return_after {
+            self.collection
+                .find_one(doc! {"_id": id})
+                .map_err(|e| self.error(e))
+        } else {
+            Ok(found)
+        }
+    }
+
+    fn replace_by_id(&self, id: &Bson, replaced: Document) -> PyResult<()> {
+        self.delete(doc! {"_id": id.clone()}, false)?;
+        self.insert_one(replaced)?;
+        Ok(())
+    }
+
+    /// Insert the seed of an upsert and apply `update` to it, returning its `_id`.
+    fn insert_upserted(&self, filter: &Document, update: Document) -> PyResult<Bson> {
+        let (id, seed) = with_id(upsert_seed(filter));
+        self.check_new_id(&id)?;
+        self.record(&id, None)?;
+        self.collection
+            .insert_one(seed)
+            .map_err(|e| self.error(e))?;
+        self.collection
+            .update_one(doc! {"_id": id.clone()}, update)
+            .map_err(|e| self.error(e))?;
+        let upserted = self.read_back(std::slice::from_ref(&id))?;
+        self.check_unique(&upserted)?;
+        self.resize(measure(&upserted));
+        Ok(id)
+    }
+
+    /// polodb writes over a stored document with the same `_id` instead of failing, so an
+    /// insert checks for one first.
+    fn check_new_id(&self, id: &Bson) -> PyResult<()> {
+        if !is_indexable(id) {
+            return Err(PyValueError::new_err(format!(
+                "{} error: _id cannot be {}",
+                self.context,
+                element_type_name(id)
+            )));
+        }
+        match self
+            .collection
+            .find_one(doc! {"_id": id.clone()})
+            .map_err(|e| self.error(e))?
+        {
+            Some(_) => Err(self.duplicate_key_error(ID_INDEX_NAME, id)),
+            None => Ok(()),
+        }
+    }
+
+    fn resize(&self, change: CollectionSize) {
+        self.size_change.set(self.size_change.get() + change);
+    }
+
+    fn duplicate_key_error(&self, index: &str, key: &Bson) -> PyErr {
+        DuplicateKeyError::new_err(format!(
+            "{} error: duplicate key error collection: {}, index: {}, key: {}",
+            self.context,
+            self.collection.name(),
+            index,
+            key
+        ))
+    }
+
+    fn has_unique_indexes(&self) -> bool {
+        self.indexes.iter().any(|spec| spec.unique)
+    }
+
+    /// The `_id`s of the documents a write with `filter` changes and their size before it,
+    /// passed to the savepoint and the guard.
+    fn matching(&self, filter: &Document, many: bool) -> PyResult<(Vec<Bson>, CollectionSize)> {
+        let mut find = self.collection.find(filter.clone());
+        if !many {
+            find = find.limit(1);
+        }
+        let mut cursor = find.run().map_err(|e| self.error(e))?;
+        let mut ids = Vec::new();
+        let mut size = CollectionSize::default();
+        while cursor.advance().map_err(|e| self.error(e))? {
+            let doc = cursor.deserialize_current().map_err(|e| self.error(e))?;
+            let id = doc.get("_id").cloned().unwrap_or(Bson::Null);
+            self.record(&id, Some(&doc))?;
+            size = size + CollectionSize::of(&doc);
+            ids.push(id);
+        }
+        Ok((ids, size))
+    }
+
+    /// The documents with the `_id`s in `ids` as a write left them.
+    fn read_back(&self, ids: &[Bson]) -> PyResult<Vec<Document>> {
+        let mut docs = Vec::with_capacity(ids.len());
+        for id in ids {
+            if let Some(doc) = self
+                .collection
+                .find_one(doc! {"_id": id.clone()})
+                .map_err(|e| self.error(e))?
+            {
+                docs.push(doc);
+            }
+        }
+        Ok(docs)
+    }
+
+    /// Pass the `_id` of a document about to be written to the guard and the journal, with its
+    /// content before, None when it is inserted.
+    fn record(&self, id: &Bson, before: Option<&Document>) -> PyResult<()> {
+        if let Some(guard) = self.guard {
+            guard(id)?;
+        }
+        if let Some(journal) = self.journal.borrow_mut().as_mut() {
+            journal.push((id.clone(), before.cloned()));
+        }
+        Ok(())
+    }
+
+    /// Fail with `DuplicateKeyError` when one of the written `docs` holds the same value as
+    /// another document for a unique index.
+    fn check_unique(&self, docs: &[Document]) -> PyResult<()> {
+        if !self.has_unique_indexes() {
+            return Ok(());
+        }
+        for doc in docs {
+            for spec in self.indexes.iter().filter(|spec| spec.unique) {
+                let Some(value) = unique_key(doc, self.collection.name(), spec, self.context)?
+                else {
+                    continue;
+                };
+                if count_key_holders(self.collection, spec, value, |e| self.error(e))? > 1 {
+                    return Err(self.duplicate_key_error(&spec.name, value));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn measure(docs: &[Document]) -> CollectionSize {
+    docs.iter().fold(CollectionSize::default(), |size, doc| {
+        size + CollectionSize::of(doc)
+    })
+}
+
+/// `doc` with the `_id` polodb would give it, an ObjectId when it has none or a null one.
+fn with_id(mut doc: Document) -> (Bson, Document) {
+    let id = match doc.get("_id") {
+        Some(Bson::Null) | None => {
+            let id = Bson::ObjectId(ObjectId::new());
+            doc.insert("_id", id.clone());
+            id
+        }
+        Some(id) => id.clone(),
+    };
+    (id, doc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mongo::test_dir::TestDir;
+
+    fn with_writer(name: &str, test: impl FnOnce(&CollectionWriter)) {
+        let dir = TestDir::new(name);
+        let db = dir.open();
+        let txn = db.start_transaction().unwrap();
+        let collection = txn.collection::<Document>("items");
+        test(&CollectionWriter::new(&collection, &[], "Insert"));
+    }
+
+    #[test]
+    fn insert_refuses_a_stored_id() {
+        with_writer("write_stored_id", |writer| {
+            writer.insert_one(doc! {"_id": 1, "v": "first"}).unwrap();
+            let err = writer
+                .insert_one(doc! {"_id": 1, "v": "second"})
+                .unwrap_err();
+            Python::attach(|py| assert!(err.is_instance_of::<DuplicateKeyError>(py)));
+            let stored = writer
+                .collection
+                .find_one(doc! {"_id": 1})
+                .unwrap()
+                .unwrap();
+            assert_eq!(stored.get_str("v").unwrap(), "first");
+        });
+    }
+
+    #[test]
+    fn insert_many_refuses_an_id_repeated_in_the_batch() {
+        with_writer("write_batch_id", |writer| {
+            let docs = vec![doc! {"_id": 1}, doc! {"_id": 2}, doc! {"_id": 1}];
+            let err = writer.insert_many(docs).unwrap_err();
+            Python::attach(|py| assert!(err.is_instance_of::<DuplicateKeyError>(py)));
+            assert!(writer.insert_one(doc! {"_id": [1]}).is_err());
+            let result = writer.insert_many(vec![doc! {"_id": 3}, doc! {}]).unwrap();
+            assert_eq!(result.inserted_ids[&0], Bson::Int32(3));
+            assert_eq!(result.inserted_ids.len(), 2);
+        });
+    }
+
+    fn find_one_and_update(
+        writer: &CollectionWriter,
+        filter: Document,
+        sort: Option<&SortSpec>,
+        upsert: bool,
+        return_after: bool,
+    ) -> Option<Document> {
+        let update = Modification::Update(doc! {"$set": {"claimed": true}});
+        writer
+            .find_one_and_modify(filter, update, sort, upsert, return_after)
+            .unwrap()
+    }
+
+    #[test]
+    fn find_one_and_modify_takes_the_first_sorted_document() {
+        with_writer("write_find_one_and", |writer| {
+            writer
+                .insert_many(vec![doc! {"_id": 1, "p": 1}, doc! {"_id": 2, "p": 5}])
+                .unwrap();
+            let sort = vec![("p".to_string(), -1)];
+            let before = find_one_and_update(writer, doc! {}, Some(&sort), false, false);
+            assert_eq!(before, Some(doc! {"_id": 2, "p": 5}));
+            let after = find_one_and_update(writer, doc! {"_id": 1}, None, false, true);
+            assert_eq!(after, Some(doc! {"_id": 1, "p": 1, "claimed": true}));
+
+            let replacement = Modification::Replace(doc! {"q": 1});
+            let replaced = writer
+                .find_one_and_modify(doc! {"_id": 2}, replacement, None, false, true)
+                .unwrap();
+            assert_eq!(replaced, Some(doc! {"_id": 2, "q": 1}));
+            let deleted = writer
+                .find_one_and_modify(doc! {"q": 1}, Modification::Delete, None, false, false)
+                .unwrap();
+            assert_eq!(deleted, Some(doc! {"_id": 2, "q": 1}));
+            assert_eq!(writer.collection.count_documents().unwrap(), 1);
+        });
+    }
+
+    #[test]
+    fn find_one_and_modify_upserts() {
+        with_writer("write_find_one_and_upsert", |writer| {
+            assert_eq!(
+                find_one_and_update(writer, doc! {"_id": 7}, None, false, true),
+                None
+            );
+            assert_eq!(
+                find_one_and_update(writer, doc! {"_id": 7}, None, true, false),
+                None
+            );
+            assert_eq!(
+                find_one_and_update(writer, doc! {"_id": 8}, None, true, true),
+                Some(doc! {"_id": 8, "claimed": true})
+            );
+            let delete = writer
+                .find_one_and_modify(doc! {"_id": 9}, Modification::Delete, None, true, true)
+                .unwrap();
+            assert_eq!(delete, None);
+            assert_eq!(writer.collection.count_documents().unwrap(), 2);
+        });
+    }
+
+    #[test]
+    fn replace_reports_what_changed() {
+        with_writer("write_replace", |writer| {
+            writer.insert_one(doc! {"_id": 1, "a": 1}).unwrap();
+            let (result, upserted) = writer.replace(doc! {"a": 1}, doc! {"b": 2}, false).unwrap();
+            assert_eq!((result.matched_count, result.modified_count), (1, 1));
+            assert_eq!(upserted, None);
+            let (result, _) = writer
+                .replace(doc! {"_id": 1}, doc! {"b": 2}, false)
+                .unwrap();
+            assert_eq!((result.matched_count, result.modified_count), (1, 0));
+            let (result, upserted) = writer
+                .replace(doc! {"_id": 5}, doc! {"c": 3}, true)
+                .unwrap();
+            assert_eq!((result.matched_count, result.modified_count), (0, 0));
+            assert_eq!(upserted, Some(Bson::Int32(5)));
+            let stored = writer.collection.find_one(doc! {"_id": 5}).unwrap();
+            assert_eq!(stored, Some(doc! {"_id": 5, "c": 3}));
+        });
+    }
+
+    #[test]
+    fn savepoint_undoes_only_the_failed_write() {
+        with_writer("write_savepoint", |writer| {
+            writer
+                .insert_many(vec![doc! {"_id": 1, "n": 1}, doc! {"_id": 2, "n": "two"}])
+                .unwrap();
+            let kept = writer.savepoint(|| writer.insert_one(doc! {"_id": 3, "n": 3}));
+            assert!(kept.unwrap().is_ok());
+            // The first document is changed before the second one fails
+            let failed = writer.savepoint(|| {
+                writer.delete(doc! {"_id": 3}, false)?;
+                writer.update(doc! {}, doc! {"$inc": {"n": 1}}, true, false)
+            });
+            assert!(failed.unwrap().is_err());
+            let docs: Vec<Document> = writer
+                .collection
+                .find(doc! {})
+                .run()
+                .unwrap()
+                .collect::<polodb_core::Result<_>>()
+                .unwrap();
+            assert_eq!(
+                docs,
+                vec![
+                    doc! {"_id": 1, "n": 1},
+                    doc! {"_id": 2, "n": "two"},
+                    doc! {"_id": 3, "n": 3}
+                ]
+            );
+            // Neither is their size
+            assert_eq!(writer.size_change(), measure(&docs));
+        });
+    }
+
+    #[test]
+    fn size_change_follows_every_write() {
+        with_writer("write_size", |writer| {
+            writer
+                .insert_many(vec![doc! {"_id": 1, "n": 1}, doc! {"_id": 2, "n": 2}])
+                .unwrap();
+            writer
+                .update(doc! {}, doc! {"$set": {"tag": "grown"}}, true, false)
+                .unwrap();
+            writer
+                .update(doc! {"_id": 3}, doc! {"$set": {"n": 3}}, false, true)
+                .unwrap();
+            writer
+                .replace(doc! {"_id": 2}, doc! {"n": "replaced"}, false)
+                .unwrap();
+            writer.delete(doc! {"_id": 1}, false).unwrap();
+            find_one_and_update(writer, doc! {"_id": 3}, None, false, true).unwrap();
+            let docs: Vec<Document> = writer
+                .collection
+                .find(doc! {})
+                .run()
+                .unwrap()
+                .collect::<polodb_core::Result<_>>()
+                .unwrap();
+            assert_eq!(docs.len(), 2);
+            assert_eq!(writer.size_change(), measure(&docs));
+        });
+    }
+
+    #[test]
+    fn guard_sees_every_written_document() {
+        with_writer("write_guard", |writer| {
+            writer
+                .insert_many(vec![doc! {"_id": 1, "n": 1}, doc! {"_id": 2, "n": 2}])
+                .unwrap();
+            let seen = RefCell::new(Vec::new());
+            let guard = |id: &Bson| {
+                seen.borrow_mut().push(id.clone());
+                match id {
+                    Bson::Int32(2) => Err(PyValueError::new_err("refused")),
+                    _ => Ok(()),
+                }
+            };
+            let writer = CollectionWriter::new(writer.collection, &[], "Update").with_guard(&guard);
+            writer
+                .update(doc! {"_id": 1}, doc! {"$set": {"n": 5}}, false, false)
+                .unwrap();
+            writer.insert_one(doc! {"_id": 3}).unwrap();
+            assert!(writer.delete(doc! {"_id": 2}, false).is_err());
+            assert_eq!(
+                *seen.borrow(),
+                vec![Bson::Int32(1), Bson::Int32(3), Bson::Int32(2)]
+            );
+            // The refused document is left as it was
+            let stored = writer.collection.find_one(doc! {"_id": 2}).unwrap();
+            assert_eq!(stored, Some(doc! {"_id": 2, "n": 2}));
+        });
+    }
+}
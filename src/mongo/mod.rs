@@ -1,2 +1,19 @@
+mod codec_registry;
+mod helper_query;
+mod helper_save;
+mod helper_stats;
+mod helper_ttl;
 mod helper_type_translator;
-pub mod py_database;
\ No newline at end of file
+mod helper_write;
+pub mod py_bson_types;
+pub mod py_bulk_write;
+pub mod py_codec_options;
+pub mod py_cursor;
+pub mod py_database;
+pub mod py_errors;
+pub mod py_extended_json;
+pub mod py_index;
+pub mod py_results;
+pub mod py_transaction;
+#[cfg(test)]
+mod test_dir;
@@ -0,0 +1,576 @@
+use crate::mongo::codec_registry::TAG_KEY;
+use crate::mongo::helper_type_translator::{
+    CodecOptions, bson_datetime_to_py, bson_to_py_obj, document_to_pydict, py_decimal_from_str,
+};
+use polodb_core::bson::oid::ObjectId;
+use polodb_core::bson::{
+    Binary, Bson, DateTime, DbPointer, Decimal128, RawBsonRef, RawDocument, RawDocumentBuf,
+};
+use pyo3::exceptions::{PyKeyError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::sync::PyOnceLock;
+use pyo3::types::{PyByteArray, PyBytes, PyDict, PyList, PyType};
+use std::ffi::CStr;
+use std::sync::Arc;
+
+/// BSON ObjectId, modeled on `bson.ObjectId` from pymongo.
+#[pyclass(name = "ObjectId", frozen, eq, ord, hash)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PyObjectId {
+    pub(crate) inner: ObjectId,
+}
+
+#[pymethods]
+impl PyObjectId {
+    /// Build an ObjectId from a 24 character hex string, 12 raw bytes or another ObjectId.
+    /// A new unique id is generated when `oid` is omitted.
+    #[new]
+    #[pyo3(signature = (oid=None))]
+    fn new(oid: Option<&Bound<'_, PyAny>>) -> PyResult<Self> {
+        let Some(oid) = oid else {
+            return Ok(ObjectId::new().into());
+        };
+        if let Ok(other) = oid.cast::<PyObjectId>() {
+            Ok(other.get().clone())
+        } else if let Ok(hex) = oid.extract::<String>() {
+            ObjectId::parse_str(&hex)
+                .map(PyObjectId::from)
+                .map_err(|e| PyValueError::new_err(format!("Invalid ObjectId '{}': {}", hex, e)))
+        } else if let Ok(bytes) = oid.cast::<PyBytes>() {
+            let raw: [u8; 12] = bytes.as_bytes().try_into().map_err(|_| {
+                PyValueError::new_err("ObjectId bytes must be exactly 12 bytes long")
+            })?;
+            Ok(ObjectId::from_bytes(raw).into())
+        } else {
+            Err(PyTypeError::new_err(
+                "ObjectId must be built from a hex str, 12 bytes or an ObjectId",
+            ))
+        }
+    }
+
+    /// Return True if `oid` is a valid 24 character hex ObjectId string.
+    #[staticmethod]
+    fn is_valid(oid: &str) -> bool {
+        ObjectId::parse_str(oid).is_ok()
+    }
+
+    /// The creation time encoded in the id, as an aware UTC datetime.
+    #[getter]
+    fn generation_time(&self, py: Python) -> PyResult<Py<PyAny>> {
+        let opts = CodecOptions {
+            tz_aware: true,
+            ..Default::default()
+        };
+        bson_datetime_to_py(py, &self.inner.timestamp(), &opts)
+    }
+
+    /// The 12 raw bytes of the id.
+    #[getter]
+    fn binary<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.inner.bytes())
+    }
+
+    fn __str__(&self) -> String {
+        self.inner.to_hex()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("ObjectId('{}')", self.inner.to_hex())
+    }
+}
+
+impl From<ObjectId> for PyObjectId {
+    fn from(inner: ObjectId) -> PyObjectId {
+        PyObjectId { inner }
+    }
+}
+
+/// `Int32`, an explicit 32-bit integer stored as BSON Int32 instead of the default Int64. It is
+/// an `int` subclass, so the values read back from an Int32 field work wherever an int does and
+/// are written back as Int32. pyo3 classes cannot extend `int`, so it is defined in Python.
+const INT32_SOURCE: &CStr = c"
+class Int32(int):
+    \"\"\"Explicit 32-bit integer, stored as BSON Int32 instead of the default Int64.\"\"\"
+
+    __slots__ = ()
+
+    def __new__(cls, value=0):
+        value = int.__new__(cls, value)
+        if not -2 ** 31 <= value < 2 ** 31:
+            raise OverflowError(f'Int32 out of range: {int(value)}')
+        return value
+
+    def __repr__(self):
+        return f'Int32({int(self)})'
+
+    def __str__(self):
+        return int.__repr__(self)
+
+Int32.__module__ = 'mongo_emb'
+";
+
+static INT32_TYPE: PyOnceLock<Py<PyType>> = PyOnceLock::new();
+
+/// The `Int32` class, see `INT32_SOURCE`.
+pub fn int32_type(py: Python<'_>) -> PyResult<&Bound<'_, PyType>> {
+    INT32_TYPE
+        .get_or_try_init(py, || {
+            let module = PyModule::from_code(py, INT32_SOURCE, c"mongo_emb/int32.py", c"int32")?;
+            Ok::<_, PyErr>(module.getattr("Int32")?.cast_into::<PyType>()?.unbind())
+        })
+        .map(|int32| int32.bind(py))
+}
+
+/// Read a BSON Int32 as an `Int32`.
+pub fn new_int32(py: Python<'_>, value: i32) -> PyResult<Py<PyAny>> {
+    Ok(int32_type(py)?.call1((value,))?.unbind())
+}
+
+/// The value of an `Int32`, None for any other object.
+pub fn int32_value(obj: &Bound<'_, PyAny>) -> Option<i32> {
+    let int32 = int32_type(obj.py()).ok()?;
+    match obj.is_instance(int32) {
+        Ok(true) => obj.extract().ok(),
+        _ => None,
+    }
+}
+
+/// BSON Decimal128, modeled on `bson.Decimal128` from pymongo. Values that `decimal.Decimal`
+/// holds exactly are read as `decimal.Decimal`; this class keeps the others (NaN payloads, and
+/// every value in strict mode) so they are written back bit for bit.
+#[pyclass(name = "Decimal128", frozen, eq, hash)]
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct PyDecimal128 {
+    pub(crate) bytes: [u8; 16],
+}
+
+#[pymethods]
+impl PyDecimal128 {
+    /// Build a Decimal128 from a `decimal.Decimal`, its string form or another Decimal128.
+    #[new]
+    fn new(value: &Bound<'_, PyAny>) -> PyResult<Self> {
+        if let Ok(other) = value.cast::<PyDecimal128>() {
+            return Ok(other.get().clone());
+        }
+        let text = value.str()?.to_string();
+        text.parse::<Decimal128>()
+            .map(PyDecimal128::from)
+            .map_err(|e| PyValueError::new_err(format!("Invalid Decimal128 '{}': {}", text, e)))
+    }
+
+    /// The value as a `decimal.Decimal`.
+    fn to_decimal(&self, py: Python) -> PyResult<Py<PyAny>> {
+        py_decimal_from_str(py, &self.__str__())
+    }
+
+    /// The 16 bytes of the value in the BSON binary integer decimal encoding.
+    #[getter]
+    fn bid<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.bytes)
+    }
+
+    fn __str__(&self) -> String {
+        Decimal128::from_bytes(self.bytes).to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Decimal128('{}')", self.__str__())
+    }
+}
+
+impl From<Decimal128> for PyDecimal128 {
+    fn from(decimal: Decimal128) -> PyDecimal128 {
+        PyDecimal128 {
+            bytes: decimal.bytes(),
+        }
+    }
+}
+
+/// BSON Timestamp, an internal MongoDB type made of seconds since the epoch and an ordinal.
+#[pyclass(name = "Timestamp", frozen, eq, ord, hash)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PyTimestamp {
+    pub(crate) time: u32,
+    pub(crate) inc: u32,
+}
+
+#[pymethods]
+impl PyTimestamp {
+    #[new]
+    fn new(time: u32, inc: u32) -> Self {
+        PyTimestamp { time, inc }
+    }
+
+    #[getter]
+    fn time(&self) -> u32 {
+        self.time
+    }
+
+    #[getter]
+    fn inc(&self) -> u32 {
+        self.inc
+    }
+
+    /// The `time` part as an aware UTC datetime.
+    fn as_datetime(&self, py: Python) -> PyResult<Py<PyAny>> {
+        let opts = CodecOptions {
+            tz_aware: true,
+            ..Default::default()
+        };
+        bson_datetime_to_py(py, &DateTime::from_millis(self.time as i64 * 1000), &opts)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Timestamp({}, {})", self.time, self.inc)
+    }
+}
+
+/// BSON MinKey, compares lower than every other BSON value.
+#[pyclass(name = "MinKey", frozen, eq, hash)]
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct PyMinKey;
+
+#[pymethods]
+impl PyMinKey {
+    #[new]
+    fn new() -> Self {
+        PyMinKey
+    }
+
+    fn __repr__(&self) -> &'static str {
+        "MinKey()"
+    }
+}
+
+/// BSON MaxKey, compares higher than every other BSON value.
+#[pyclass(name = "MaxKey", frozen, eq, hash)]
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct PyMaxKey;
+
+#[pymethods]
+impl PyMaxKey {
+    #[new]
+    fn new() -> Self {
+        PyMaxKey
+    }
+
+    fn __repr__(&self) -> &'static str {
+        "MaxKey()"
+    }
+}
+
+/// BSON JavaScript code, with an optional scope document. The scope is kept as a dict and
+/// converted with the codec options of the collection it is written to or read from.
+#[pyclass(name = "Code", frozen)]
+pub struct PyCode {
+    pub(crate) code: String,
+    pub(crate) scope: Option<Py<PyDict>>,
+}
+
+#[pymethods]
+impl PyCode {
+    #[new]
+    #[pyo3(signature = (code, scope=None))]
+    fn new(code: String, scope: Option<Bound<'_, PyDict>>) -> Self {
+        PyCode {
+            code,
+            scope: scope.map(Bound::unbind),
+        }
+    }
+
+    #[getter]
+    fn code(&self) -> &str {
+        &self.code
+    }
+
+    #[getter]
+    fn scope(&self, py: Python) -> Option<Py<PyDict>> {
+        self.scope.as_ref().map(|scope| scope.clone_ref(py))
+    }
+
+    fn __eq__(&self, other: &Bound<'_, PyAny>) -> PyResult<bool> {
+        let py = other.py();
+        let Ok(other) = other.cast::<PyCode>() else {
+            return Ok(false);
+        };
+        let other = other.get();
+        if self.code != other.code {
+            return Ok(false);
+        }
+        match (&self.scope, &other.scope) {
+            (Some(scope), Some(other_scope)) => scope.bind(py).eq(other_scope),
+            (None, None) => Ok(true),
+            _ => Ok(false),
+        }
+    }
+
+    fn __str__(&self) -> &str {
+        &self.code
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        match &self.scope {
+            Some(scope) => Ok(format!("Code({:?}, {})", self.code, scope.bind(py).repr()?)),
+            None => Ok(format!("Code({:?})", self.code)),
+        }
+    }
+}
+
+/// BSON Symbol, a deprecated string variant kept so it can be written back unchanged.
+#[pyclass(name = "Symbol", frozen, eq, hash)]
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct PySymbol {
+    pub(crate) symbol: String,
+}
+
+#[pymethods]
+impl PySymbol {
+    #[new]
+    fn new(symbol: String) -> Self {
+        PySymbol { symbol }
+    }
+
+    fn __str__(&self) -> &str {
+        &self.symbol
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Symbol({:?})", self.symbol)
+    }
+}
+
+/// BSON Undefined, a deprecated value kept distinct from `None` so it can be written back unchanged.
+#[pyclass(name = "Undefined", frozen, eq, hash)]
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct PyUndefined;
+
+#[pymethods]
+impl PyUndefined {
+    #[new]
+    fn new() -> Self {
+        PyUndefined
+    }
+
+    fn __repr__(&self) -> &'static str {
+        "Undefined()"
+    }
+}
+
+/// BSON DBPointer, a deprecated type that is only read from existing data and written back as is.
+#[pyclass(name = "DBPointer", frozen, eq, hash)]
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct PyDbPointer {
+    pub(crate) inner: DbPointer,
+}
+
+#[pymethods]
+impl PyDbPointer {
+    fn __repr__(&self) -> String {
+        format!("{:?}", self.inner)
+    }
+}
+
+/// BSON binary data with an explicit subtype. Generic binary is read as `bytes` and subtype 4
+/// as `uuid.UUID`, every other subtype comes back as this class.
+#[pyclass(name = "Binary", frozen, eq, hash)]
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct PyBinary {
+    pub(crate) bytes: Vec<u8>,
+    pub(crate) subtype: u8,
+}
+
+#[pymethods]
+impl PyBinary {
+    #[new]
+    #[pyo3(signature = (data, subtype=0))]
+    fn new(data: Vec<u8>, subtype: u8) -> Self {
+        PyBinary {
+            bytes: data,
+            subtype,
+        }
+    }
+
+    #[getter]
+    fn subtype(&self) -> u8 {
+        self.subtype
+    }
+
+    #[getter]
+    fn data<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.bytes)
+    }
+
+    fn __bytes__<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.bytes)
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        let data = PyBytes::new(py, &self.bytes).repr()?;
+        Ok(format!("Binary({}, {})", data, self.subtype))
+    }
+}
+
+impl From<&Binary> for PyBinary {
+    fn from(binary: &Binary) -> PyBinary {
+        PyBinary {
+            bytes: binary.bytes.clone(),
+            subtype: binary.subtype.into(),
+        }
+    }
+}
+
+impl From<&PyBinary> for Binary {
+    fn from(binary: &PyBinary) -> Binary {
+        Binary {
+            subtype: binary.subtype.into(),
+            bytes: binary.bytes.clone(),
+        }
+    }
+}
+
+/// A BSON document kept as raw bytes. Fields are only converted to Python objects when they are
+/// accessed, embedded documents come back as `RawDocument` as well.
+#[pyclass(name = "RawDocument", frozen)]
+pub struct PyRawDocument {
+    pub(crate) raw: RawDocumentBuf,
+    codec_options: Arc<CodecOptions>,
+}
+
+#[pymethods]
+impl PyRawDocument {
+    #[new]
+    fn new(data: &Bound<'_, PyAny>) -> PyResult<Self> {
+        Ok(PyRawDocument::from_raw(
+            raw_document_from_py(data, "data")?,
+            Arc::default(),
+        ))
+    }
+
+    /// The BSON bytes of the document.
+    #[getter]
+    fn raw<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, self.raw.as_bytes())
+    }
+
+    fn __bytes__<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, self.raw.as_bytes())
+    }
+
+    fn __getitem__(&self, py: Python, key: &str) -> PyResult<Py<PyAny>> {
+        match self.raw.get(key).map_err(invalid_bson)? {
+            Some(value) => raw_bson_to_py_obj(py, value, &self.codec_options),
+            None => Err(PyKeyError::new_err(key.to_string())),
+        }
+    }
+
+    #[pyo3(signature = (key, default=None))]
+    fn get(&self, py: Python, key: &str, default: Option<Py<PyAny>>) -> PyResult<Py<PyAny>> {
+        match self.raw.get(key).map_err(invalid_bson)? {
+            Some(value) => raw_bson_to_py_obj(py, value, &self.codec_options),
+            None => Ok(default.unwrap_or_else(|| py.None())),
+        }
+    }
+
+    fn __contains__(&self, key: &str) -> PyResult<bool> {
+        Ok(self.raw.get(key).map_err(invalid_bson)?.is_some())
+    }
+
+    fn __len__(&self) -> PyResult<usize> {
+        Ok(self.keys()?.len())
+    }
+
+    fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        PyList::new(py, self.keys()?)?
+            .try_iter()
+            .map(|iter| iter.into_any())
+    }
+
+    fn keys(&self) -> PyResult<Vec<String>> {
+        self.raw
+            .iter()
+            .map(|element| element.map(|(key, _)| key.to_string()))
+            .collect::<Result<_, _>>()
+            .map_err(invalid_bson)
+    }
+
+    /// Decode the whole document into a dict.
+    fn to_dict(&self, py: Python) -> PyResult<Py<PyDict>> {
+        let doc = self.raw.to_document().map_err(invalid_bson)?;
+        document_to_pydict(py, &doc, &self.codec_options)
+    }
+
+    fn __eq__(&self, other: &Bound<'_, PyAny>) -> bool {
+        match other.cast::<PyRawDocument>() {
+            Ok(other) => self.raw.as_bytes() == other.get().raw.as_bytes(),
+            Err(_) => false,
+        }
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        let data = PyBytes::new(py, self.raw.as_bytes()).repr()?;
+        Ok(format!("RawDocument({})", data))
+    }
+}
+
+impl PyRawDocument {
+    pub(crate) fn from_raw(raw: RawDocumentBuf, codec_options: Arc<CodecOptions>) -> PyRawDocument {
+        PyRawDocument { raw, codec_options }
+    }
+}
+
+/// Read BSON bytes (`bytes`, `bytearray` or a `RawDocument`) into a checked raw document.
+pub(crate) fn raw_document_from_py(obj: &Bound<'_, PyAny>, path: &str) -> PyResult<RawDocumentBuf> {
+    let bytes = if let Ok(raw) = obj.cast::<PyRawDocument>() {
+        return Ok(raw.get().raw.clone());
+    } else if let Ok(bytes) = obj.cast::<PyBytes>() {
+        bytes.as_bytes().to_vec()
+    } else if let Ok(bytearray) = obj.cast::<PyByteArray>() {
+        bytearray.to_vec()
+    } else {
+        return Err(PyTypeError::new_err(format!(
+            "{}: expected BSON bytes, got '{}'",
+            path,
+            obj.get_type().name()?
+        )));
+    };
+    let raw = RawDocumentBuf::from_bytes(bytes)
+        .map_err(|e| PyValueError::new_err(format!("{}: invalid BSON document: {}", path, e)))?;
+    // Walk the top level once so a truncated document fails here and not on field access
+    for element in raw.iter() {
+        element.map_err(|e| {
+            PyValueError::new_err(format!("{}: invalid BSON document: {}", path, e))
+        })?;
+    }
+    Ok(raw)
+}
+
+fn raw_bson_to_py_obj(
+    py: Python,
+    value: RawBsonRef<'_>,
+    opts: &Arc<CodecOptions>,
+) -> PyResult<Py<PyAny>> {
+    match value {
+        // Tagged custom values are decoded by their codec instead of staying raw
+        RawBsonRef::Document(doc) if opts.codecs.is_empty() || !doc_has_tag(doc) => Ok(Py::new(
+            py,
+            PyRawDocument::from_raw(doc.to_raw_document_buf(), opts.clone()),
+        )?
+        .into_any()),
+        RawBsonRef::Array(array) => {
+            let py_list = PyList::empty(py);
+            for item in array {
+                py_list.append(raw_bson_to_py_obj(py, item.map_err(invalid_bson)?, opts)?)?;
+            }
+            Ok(py_list.into_any().unbind())
+        }
+        other => bson_to_py_obj(py, &Bson::try_from(other).map_err(invalid_bson)?, opts),
+    }
+}
+
+fn doc_has_tag(doc: &RawDocument) -> bool {
+    matches!(doc.get(TAG_KEY), Ok(Some(_)))
+}
+
+fn invalid_bson(e: polodb_core::bson::raw::Error) -> PyErr {
+    PyValueError::new_err(format!("invalid BSON document: {}", e))
+}
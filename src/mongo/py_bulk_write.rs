@@ -0,0 +1,350 @@
+use crate::mongo::helper_query::{validate_replacement, validate_update};
+use crate::mongo::helper_type_translator::{
+    CodecOptions, bson_to_py_obj, convert_py_obj_to_document, convert_py_obj_to_filter,
+};
+use crate::mongo::helper_write::CollectionWriter;
+use crate::mongo::py_errors::BulkWriteError;
+use crate::mongo::py_results::{BulkWriteCounts, py_bool};
+use polodb_core::bson::Document;
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+/// Insert `document`, a `bulk_write` request.
+#[pyclass(name = "InsertOne", frozen)]
+pub struct PyInsertOne {
+    #[pyo3(get)]
+    document: Py<PyAny>,
+}
+
+#[pymethods]
+impl PyInsertOne {
+    #[new]
+    fn new(document: Py<PyAny>) -> Self {
+        PyInsertOne { document }
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!("InsertOne({})", self.document.bind(py).repr()?))
+    }
+}
+
+/// Update the first document matching `filter`, a `bulk_write` request.
+#[pyclass(name = "UpdateOne", frozen)]
+pub struct PyUpdateOne {
+    #[pyo3(get)]
+    filter: Py<PyAny>,
+    #[pyo3(get)]
+    update: Py<PyAny>,
+    #[pyo3(get)]
+    upsert: bool,
+}
+
+#[pymethods]
+impl PyUpdateOne {
+    #[new]
+    #[pyo3(signature = (filter, update, upsert=false))]
+    fn new(filter: Py<PyAny>, update: Py<PyAny>, upsert: bool) -> Self {
+        PyUpdateOne {
+            filter,
+            update,
+            upsert,
+        }
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!(
+            "UpdateOne({}, {}, upsert={})",
+            self.filter.bind(py).repr()?,
+            self.update.bind(py).repr()?,
+            py_bool(self.upsert)
+        ))
+    }
+}
+
+/// Update every document matching `filter`, a `bulk_write` request.
+#[pyclass(name = "UpdateMany", frozen)]
+pub struct PyUpdateMany {
+    #[pyo3(get)]
+    filter: Py<PyAny>,
+    #[pyo3(get)]
+    update: Py<PyAny>,
+    #[pyo3(get)]
+    upsert: bool,
+}
+
+#[pymethods]
+impl PyUpdateMany {
+    #[new]
+    #[pyo3(signature = (filter, update, upsert=false))]
+    fn new(filter: Py<PyAny>, update: Py<PyAny>, upsert: bool) -> Self {
+        PyUpdateMany {
+            filter,
+            update,
+            upsert,
+        }
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!(
+            "UpdateMany({}, {}, upsert={})",
+            self.filter.bind(py).repr()?,
+            self.update.bind(py).repr()?,
+            py_bool(self.upsert)
+        ))
+    }
+}
+
+/// Replace the first document matching `filter`, a `bulk_write` request.
+#[pyclass(name = "ReplaceOne", frozen)]
+pub struct PyReplaceOne {
+    #[pyo3(get)]
+    filter: Py<PyAny>,
+    #[pyo3(get)]
+    replacement: Py<PyAny>,
+    #[pyo3(get)]
+    upsert: bool,
+}
+
+#[pymethods]
+impl PyReplaceOne {
+    #[new]
+    #[pyo3(signature = (filter, replacement, upsert=false))]
+    fn new(filter: Py<PyAny>, replacement: Py<PyAny>, upsert: bool) -> Self {
+        PyReplaceOne {
+            filter,
+            replacement,
+            upsert,
+        }
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!(
+            "ReplaceOne({}, {}, upsert={})",
+            self.filter.bind(py).repr()?,
+            self.replacement.bind(py).repr()?,
+            py_bool(self.upsert)
+        ))
+    }
+}
+
+/// Delete the first document matching `filter`, a `bulk_write` request.
+#[pyclass(name = "DeleteOne", frozen)]
+pub struct PyDeleteOne {
+    #[pyo3(get)]
+    filter: Py<PyAny>,
+}
+
+#[pymethods]
+impl PyDeleteOne {
+    #[new]
+    fn new(filter: Py<PyAny>) -> Self {
+        PyDeleteOne { filter }
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!("DeleteOne({})", self.filter.bind(py).repr()?))
+    }
+}
+
+/// Delete every document matching `filter`, a `bulk_write` request.
+#[pyclass(name = "DeleteMany", frozen)]
+pub struct PyDeleteMany {
+    #[pyo3(get)]
+    filter: Py<PyAny>,
+}
+
+#[pymethods]
+impl PyDeleteMany {
+    #[new]
+    fn new(filter: Py<PyAny>) -> Self {
+        PyDeleteMany { filter }
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!("DeleteMany({})", self.filter.bind(py).repr()?))
+    }
+}
+
+/// A `bulk_write` request converted to BSON.
+pub enum WriteModel {
+    InsertOne(Document),
+    Update {
+        filter: Document,
+        update: Document,
+        many: bool,
+        upsert: bool,
+    },
+    ReplaceOne {
+        filter: Document,
+        replacement: Document,
+        upsert: bool,
+    },
+    Delete {
+        filter: Document,
+        many: bool,
+    },
+}
+
+impl WriteModel {
+    /// Convert and validate one request, `path` names it in error messages.
+    pub fn from_py(
+        request: &Bound<'_, PyAny>,
+        path: &str,
+        opts: &CodecOptions,
+    ) -> PyResult<WriteModel> {
+        let py = request.py();
+        let filter = |filter: &Py<PyAny>| {
+            convert_py_obj_to_filter(filter.bind(py), &format!("{}.filter", path), opts)
+        };
+        let update = |update: &Py<PyAny>| {
+            let update =
+                convert_py_obj_to_document(update.bind(py), &format!("{}.update", path), opts)?;
+            validate_update(&update)?;
+            Ok::<_, PyErr>(update)
+        };
+
+        if let Ok(request) = request.cast::<PyInsertOne>() {
+            let document = request.get().document.bind(py);
+            return Ok(WriteModel::InsertOne(convert_py_obj_to_document(
+                document,
+                &format!("{}.document", path),
+                opts,
+            )?));
+        }
+        if let Ok(request) = request.cast::<PyUpdateOne>() {
+            let request = request.get();
+            return Ok(WriteModel::Update {
+                filter: filter(&request.filter)?,
+                update: update(&request.update)?,
+                many: false,
+                upsert: request.upsert,
+            });
+        }
+        if let Ok(request) = request.cast::<PyUpdateMany>() {
+            let request = request.get();
+            return Ok(WriteModel::Update {
+                filter: filter(&request.filter)?,
+                update: update(&request.update)?,
+                many: true,
+                upsert: request.upsert,
+            });
+        }
+        if let Ok(request) = request.cast::<PyReplaceOne>() {
+            let request = request.get();
+            let replacement = convert_py_obj_to_document(
+                request.replacement.bind(py),
+                &format!("{}.replacement", path),
+                opts,
+            )?;
+            validate_replacement(&replacement)?;
+            return Ok(WriteModel::ReplaceOne {
+                filter: filter(&request.filter)?,
+                replacement,
+                upsert: request.upsert,
+            });
+        }
+        if let Ok(request) = request.cast::<PyDeleteOne>() {
+            return Ok(WriteModel::Delete {
+                filter: filter(&request.get().filter)?,
+                many: false,
+            });
+        }
+        if let Ok(request) = request.cast::<PyDeleteMany>() {
+            return Ok(WriteModel::Delete {
+                filter: filter(&request.get().filter)?,
+                many: true,
+            });
+        }
+        Err(PyTypeError::new_err(format!(
+            "{}: expected InsertOne, UpdateOne, UpdateMany, ReplaceOne, DeleteOne or DeleteMany, \
+             got '{}'",
+            path,
+            request
+                .get_type()
+                .name()
+                .map(|n| n.to_string())
+                .unwrap_or_default()
+        )))
+    }
+
+    /// Apply the request at `index` through `writer`, adding what it did to `counts`.
+    pub fn apply(
+        self,
+        index: usize,
+        writer: &CollectionWriter,
+        counts: &mut BulkWriteCounts,
+    ) -> PyResult<()> {
+        let (update_result, upserted_id) = match self {
+            WriteModel::InsertOne(document) => {
+                writer.insert_one(document)?;
+                counts.inserted_count += 1;
+                return Ok(());
+            }
+            WriteModel::Delete { filter, many } => {
+                counts.deleted_count += writer.delete(filter, many)?.deleted_count;
+                return Ok(());
+            }
+            WriteModel::Update {
+                filter,
+                update,
+                many,
+                upsert,
+            } => writer.update(filter, update, many, upsert)?,
+            WriteModel::ReplaceOne {
+                filter,
+                replacement,
+                upsert,
+            } => writer.replace(filter, replacement, upsert)?,
+        };
+        counts.matched_count += update_result.matched_count;
+        counts.modified_count += update_result.modified_count;
+        if let Some(id) = upserted_id {
+            counts.upserted_ids.push((index, id));
+        }
+        Ok(())
+    }
+}
+
+/// Build the `BulkWriteError` for the requests that failed, its `details` follow pymongo.
+pub fn bulk_write_error(
+    py: Python,
+    requests: &Bound<'_, PyList>,
+    counts: &BulkWriteCounts,
+    write_errors: Vec<(usize, PyErr)>,
+    opts: &CodecOptions,
+) -> PyResult<PyErr> {
+    let indexes: Vec<usize> = write_errors.iter().map(|(index, _)| *index).collect();
+    let py_write_errors = PyList::empty(py);
+    for (index, err) in &write_errors {
+        let py_write_error = PyDict::new(py);
+        py_write_error.set_item("index", index)?;
+        py_write_error.set_item("errmsg", err.value(py).str()?)?;
+        py_write_error.set_item("op", requests.get_item(*index)?)?;
+        py_write_errors.append(py_write_error)?;
+    }
+    let py_upserted = PyList::empty(py);
+    for (index, id) in &counts.upserted_ids {
+        let py_entry = PyDict::new(py);
+        py_entry.set_item("index", index)?;
+        py_entry.set_item("_id", bson_to_py_obj(py, id, opts)?)?;
+        py_upserted.append(py_entry)?;
+    }
+
+    let details = PyDict::new(py);
+    details.set_item("writeErrors", py_write_errors)?;
+    details.set_item("nInserted", counts.inserted_count)?;
+    details.set_item("nUpserted", counts.upserted_ids.len())?;
+    details.set_item("nMatched", counts.matched_count)?;
+    details.set_item("nModified", counts.modified_count)?;
+    details.set_item("nRemoved", counts.deleted_count)?;
+    details.set_item("upserted", py_upserted)?;
+
+    let first_message = write_errors[0].1.value(py).str()?;
+    let err = BulkWriteError::new_err(format!(
+        "bulk write failed at indexes {:?}: {}",
+        indexes, first_message
+    ));
+    err.value(py).setattr("details", details)?;
+    Ok(err)
+}
@@ -0,0 +1,70 @@
+use crate::mongo::helper_type_translator::CodecOptions;
+use pyo3::prelude::*;
+use std::sync::Arc;
+
+/// The options a database or collection converts values with, see `PyDatabase`. Passed to
+/// `to_extended_json` and `from_extended_json` to convert like that database would, custom
+/// codecs included. Options built directly have no custom codecs.
+#[pyclass(name = "CodecOptions", frozen)]
+pub struct PyCodecOptions {
+    inner: Arc<CodecOptions>,
+}
+
+#[pymethods]
+impl PyCodecOptions {
+    #[new]
+    #[pyo3(signature = (tz_aware=false, tzinfo=None, strict=false))]
+    fn new(tz_aware: bool, tzinfo: Option<Py<PyAny>>, strict: bool) -> PyCodecOptions {
+        PyCodecOptions {
+            inner: Arc::new(CodecOptions {
+                tz_aware,
+                tzinfo,
+                strict,
+                codecs: Arc::default(),
+            }),
+        }
+    }
+
+    #[getter]
+    fn tz_aware(&self) -> bool {
+        self.inner.tz_aware
+    }
+
+    #[getter]
+    fn tzinfo(&self, py: Python) -> Option<Py<PyAny>> {
+        self.inner
+            .tzinfo
+            .as_ref()
+            .map(|tzinfo| tzinfo.clone_ref(py))
+    }
+
+    #[getter]
+    fn strict(&self) -> bool {
+        self.inner.strict
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        let tzinfo = match &self.inner.tzinfo {
+            Some(tzinfo) => tzinfo.bind(py).repr()?.to_string(),
+            None => "None".to_string(),
+        };
+        Ok(format!(
+            "CodecOptions(tz_aware={}, tzinfo={}, strict={})",
+            if self.inner.tz_aware { "True" } else { "False" },
+            tzinfo,
+            if self.inner.strict { "True" } else { "False" }
+        ))
+    }
+}
+
+impl PyCodecOptions {
+    pub fn options(&self) -> &CodecOptions {
+        &self.inner
+    }
+}
+
+impl From<Arc<CodecOptions>> for PyCodecOptions {
+    fn from(inner: Arc<CodecOptions>) -> PyCodecOptions {
+        PyCodecOptions { inner }
+    }
+}
@@ -0,0 +1,210 @@
+use crate::mongo::helper_query::Projection;
+use crate::mongo::helper_type_translator::{CodecOptions, document_to_pydict};
+use crate::mongo::py_errors::ClosedError;
+use polodb_core::ClientCursor;
+use polodb_core::bson::Document;
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use pyo3::types::PyList;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+const DEFAULT_BATCH_SIZE: usize = 100;
+
+/// A live polodb query, None once closing the database released it.
+pub type QuerySlot = Mutex<Option<ClientCursor<Document>>>;
+
+/// A query shared so that closing the database can release it while a `Cursor` still refers
+/// to it, see `SharedDatabase::track_query`.
+pub type SharedQuery = Arc<QuerySlot>;
+
+/// Where a cursor pulls its documents from.
+pub enum CursorSource {
+    /// A live polodb query, read on demand.
+    Query(SharedQuery),
+    /// Results that had to be materialized first, e.g. to sort them.
+    Buffered(std::vec::IntoIter<Document>),
+}
+
+impl CursorSource {
+    /// A query read within the call that started it.
+    pub fn query(cursor: ClientCursor<Document>) -> CursorSource {
+        CursorSource::Query(Arc::new(Mutex::new(Some(cursor))))
+    }
+
+    fn next_document(&mut self) -> polodb_core::Result<Option<Document>> {
+        match self {
+            CursorSource::Query(query) => {
+                let mut query = query.lock().map_err(|_| polodb_core::Error::DbIsClosed)?;
+                // Released by closing the database
+                let Some(cursor) = query.as_mut() else {
+                    return Err(polodb_core::Error::DbIsClosed);
+                };
+                if cursor.advance()? {
+                    cursor.deserialize_current().map(Some)
+                } else {
+                    Ok(None)
+                }
+            }
+            CursorSource::Buffered(docs) => Ok(docs.next()),
+        }
+    }
+
+    /// Read every remaining document.
+    pub fn collect_documents(mut self) -> polodb_core::Result<Vec<Document>> {
+        let mut docs = Vec::new();
+        while let Some(doc) = self.next_document()? {
+            docs.push(doc);
+        }
+        Ok(docs)
+    }
+}
+
+/// Iterator over the results of `find` or `aggregate`. Documents are read from the database
+/// `batch_size` at a time and converted to dicts one by one as they are consumed.
+#[pyclass(name = "Cursor")]
+pub struct PyCursor {
+    // None once the source is exhausted or the cursor is closed
+    source: Option<CursorSource>,
+    buffer: VecDeque<Document>,
+    // Raised once the documents read before the failure are consumed
+    pending_error: Option<PyErr>,
+    batch_size: usize,
+    projection: Option<Projection>,
+    codec_options: Arc<CodecOptions>,
+}
+
+#[pymethods]
+impl PyCursor {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python) -> PyResult<Option<Py<PyAny>>> {
+        match self.next_document()? {
+            Some(doc) => Ok(Some(
+                document_to_pydict(py, &doc, &self.codec_options)?.into_any(),
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Set how many documents are read from the database at once, 0 restores the default.
+    fn batch_size(mut slf: PyRefMut<'_, Self>, batch_size: usize) -> PyRefMut<'_, Self> {
+        slf.set_batch_size(batch_size);
+        slf
+    }
+
+    /// Return the remaining documents as a list, at most `length` of them when given.
+    #[pyo3(name = "to_list", signature = (length=None))]
+    fn take_list(&mut self, py: Python, length: Option<usize>) -> PyResult<Py<PyList>> {
+        let py_result = PyList::empty(py);
+        while length.is_none_or(|length| py_result.len() < length) {
+            match self.next_document()? {
+                Some(doc) => {
+                    py_result.append(document_to_pydict(py, &doc, &self.codec_options)?)?
+                }
+                None => break,
+            }
+        }
+        Ok(py_result.unbind())
+    }
+
+    /// Release the underlying query, later iteration stops immediately.
+    fn close(&mut self) {
+        self.source = None;
+        self.buffer.clear();
+        self.pending_error = None;
+    }
+
+    /// False once every document was consumed or the cursor was closed.
+    #[getter]
+    fn alive(&self) -> bool {
+        self.source.is_some() || !self.buffer.is_empty()
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __exit__(
+        &mut self,
+        _exc_type: Option<&Bound<'_, PyAny>>,
+        _exc_value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) {
+        self.close();
+    }
+}
+
+impl PyCursor {
+    pub fn new(
+        source: CursorSource,
+        projection: Option<Projection>,
+        codec_options: Arc<CodecOptions>,
+    ) -> PyCursor {
+        PyCursor {
+            source: Some(source),
+            buffer: VecDeque::new(),
+            pending_error: None,
+            batch_size: DEFAULT_BATCH_SIZE,
+            projection,
+            codec_options,
+        }
+    }
+
+    pub fn set_batch_size(&mut self, batch_size: usize) {
+        self.batch_size = match batch_size {
+            0 => DEFAULT_BATCH_SIZE,
+            batch_size => batch_size,
+        };
+    }
+
+    /// Read every remaining document, with the projection applied.
+    pub fn collect_documents(mut self) -> PyResult<Vec<Document>> {
+        let mut docs = Vec::new();
+        while let Some(doc) = self.next_document()? {
+            docs.push(doc);
+        }
+        Ok(docs)
+    }
+
+    pub fn next_document(&mut self) -> PyResult<Option<Document>> {
+        if self.buffer.is_empty() {
+            self.fill_buffer();
+        }
+        if self.buffer.is_empty()
+            && let Some(err) = self.pending_error.take()
+        {
+            return Err(err);
+        }
+        Ok(self.buffer.pop_front().map(|doc| match &self.projection {
+            Some(projection) => projection.apply(doc),
+            None => doc,
+        }))
+    }
+
+    fn fill_buffer(&mut self) {
+        while self.buffer.len() < self.batch_size {
+            let Some(source) = self.source.as_mut() else {
+                break;
+            };
+            match source.next_document() {
+                Ok(Some(doc)) => self.buffer.push_back(doc),
+                Ok(None) => self.source = None,
+                Err(polodb_core::Error::DbIsClosed) => {
+                    self.source = None;
+                    self.pending_error = Some(ClosedError::new_err(
+                        "Cursor error: the database has been closed",
+                    ));
+                }
+                Err(e) => {
+                    // A failed query cannot be resumed
+                    self.source = None;
+                    self.pending_error =
+                        Some(PyRuntimeError::new_err(format!("Cursor error: {}", e)));
+                }
+            }
+        }
+    }
+}
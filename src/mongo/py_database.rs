@@ -1,21 +1,245 @@
+use crate::mongo::codec_registry::CodecRegistry;
+use crate::mongo::helper_query::{
+    DistinctValues, FindOptions, convert_py_obj_to_return_document, sort_documents,
+    validate_replacement, validate_update,
+};
+use crate::mongo::helper_save::{copy_collection, save_database};
+use crate::mongo::helper_stats::{
+    CollectionSize, SizeChanges, SizeCounters, collection_stats, database_stats, measure_collection,
+};
+use crate::mongo::helper_ttl::{TtlMonitor, purge_expired};
 use crate::mongo::helper_type_translator::{
-    bson_to_py_obj, convert_py_list_to_vec_document, convert_py_obj_to_document,
-    delete_result_to_pydict, document_to_pydict, update_result_to_pydict,
+    CodecOptions, bson_to_extended_json, bson_to_py_obj, convert_py_list_to_vec_document,
+    convert_py_obj_to_document, convert_py_obj_to_filter, document_to_pydict,
+    extended_json_to_documents, raw_document_to_document,
+};
+use crate::mongo::helper_write::{CollectionWriter, Modification};
+use crate::mongo::py_bson_types::raw_document_from_py;
+use crate::mongo::py_bulk_write::{WriteModel, bulk_write_error};
+use crate::mongo::py_codec_options::PyCodecOptions;
+use crate::mongo::py_cursor::{CursorSource, PyCursor, QuerySlot, SharedQuery};
+use crate::mongo::py_errors::{ClosedError, write_error};
+use crate::mongo::py_index::{
+    INDEX_CATALOG, IndexCache, IndexSpec, PyIndexModel, create_collection, create_index,
+    drop_collection, drop_index, index_name_from_py, list_indexes,
 };
-use polodb_core::bson::Document;
-use polodb_core::options::UpdateOptions;
-use polodb_core::{Collection, CollectionT, Database};
+use crate::mongo::py_results::{
+    BulkWriteCounts, PyBulkWriteResult, PyDeleteResult, PyInsertManyResult, PyInsertOneResult,
+    PyUpdateResult,
+};
+use crate::mongo::py_transaction::{PyTransaction, TransactionSession};
+use polodb_core::action::Find;
+use polodb_core::bson::{Bson, Document, doc};
+use polodb_core::results::InsertOneResult;
+use polodb_core::{ClientCursor, Collection, CollectionT, Database, Transaction};
 use pyo3::exceptions::PyOSError;
 use pyo3::exceptions::PyRuntimeError; // Import PyRuntimeError for error handling
-use pyo3::types::{PyDict, PyList};
-use pyo3::{IntoPyObjectExt, prelude::*};
-use std::borrow::Borrow;
-use std::path::Path;
-use std::sync::{Arc, Mutex};
+use pyo3::exceptions::PyTypeError;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyList, PyString, PyType};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The database shared by a `PyDatabase` and its collections, until it is closed. polodb
+/// refuses to close while a transaction is open, and every live query holds one, so the
+/// queries of cursors and the user's transactions are tracked to end them first.
+pub struct SharedDatabase {
+    database: Mutex<Option<Database>>,
+    queries: Mutex<Vec<Weak<QuerySlot>>>,
+    sessions: Mutex<Vec<Weak<TransactionSession>>>,
+    sizes: Arc<SizeCounters>,
+    path: PathBuf,
+    // Set for a database opened by `open_temporary`, whose directory is removed on close
+    temporary: bool,
+}
+
+impl SharedDatabase {
+    fn new(database: Database, path: PathBuf, temporary: bool) -> SharedDatabase {
+        SharedDatabase {
+            database: Mutex::new(Some(database)),
+            queries: Mutex::default(),
+            sessions: Mutex::default(),
+            sizes: Arc::default(),
+            path,
+            temporary,
+        }
+    }
+
+    /// Run `operation` with the database locked, failing with `ClosedError` once it is closed.
+    pub fn with_database<R>(
+        &self,
+        operation: impl FnOnce(&Database) -> PyResult<R>,
+    ) -> PyResult<R> {
+        let guard = self
+            .database
+            .lock()
+            .map_err(|e| PyRuntimeError::new_err(format!("Failed to lock: {}", e)))?;
+        match guard.as_ref() {
+            Some(database) => operation(database),
+            None => Err(ClosedError::new_err("Cannot use a closed database")),
+        }
+    }
+
+    /// The size of each collection, kept current by the writes that commit.
+    pub fn sizes(&self) -> &SizeCounters {
+        &self.sizes
+    }
+
+    /// Wrap a query a `Cursor` will read after the call that started it.
+    fn track_query(&self, cursor: ClientCursor<Document>) -> CursorSource {
+        let query: SharedQuery = Arc::new(Mutex::new(Some(cursor)));
+        if let Ok(mut queries) = self.queries.lock() {
+            queries.retain(|query| query.strong_count() > 0);
+            queries.push(Arc::downgrade(&query));
+        }
+        CursorSource::Query(query)
+    }
+
+    fn track_session(&self, session: &Arc<TransactionSession>) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.retain(|session| session.strong_count() > 0);
+            sessions.push(Arc::downgrade(session));
+        }
+    }
+
+    /// The open transactions other than `own` that have written documents.
+    pub fn writing_sessions(
+        &self,
+        own: Option<&Arc<TransactionSession>>,
+    ) -> Vec<Arc<TransactionSession>> {
+        let Ok(sessions) = self.sessions.lock() else {
+            return Vec::new();
+        };
+        sessions
+            .iter()
+            .filter_map(|session| session.upgrade())
+            .filter(|session| own.is_none_or(|own| !Arc::ptr_eq(own, session)))
+            .filter(|session| session.has_writes())
+            .collect()
+    }
+
+    /// End the tracked queries and transactions, then flush and close the database. Closing
+    /// twice does nothing.
+    fn close(&self) -> PyResult<()> {
+        let mut guard = self
+            .database
+            .lock()
+            .map_err(|e| PyRuntimeError::new_err(format!("Failed to lock: {}", e)))?;
+        if guard.is_none() {
+            return Ok(());
+        }
+        if let Ok(mut queries) = self.queries.lock() {
+            for query in queries.drain(..).filter_map(|query| query.upgrade()) {
+                if let Ok(mut query) = query.lock() {
+                    query.take();
+                }
+            }
+        }
+        if let Ok(mut sessions) = self.sessions.lock() {
+            for session in sessions.drain(..).filter_map(|session| session.upgrade()) {
+                session.close();
+            }
+        }
+        // polodb flushes its write-ahead log and releases the lock on the path when dropped
+        drop(guard.take());
+        if self.temporary {
+            let _ = std::fs::remove_dir_all(&self.path);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for SharedDatabase {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+/// The collection a `PyCollection` works on: on its own, or inside a transaction started by
+/// `PyDatabase.start_transaction`.
+enum CollectionHandle {
+    Standalone(Collection<Document>),
+    // Only the name is kept, the session owns the transaction so that closing can end it
+    Session(String, Arc<TransactionSession>),
+}
+
+impl CollectionHandle {
+    fn name(&self) -> &str {
+        match self {
+            CollectionHandle::Standalone(collection) => collection.name(),
+            CollectionHandle::Session(name, _) => name,
+        }
+    }
+
+    /// Start a query, `skip` and `limit` apply when not 0.
+    fn find(
+        &self,
+        filter: Document,
+        skip: u64,
+        limit: u64,
+    ) -> polodb_core::Result<ClientCursor<Document>> {
+        fn configure<'a, 'b>(
+            mut find: Find<'a, 'b, Document>,
+            skip: u64,
+            limit: u64,
+        ) -> Find<'a, 'b, Document> {
+            if skip > 0 {
+                find = find.skip(skip);
+            }
+            if limit > 0 {
+                find = find.limit(limit);
+            }
+            find
+        }
+        match self {
+            CollectionHandle::Standalone(collection) => {
+                configure(collection.find(filter), skip, limit).run()
+            }
+            CollectionHandle::Session(name, session) => session.read(|txn| {
+                configure(txn.collection::<Document>(name).find(filter), skip, limit).run()
+            }),
+        }
+    }
+
+    fn find_one(&self, filter: Document) -> polodb_core::Result<Option<Document>> {
+        match self {
+            CollectionHandle::Standalone(collection) => collection.find_one(filter),
+            CollectionHandle::Session(name, session) => {
+                session.read(|txn| txn.collection::<Document>(name).find_one(filter))
+            }
+        }
+    }
+
+    fn count_documents(&self) -> polodb_core::Result<u64> {
+        match self {
+            CollectionHandle::Standalone(collection) => collection.count_documents(),
+            CollectionHandle::Session(name, session) => {
+                session.read(|txn| txn.collection::<Document>(name).count_documents())
+            }
+        }
+    }
+
+    fn aggregate(&self, pipeline: Vec<Document>) -> polodb_core::Result<ClientCursor<Document>> {
+        match self {
+            CollectionHandle::Standalone(collection) => collection.aggregate(pipeline).run(),
+            CollectionHandle::Session(name, session) => {
+                session.read(|txn| txn.collection::<Document>(name).aggregate(pipeline).run())
+            }
+        }
+    }
+}
 
 #[pyclass]
 pub struct PyCollection {
-    inner: Arc<Collection<Document>>, // Use Arc for thread-safe shared ownership
+    inner: CollectionHandle,
+    database: Arc<SharedDatabase>,
+    codec_options: Arc<CodecOptions>,
+    indexes: Arc<IndexCache>,
+    closed: AtomicBool,
 }
 
 #[pymethods]
@@ -24,352 +248,1115 @@ impl PyCollection {
         self.inner.name()
     }
 
-    pub fn insert_many(&self, doc: Py<PyList>) -> PyResult<Py<PyAny>> {
-        // Acquire the Python GIL (Global Interpreter Lock)
-        Python::attach(|py| {
-            // Now you can use `py` inside this block.
-
-            // Example: Create a Python object or interact with the Python runtime.
-            let bson_vec_docs: Vec<Document> =
-                convert_py_list_to_vec_document(&doc.into_py_any(py).unwrap());
-            // let bson_doc = convert_py_to_bson(doc);
-            match self.inner.insert_many(bson_vec_docs) {
-                Ok(result) => {
-                    // Create a Python object from the Rust result and return it
-                    let dict: Bound<'_, PyDict> = PyDict::new(py);
+    /// Close this handle, later calls on it raise `ClosedError`. The database and other
+    /// handles stay open, see `PyDatabase.close`.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+    }
 
-                    for (key, value) in &result.inserted_ids {
-                        dict.set_item(key, bson_to_py_obj(py, value)).unwrap();
-                    }
+    /// Statistics of the collection: `count`, `size` and `avgObjSize` in BSON bytes, `nindexes`,
+    /// and `indexSizes` per index name, None like `totalIndexSize`. The sizes come from counters
+    /// kept as writes commit, the first call after the database is opened measures every
+    /// document once.
+    pub fn stats(&self, py: Python) -> PyResult<Py<PyDict>> {
+        let stats = self.with_transaction("Collection stats", |txn, modify_error| {
+            let size = self.collection_size(txn, modify_error)?;
+            let specs = list_indexes(txn, self.inner.name(), modify_error)?;
+            Ok(collection_stats(self.inner.name(), size, &specs))
+        })?;
+        document_to_pydict(py, &stats, &self.codec_options)
+    }
 
-                    Ok(dict.into_py_any(py).unwrap())
-                }
-                Err(e) => {
-                    // Raise a Python exception on error
-                    Err(PyRuntimeError::new_err(format!("Insert many error: {}", e)))
-                }
-            }
+    /// Drop the collection with its documents and indexes, see `PyDatabase.drop_collection`.
+    pub fn drop(&self) -> PyResult<()> {
+        self.write_transaction("Drop collection", |txn, modify_error, changes| {
+            self.indexes_changed();
+            changes.recount(self.inner.name());
+            drop_collection(txn, self.inner.name(), modify_error)
         })
     }
 
-    pub fn insert_one(&self, doc: Py<PyDict>) -> PyResult<Py<PyAny>> {
-        // Acquire the Python GIL (Global Interpreter Lock)
-        Python::attach(|py| {
-            let bson_doc: Document = match convert_py_obj_to_document(&doc.into_py_any(py).unwrap())
-            {
-                Ok(d) => d,
-                Err(e) => return Err(PyRuntimeError::new_err(format!("Insert many error: {}", e))),
-            };
-            // let bson_doc = convert_py_to_bson(doc);
-            match self.inner.insert_one(bson_doc) {
-                Ok(result) => {
-                    // Create a Python object from the Rust result and return it
-                    let py_inserted_id = bson_to_py_obj(py, &result.inserted_id);
-                    let dict = PyDict::new(py);
-                    let dict_ref = dict.borrow();
-                    dict_ref.set_item("inserted_id", py_inserted_id)?;
-                    Ok(dict.into_py_any(py).unwrap())
-
-                    // Ok(Py::new(py, result)?.to_object(py))
-                }
-                Err(e) => {
-                    // Raise a Python exception on error
-                    Err(PyRuntimeError::new_err(format!("Insert error: {}", e)))
-                }
-            }
-        })
+    /// The options values of this collection are converted with, its own codecs included.
+    #[getter]
+    fn codec_options(&self) -> PyCodecOptions {
+        self.codec_options.clone().into()
+    }
+
+    /// Register a codec for `python_type` on this collection only, it takes precedence over the
+    /// codecs registered on the database. See `PyDatabase.register_type`.
+    #[pyo3(signature = (python_type, encode, decode=None, subtype=None, tag=None))]
+    pub fn register_type(
+        &self,
+        python_type: &Bound<'_, PyType>,
+        encode: Py<PyAny>,
+        decode: Option<Py<PyAny>>,
+        subtype: Option<u8>,
+        tag: Option<String>,
+    ) -> PyResult<()> {
+        self.codec_options
+            .codecs
+            .register(python_type, encode, decode, subtype, tag)
+    }
+
+    pub fn insert_many(&self, py: Python, docs: &Bound<'_, PyAny>) -> PyResult<PyInsertManyResult> {
+        let bson_vec_docs: Vec<Document> =
+            convert_py_list_to_vec_document(docs, "docs", &self.codec_options)?;
+        let result =
+            self.in_transaction("Insert many", |writer| writer.insert_many(bson_vec_docs))?;
+        PyInsertManyResult::new(py, result, &self.codec_options)
+    }
+
+    /// Insert documents given as BSON bytes (or `RawDocument`s) without building Python dicts.
+    /// A document whose `_id` is already stored raises `DuplicateKeyError` and nothing is written.
+    pub fn insert_raw_many(
+        &self,
+        py: Python,
+        docs: &Bound<'_, PyAny>,
+    ) -> PyResult<PyInsertManyResult> {
+        let list = docs.cast::<PyList>().map_err(|_| {
+            PyTypeError::new_err(format!(
+                "docs: expected a list of BSON documents, got '{}'",
+                docs.get_type()
+                    .name()
+                    .map(|n| n.to_string())
+                    .unwrap_or_default()
+            ))
+        })?;
+        let mut bson_vec_docs: Vec<Document> = Vec::with_capacity(list.len());
+        for (index, item) in list.iter().enumerate() {
+            let path = format!("docs[{}]", index);
+            let raw = raw_document_from_py(&item, &path)?;
+            bson_vec_docs.push(raw_document_to_document(&raw, &path)?);
+        }
+        let result =
+            self.in_transaction("Insert many", |writer| writer.insert_many(bson_vec_docs))?;
+        PyInsertManyResult::new(py, result, &self.codec_options)
+    }
+
+    /// Insert documents from Extended JSON, either a JSON array or one document per line.
+    pub fn insert_many_json(&self, py: Python, text: &str) -> PyResult<PyInsertManyResult> {
+        let bson_vec_docs: Vec<Document> = extended_json_to_documents(text, "docs")?;
+        let result =
+            self.in_transaction("Insert many", |writer| writer.insert_many(bson_vec_docs))?;
+        PyInsertManyResult::new(py, result, &self.codec_options)
+    }
+
+    pub fn insert_one(&self, py: Python, doc: &Bound<'_, PyAny>) -> PyResult<PyInsertOneResult> {
+        let bson_doc: Document = convert_py_obj_to_document(doc, "doc", &self.codec_options)?;
+        let inserted_id = self.in_transaction("Insert", |writer| writer.insert_one(bson_doc))?;
+        PyInsertOneResult::new(py, InsertOneResult { inserted_id }, &self.codec_options)
     }
 
     pub fn update_one(
         &self,
         py: Python,
-        filter: Py<PyDict>,
-        update: Py<PyDict>,
-    ) -> PyResult<Option<Py<PyAny>>> {
+        filter: &Bound<'_, PyAny>,
+        update: &Bound<'_, PyAny>,
+    ) -> PyResult<PyUpdateResult> {
         // Convert PyDict to BSON Document
-        let filter_doc = convert_py_obj_to_document(&filter.into_py_any(py).unwrap())?;
-        let update_doc = convert_py_obj_to_document(&update.into_py_any(py).unwrap())?;
+        let filter_doc = convert_py_obj_to_filter(filter, "filter", &self.codec_options)?;
+        let update_doc = convert_py_obj_to_document(update, "update", &self.codec_options)?;
 
-        // Call the Rust method `find_one`
-        match self.inner.update_one(filter_doc, update_doc) {
-            Ok(update_result) => {
-                // Convert BSON Document to Python Dict
-                let py_result = update_result_to_pydict(py, update_result).unwrap();
-                Ok(Some(py_result.into_py_any(py).unwrap()))
+        let (update_result, _) = self.in_transaction("Update one", |writer| {
+            writer.update(filter_doc, update_doc, false, false)
+        })?;
+        PyUpdateResult::new(py, update_result, None, &self.codec_options)
+    }
+
+    pub fn update_many(
+        &self,
+        py: Python,
+        filter: &Bound<'_, PyAny>,
+        update: &Bound<'_, PyAny>,
+    ) -> PyResult<PyUpdateResult> {
+        // Convert PyDict to BSON Document
+        let filter_doc = convert_py_obj_to_filter(filter, "filter", &self.codec_options)?;
+        let update_doc = convert_py_obj_to_document(update, "update", &self.codec_options)?;
+
+        let (update_result, _) = self.in_transaction("Update many", |writer| {
+            writer.update(filter_doc, update_doc, true, false)
+        })?;
+        PyUpdateResult::new(py, update_result, None, &self.codec_options)
+    }
+
+    pub fn upsert(
+        &self,
+        py: Python,
+        filter: &Bound<'_, PyAny>,
+        update: &Bound<'_, PyAny>,
+    ) -> PyResult<PyUpdateResult> {
+        // Convert PyDict to BSON Document
+        let filter_doc = convert_py_obj_to_filter(filter, "filter", &self.codec_options)?;
+        let update_doc = convert_py_obj_to_document(update, "update", &self.codec_options)?;
+
+        let (update_result, upserted_id) = self.in_transaction("Upsert one", |writer| {
+            writer.update(filter_doc, update_doc, false, true)
+        })?;
+        PyUpdateResult::new(py, update_result, upserted_id, &self.codec_options)
+    }
+
+    /// Run an aggregation pipeline, its results are returned as a `Cursor`.
+    fn aggregate(&self, py: Python, pipeline: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        let bson_vec_pipeline: Vec<Document> =
+            convert_py_list_to_vec_document(pipeline, "pipeline", &self.codec_options)?;
+        match self.reader()?.aggregate(bson_vec_pipeline) {
+            Ok(result) => {
+                let cursor = PyCursor::new(
+                    self.database.track_query(result),
+                    None,
+                    self.codec_options.clone(),
+                );
+                Ok(Py::new(py, cursor)?.into_any())
+            }
+            Err(e) => {
+                // Raise a Python exception on error
+                Err(PyRuntimeError::new_err(format!("Aggregate error: {}", e)))
             }
-            Err(err) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Update one error: {}",
-                err
-            ))),
         }
     }
 
-    pub fn update_many(
+    pub fn upsert_many(
         &self,
         py: Python,
-        filter: Py<PyDict>,
-        update: Py<PyDict>,
-    ) -> PyResult<Option<Py<PyAny>>> {
+        filter: &Bound<'_, PyAny>,
+        update: &Bound<'_, PyAny>,
+    ) -> PyResult<PyUpdateResult> {
         // Convert PyDict to BSON Document
-        let filter_doc = convert_py_obj_to_document(&filter.into_py_any(py).unwrap())?;
-        let update_doc = convert_py_obj_to_document(&update.into_py_any(py).unwrap())?;
+        let filter_doc = convert_py_obj_to_filter(filter, "filter", &self.codec_options)?;
+        let update_doc = convert_py_obj_to_document(update, "update", &self.codec_options)?;
 
-        // Call the Rust method `find_one`
-        match self.inner.update_many(filter_doc, update_doc) {
-            Ok(update_result) => {
-                // Convert BSON Document to Python Dict
-                let py_result = update_result_to_pydict(py, update_result).unwrap();
-                Ok(Some(py_result.into_py_any(py).unwrap()))
+        let (update_result, upserted_id) = self.in_transaction("Upsert many", |writer| {
+            writer.update(filter_doc, update_doc, true, true)
+        })?;
+        PyUpdateResult::new(py, update_result, upserted_id, &self.codec_options)
+    }
+
+    pub fn delete_one(&self, filter: &Bound<'_, PyAny>) -> PyResult<PyDeleteResult> {
+        let bson_doc: Document = convert_py_obj_to_filter(filter, "filter", &self.codec_options)?;
+        let delete_result =
+            self.in_transaction("Delete one", |writer| writer.delete(bson_doc, false))?;
+        Ok(delete_result.into())
+    }
+
+    pub fn delete_many(&self, filter: &Bound<'_, PyAny>) -> PyResult<PyDeleteResult> {
+        let bson_doc: Document = convert_py_obj_to_filter(filter, "filter", &self.codec_options)?;
+        let delete_result =
+            self.in_transaction("Delete many", |writer| writer.delete(bson_doc, true))?;
+        Ok(delete_result.into())
+    }
+
+    /// Count the documents matching `filter` without converting them to Python. `skip` and
+    /// `limit` apply as in `find`, a `limit` of 0 means no limit.
+    #[pyo3(signature = (filter, skip=None, limit=None))]
+    pub fn count_documents(
+        &self,
+        filter: &Bound<'_, PyAny>,
+        skip: Option<u64>,
+        limit: Option<u64>,
+    ) -> PyResult<u64> {
+        let filter_doc = convert_py_obj_to_filter(filter, "filter", &self.codec_options)?;
+        let count_error = |e: polodb_core::Error| {
+            PyRuntimeError::new_err(format!("Count documents error: {}", e))
+        };
+
+        let matched = if filter_doc.is_empty() {
+            self.reader()?.count_documents().map_err(count_error)?
+        } else {
+            let mut cursor = self.reader()?.find(filter_doc, 0, 0).map_err(count_error)?;
+            let mut matched = 0;
+            while cursor.advance().map_err(count_error)? {
+                matched += 1;
             }
-            Err(err) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Update many error: {}",
-                err
-            ))),
+            matched
+        };
+        let count = matched.saturating_sub(skip.unwrap_or(0));
+        Ok(match limit {
+            Some(limit) if limit > 0 => count.min(limit),
+            _ => count,
+        })
+    }
+
+    /// Number of documents in the collection, from a count the database keeps as writes commit.
+    /// The first call for a collection after the database is opened counts every document.
+    pub fn estimated_document_count(&self) -> PyResult<u64> {
+        let size = self.with_transaction("Count documents", |txn, modify_error| {
+            self.collection_size(txn, modify_error)
+        })?;
+        Ok(size.count.max(0) as u64)
+    }
+
+    /// Distinct values of `key`, a dotted path, over the documents matching `filter`. Each
+    /// element of an array field counts as a value. Values are returned in sort order.
+    #[pyo3(signature = (key, filter=None))]
+    pub fn distinct(
+        &self,
+        py: Python,
+        key: &str,
+        filter: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<Py<PyList>> {
+        let filter_doc = match filter {
+            Some(filter) => convert_py_obj_to_filter(filter, "filter", &self.codec_options)?,
+            None => Document::new(),
+        };
+        let distinct_error =
+            |e: polodb_core::Error| PyRuntimeError::new_err(format!("Distinct error: {}", e));
+
+        let mut cursor = self
+            .reader()?
+            .find(filter_doc, 0, 0)
+            .map_err(distinct_error)?;
+        let mut values = DistinctValues::default();
+        while cursor.advance().map_err(distinct_error)? {
+            let doc = cursor.deserialize_current().map_err(distinct_error)?;
+            values.add(&doc, key);
+        }
+        let py_result = PyList::empty(py);
+        for value in values.into_values() {
+            py_result.append(bson_to_py_obj(py, &value, &self.codec_options)?)?;
         }
+        Ok(py_result.unbind())
     }
 
-    pub fn upsert(
+    #[pyo3(signature = (filter, projection=None, sort=None, skip=0))]
+    pub fn find_one(
         &self,
         py: Python,
-        filter: Py<PyDict>,
-        update: Py<PyDict>,
+        filter: &Bound<'_, PyAny>,
+        projection: Option<&Bound<'_, PyAny>>,
+        sort: Option<&Bound<'_, PyAny>>,
+        skip: u64,
     ) -> PyResult<Option<Py<PyAny>>> {
         // Convert PyDict to BSON Document
-        let filter_doc = convert_py_obj_to_document(&filter.into_py_any(py).unwrap())?;
-        let update_doc = convert_py_obj_to_document(&update.into_py_any(py).unwrap())?;
+        let filter_doc = convert_py_obj_to_filter(filter, "filter", &self.codec_options)?;
+        let options = FindOptions::from_py(projection, sort, skip, 1)?;
 
-        match self.inner.update_one_with_options(
-            filter_doc,
-            update_doc,
-            UpdateOptions::builder().upsert(true).build(),
-        ) {
-            Ok(update_result) => {
+        let result_doc = if options.sort.is_none() && options.skip == 0 {
+            self.reader()?
+                .find_one(filter_doc)
+                .map_err(|e| PyRuntimeError::new_err(format!("Find one error: {}", e)))?
+                .map(|doc| match &options.projection {
+                    Some(projection) => projection.apply(doc),
+                    None => doc,
+                })
+        } else {
+            PyCursor::new(
+                self.find_source(filter_doc, &options)?,
+                options.projection,
+                self.codec_options.clone(),
+            )
+            .next_document()?
+        };
+        match result_doc {
+            Some(result_doc) => {
                 // Convert BSON Document to Python Dict
-                let py_result = update_result_to_pydict(py, update_result).unwrap();
-                Ok(Some(py_result.into_py_any(py).unwrap()))
+                let py_result = document_to_pydict(py, &result_doc, &self.codec_options)?;
+                Ok(Some(py_result.into_any()))
             }
-            Err(err) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Upsert one error: {}",
-                err
-            ))),
+            None => Ok(None), // Return None if no document is found
         }
     }
 
-    fn aggregate(&self, pipeline: Py<PyList>) -> PyResult<Py<PyAny>> {
-        Python::attach(|py| {
-            let bson_vec_pipeline: Vec<Document> =
-                convert_py_list_to_vec_document(&pipeline.into_py_any(py).unwrap());
-            match self.inner.aggregate(bson_vec_pipeline).run() {
-                Ok(result) => {
-                    let vec_result = result.collect::<Result<Vec<Document>, _>>().unwrap();
+    /// Find documents, returned as a `Cursor` or with `as_json` as one Extended JSON array string.
+    /// `projection` is a dict of `field: 1|0` or a list of fields, `sort` a key, a list of
+    /// `(key, direction)` pairs or a dict, and a `limit` of 0 means no limit. The cursor reads
+    /// `batch_size` documents at a time (0 for the default), except with a `sort`: polodb cannot
+    /// sort, so every match is read and sorted in memory before the first one is returned.
+    #[pyo3(signature = (filter, projection=None, sort=None, skip=0, limit=0, as_json=false, canonical=false, batch_size=0))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn find(
+        &self,
+        py: Python,
+        filter: &Bound<'_, PyAny>,
+        projection: Option<&Bound<'_, PyAny>>,
+        sort: Option<&Bound<'_, PyAny>>,
+        skip: u64,
+        limit: u64,
+        as_json: bool,
+        canonical: bool,
+        batch_size: usize,
+    ) -> PyResult<Py<PyAny>> {
+        // Convert PyDict to BSON Document
+        let filter_doc = convert_py_obj_to_filter(filter, "filter", &self.codec_options)?;
+        let options = FindOptions::from_py(projection, sort, skip, limit)?;
+        let source = self.find_source(filter_doc, &options)?;
+        let mut cursor = PyCursor::new(source, options.projection, self.codec_options.clone());
+        cursor.set_batch_size(batch_size);
 
-                    let py_result: Vec<Py<PyDict>> = vec_result
-                        .into_iter()
-                        .map(|x| document_to_pydict(py, x).unwrap())
-                        .collect();
-                    Ok(py_result.into_py_any(py).unwrap())
-                }
-                Err(e) => {
-                    // Raise a Python exception on error
-                    Err(PyRuntimeError::new_err(format!("Aggregate error: {}", e)))
+        if as_json {
+            let json_docs = cursor
+                .collect_documents()?
+                .into_iter()
+                .map(|doc| bson_to_extended_json(Bson::Document(doc), canonical))
+                .collect();
+            let json = serde_json::Value::Array(json_docs).to_string();
+            return Ok(PyString::new(py, &json).into_any().unbind());
+        }
+        Ok(Py::new(py, cursor)?.into_any())
+    }
+
+    /// Apply a list of `InsertOne`, `UpdateOne`, `UpdateMany`, `ReplaceOne`, `DeleteOne` and
+    /// `DeleteMany` requests in one transaction. Ordered requests stop at the first failure,
+    /// unordered ones carry on; either way the requests that succeeded are kept, those that
+    /// failed leave nothing behind, and `BulkWriteError` lists the failed indexes. An update that
+    /// stores an array in an indexed field is the exception: polodb cannot take it back on its
+    /// own, so the whole bulk write fails with a `ValueError`.
+    #[pyo3(signature = (requests, ordered=true))]
+    pub fn bulk_write(
+        &self,
+        py: Python,
+        requests: &Bound<'_, PyAny>,
+        ordered: bool,
+    ) -> PyResult<PyBulkWriteResult> {
+        let requests = requests.cast::<PyList>().map_err(|_| {
+            PyTypeError::new_err(format!(
+                "requests: expected a list of write requests, got '{}'",
+                requests
+                    .get_type()
+                    .name()
+                    .map(|n| n.to_string())
+                    .unwrap_or_default()
+            ))
+        })?;
+        // Every request is converted before anything is written
+        let models = requests
+            .iter()
+            .enumerate()
+            .map(|(index, request)| {
+                WriteModel::from_py(
+                    &request,
+                    &format!("requests[{}]", index),
+                    &self.codec_options,
+                )
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+
+        let mut write_errors: Vec<(usize, PyErr)> = Vec::new();
+        let counts = self.in_transaction("Bulk write", |writer| {
+            let mut counts = BulkWriteCounts::default();
+            for (index, model) in models.into_iter().enumerate() {
+                // A failed request is undone on its own, the others are kept
+                if let Err(err) = writer.savepoint(|| model.apply(index, writer, &mut counts))? {
+                    write_errors.push((index, err));
+                    if ordered {
+                        break;
+                    }
                 }
             }
-        })
+            Ok(counts)
+        })?;
+        if !write_errors.is_empty() {
+            return Err(bulk_write_error(
+                py,
+                requests,
+                &counts,
+                write_errors,
+                &self.codec_options,
+            )?);
+        }
+        PyBulkWriteResult::new(py, counts, &self.codec_options)
     }
 
-    pub fn upsert_many(
+    /// Atomically find one document and apply `update` to it, returning the document as it was
+    /// before, or after with `return_document="after"`. With `upsert` a missing document is
+    /// created from the equality conditions of `filter`. `sort` picks which document is updated.
+    #[pyo3(signature = (filter, update, projection=None, sort=None, upsert=false, return_document=None))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn find_one_and_update(
         &self,
         py: Python,
-        filter: Py<PyDict>,
-        update: Py<PyDict>,
+        filter: &Bound<'_, PyAny>,
+        update: &Bound<'_, PyAny>,
+        projection: Option<&Bound<'_, PyAny>>,
+        sort: Option<&Bound<'_, PyAny>>,
+        upsert: bool,
+        return_document: Option<&Bound<'_, PyAny>>,
     ) -> PyResult<Option<Py<PyAny>>> {
-        // Convert PyDict to BSON Document
-        let filter_doc = convert_py_obj_to_document(&filter.into_py_any(py).unwrap())?;
-        let update_doc = convert_py_obj_to_document(&update.into_py_any(py).unwrap())?;
+        let filter_doc = convert_py_obj_to_filter(filter, "filter", &self.codec_options)?;
+        let update_doc = convert_py_obj_to_document(update, "update", &self.codec_options)?;
+        validate_update(&update_doc)?;
+        let options = FindOptions::from_py(projection, sort, 0, 1)?;
+        let return_after = convert_py_obj_to_return_document(return_document)?;
 
-        // Call the Rust method `find_one`
-        match self.inner.update_many_with_options(
+        let result_doc = self.find_one_and_modify(
             filter_doc,
-            update_doc,
-            UpdateOptions::builder().upsert(true).build(),
-        ) {
-            Ok(update_result) => {
-                // Convert BSON Document to Python Dict
-                let py_result = update_result_to_pydict(py, update_result).unwrap();
-                Ok(Some(py_result.into_py_any(py).unwrap()))
+            Modification::Update(update_doc),
+            options,
+            upsert,
+            return_after,
+            "Find one and update",
+        )?;
+        self.optional_document_to_py(py, result_doc)
+    }
+
+    /// Replace the first document matching `filter` with `replacement`, keeping its `_id`. With
+    /// `upsert` the replacement is inserted when nothing matches, and its `_id` is returned as
+    /// `upserted_id`.
+    #[pyo3(signature = (filter, replacement, upsert=false))]
+    pub fn replace_one(
+        &self,
+        py: Python,
+        filter: &Bound<'_, PyAny>,
+        replacement: &Bound<'_, PyAny>,
+        upsert: bool,
+    ) -> PyResult<PyUpdateResult> {
+        let filter_doc = convert_py_obj_to_filter(filter, "filter", &self.codec_options)?;
+        let replacement_doc =
+            convert_py_obj_to_document(replacement, "replacement", &self.codec_options)?;
+        validate_replacement(&replacement_doc)?;
+
+        let (update_result, upserted_id) = self.in_transaction("Replace one", |writer| {
+            writer.replace(filter_doc, replacement_doc, upsert)
+        })?;
+        PyUpdateResult::new(py, update_result, upserted_id, &self.codec_options)
+    }
+
+    /// Atomically find one document and replace it with `replacement`, keeping its `_id`.
+    /// Options as in `find_one_and_update`.
+    #[pyo3(signature = (filter, replacement, projection=None, sort=None, upsert=false, return_document=None))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn find_one_and_replace(
+        &self,
+        py: Python,
+        filter: &Bound<'_, PyAny>,
+        replacement: &Bound<'_, PyAny>,
+        projection: Option<&Bound<'_, PyAny>>,
+        sort: Option<&Bound<'_, PyAny>>,
+        upsert: bool,
+        return_document: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<Option<Py<PyAny>>> {
+        let filter_doc = convert_py_obj_to_filter(filter, "filter", &self.codec_options)?;
+        let replacement_doc =
+            convert_py_obj_to_document(replacement, "replacement", &self.codec_options)?;
+        validate_replacement(&replacement_doc)?;
+        let options = FindOptions::from_py(projection, sort, 0, 1)?;
+        let return_after = convert_py_obj_to_return_document(return_document)?;
+
+        let result_doc = self.find_one_and_modify(
+            filter_doc,
+            Modification::Replace(replacement_doc),
+            options,
+            upsert,
+            return_after,
+            "Find one and replace",
+        )?;
+        self.optional_document_to_py(py, result_doc)
+    }
+
+    /// Atomically find one document, delete it and return it.
+    #[pyo3(signature = (filter, projection=None, sort=None))]
+    pub fn find_one_and_delete(
+        &self,
+        py: Python,
+        filter: &Bound<'_, PyAny>,
+        projection: Option<&Bound<'_, PyAny>>,
+        sort: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<Option<Py<PyAny>>> {
+        let filter_doc = convert_py_obj_to_filter(filter, "filter", &self.codec_options)?;
+        let options = FindOptions::from_py(projection, sort, 0, 1)?;
+
+        let result_doc = self.find_one_and_modify(
+            filter_doc,
+            Modification::Delete,
+            options,
+            false,
+            false,
+            "Find one and delete",
+        )?;
+        self.optional_document_to_py(py, result_doc)
+    }
+
+    /// Create an index on one field and return its name. `keys` is a field name or
+    /// `[(field, 1)]`; a `unique` index makes writes of a value it already holds raise
+    /// `DuplicateKeyError`, counting documents without the field as null unless it is `sparse`.
+    /// With `expire_after_seconds` it is a TTL index: documents are removed that many seconds
+    /// after the date in the field, see `PyDatabase.purge_expired`.
+    #[pyo3(signature = (keys, unique=false, name=None, expire_after_seconds=None, sparse=false))]
+    pub fn create_index(
+        &self,
+        keys: &Bound<'_, PyAny>,
+        unique: bool,
+        name: Option<String>,
+        expire_after_seconds: Option<i64>,
+        sparse: bool,
+    ) -> PyResult<String> {
+        let spec = IndexSpec::from_py(keys, unique, name, expire_after_seconds, sparse)?;
+        self.with_transaction("Create index", |txn, modify_error| {
+            self.indexes_changed();
+            create_index(txn, self.inner.name(), &spec, modify_error)
+        })?;
+        Ok(spec.name)
+    }
+
+    /// Create every index of a list of `IndexModel`s in one transaction, returning their names.
+    pub fn create_indexes(&self, indexes: &Bound<'_, PyAny>) -> PyResult<Vec<String>> {
+        let py = indexes.py();
+        let specs = indexes
+            .try_iter()?
+            .enumerate()
+            .map(|(i, index)| {
+                let index = index?;
+                let model = index.cast::<PyIndexModel>().map_err(|_| {
+                    PyTypeError::new_err(format!("indexes[{}]: expected an IndexModel", i))
+                })?;
+                model.get().to_spec(py)
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        self.with_transaction("Create indexes", |txn, modify_error| {
+            self.indexes_changed();
+            for spec in &specs {
+                create_index(txn, self.inner.name(), spec, modify_error)?;
             }
-            Err(err) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Upsert many error: {}",
-                err
-            ))),
-        }
+            Ok(())
+        })?;
+        Ok(specs.into_iter().map(|spec| spec.name).collect())
     }
 
-    pub fn delete_one(&self, filter: Py<PyDict>) -> PyResult<Py<PyAny>> {
-        // Acquire the Python GIL (Global Interpreter Lock)
-        // let filter_doc = convert_py_obj_to_document(&filter.into_py_any(py).unwrap())?;
-        Python::attach(|py| {
-            let bson_doc: Document =
-                match convert_py_obj_to_document(&filter.into_py_any(py).unwrap()) {
-                    Ok(d) => d,
-                    Err(e) => return Err(PyRuntimeError::new_err(format!("Delete one : {}", e))),
-                };
-            // let bson_doc = convert_py_to_bson(doc);
-            match self.inner.delete_one(bson_doc) {
-                Ok(delete_result) => {
-                    // Create a Python object from the Rust result and return it
-                    let py_result = delete_result_to_pydict(py, delete_result).unwrap();
-                    Ok(py_result.into_py_any(py).unwrap())
-
-                    // Ok(Py::new(py, result)?.to_object(py))
-                }
-                Err(e) => {
-                    // Raise a Python exception on error
-                    Err(PyRuntimeError::new_err(format!("Delete one error: {}", e)))
-                }
+    /// Drop an index given by its name or by the keys it was created with.
+    pub fn drop_index(&self, index: &Bound<'_, PyAny>) -> PyResult<()> {
+        let name = index_name_from_py(index)?;
+        self.with_transaction("Drop index", |txn, modify_error| {
+            self.indexes_changed();
+            drop_index(txn, self.inner.name(), &name, modify_error)
+        })
+    }
+
+    /// Drop every index except the one on `_id`.
+    pub fn drop_indexes(&self) -> PyResult<()> {
+        self.with_transaction("Drop indexes", |txn, modify_error| {
+            self.indexes_changed();
+            for spec in list_indexes(txn, self.inner.name(), modify_error)? {
+                drop_index(txn, self.inner.name(), &spec.name, modify_error)?;
             }
+            Ok(())
         })
     }
 
-    pub fn delete_many(&self, filter: Py<PyDict>) -> PyResult<Py<PyAny>> {
-        // Acquire the Python GIL (Global Interpreter Lock)
-        Python::attach(|py| {
-            let bson_doc: Document =
-                match convert_py_obj_to_document(&filter.into_py_any(py).unwrap()) {
-                    Ok(d) => d,
-                    Err(e) => return Err(PyRuntimeError::new_err(format!("Delete many : {}", e))),
+    /// Describe the indexes of the collection, `_id_` first, as dicts with `name`, `key` and
+    /// `unique` when set.
+    pub fn list_indexes(&self, py: Python) -> PyResult<Py<PyList>> {
+        let specs = self.with_transaction("List indexes", |txn, modify_error| {
+            list_indexes(txn, self.inner.name(), modify_error)
+        })?;
+        let py_result = PyList::empty(py);
+        for spec in std::iter::once(IndexSpec::id_index()).chain(specs) {
+            py_result.append(document_to_pydict(
+                py,
+                &spec.to_document(),
+                &self.codec_options,
+            )?)?;
+        }
+        Ok(py_result.unbind())
+    }
+
+    /// Find documents and return each one as BSON bytes. This is not zero-copy: polodb decodes
+    /// every stored document to evaluate the filter and only hands out decoded documents, so each
+    /// match is encoded to BSON again here. What it saves is building the Python dicts.
+    pub fn find_raw(&self, py: Python, filter: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        let filter_doc = convert_py_obj_to_filter(filter, "filter", &self.codec_options)?;
+
+        match self.reader()?.find(filter_doc, 0, 0) {
+            Ok(cursor) => {
+                let py_result = PyList::empty(py);
+                let mut buf = Vec::new();
+                for doc in cursor {
+                    let doc =
+                        doc.map_err(|e| PyRuntimeError::new_err(format!("Find error: {}", e)))?;
+                    buf.clear();
+                    doc.to_writer(&mut buf)
+                        .map_err(|e| PyRuntimeError::new_err(format!("Find error: {}", e)))?;
+                    py_result.append(PyBytes::new(py, &buf))?;
+                }
+                Ok(py_result.into_any().unbind())
+            }
+            Err(err) => Err(PyRuntimeError::new_err(format!("Find error: {}", err))),
+        }
+    }
+}
+impl PyCollection {
+    /// Start a query with its sort, skip and limit applied. Sorting is done here rather than by
+    /// polodb, whose `$sort` stage neither keeps the priority of several keys nor follows dotted
+    /// paths, so a sorted query is read in full first.
+    fn find_source(&self, filter: Document, options: &FindOptions) -> PyResult<CursorSource> {
+        let find_error =
+            |e: polodb_core::Error| PyRuntimeError::new_err(format!("Find error: {}", e));
+        match &options.sort {
+            Some(sort) => {
+                let docs =
+                    CursorSource::query(self.reader()?.find(filter, 0, 0).map_err(find_error)?)
+                        .collect_documents()
+                        .map_err(find_error)?;
+                let limit = match options.limit {
+                    0 => usize::MAX,
+                    limit => limit as usize,
                 };
+                let docs: Vec<Document> = sort_documents(docs, sort)
+                    .into_iter()
+                    .skip(options.skip as usize)
+                    .take(limit)
+                    .collect();
+                Ok(CursorSource::Buffered(docs.into_iter()))
+            }
+            None => {
+                let cursor = self
+                    .reader()?
+                    .find(filter, options.skip, options.limit)
+                    .map_err(find_error)?;
+                Ok(self.database.track_query(cursor))
+            }
+        }
+    }
 
-            match self.inner.delete_many(bson_doc) {
-                Ok(delete_result) => {
-                    // Create a Python object from the Rust result and return it
-                    let py_result = delete_result_to_pydict(py, delete_result).unwrap();
-                    Ok(py_result.into_py_any(py).unwrap())
+    /// Run a `find_one_and_*` and apply the projection to the document it returns.
+    fn find_one_and_modify(
+        &self,
+        filter: Document,
+        modification: Modification,
+        options: FindOptions,
+        upsert: bool,
+        return_after: bool,
+        context: &str,
+    ) -> PyResult<Option<Document>> {
+        let result_doc = self.in_transaction(context, |writer| {
+            writer.find_one_and_modify(
+                filter,
+                modification,
+                options.sort.as_ref(),
+                upsert,
+                return_after,
+            )
+        })?;
+        Ok(result_doc.map(|doc| match &options.projection {
+            Some(projection) => projection.apply(doc),
+            None => doc,
+        }))
+    }
 
-                    // Ok(Py::new(py, result)?.to_object(py))
+    /// Run `operation` on this collection in one transaction, see `with_transaction`. The
+    /// writer it gets checks the collection's unique indexes, and the change it makes to the
+    /// number of documents is counted.
+    fn in_transaction<R>(
+        &self,
+        context: &str,
+        operation: impl FnOnce(&CollectionWriter) -> PyResult<R>,
+    ) -> PyResult<R> {
+        self.write_transaction(context, |txn, modify_error, changes| {
+            // Inside a user's transaction the indexes may differ from the committed ones
+            let indexes = match &self.inner {
+                CollectionHandle::Standalone(_) => self
+                    .indexes
+                    .get_or_load(|| list_indexes(txn, self.inner.name(), modify_error))?,
+                CollectionHandle::Session(..) => {
+                    Arc::new(list_indexes(txn, self.inner.name(), modify_error)?)
+                }
+            };
+            let collection = txn.collection::<Document>(self.inner.name());
+            let writer = CollectionWriter::new(&collection, &indexes, context);
+            let own = match &self.inner {
+                CollectionHandle::Standalone(_) => None,
+                CollectionHandle::Session(_, session) => Some(session),
+            };
+            // Refuse the documents other transactions wrote, RocksDB would block on them while
+            // the database lock is held
+            let others = self.database.writing_sessions(own);
+            let guard = |id: &Bson| {
+                for session in &others {
+                    session.check_write(self.inner.name(), id, context)?;
                 }
-                Err(e) => {
-                    // Raise a Python exception on error
-                    Err(PyRuntimeError::new_err(format!("Delete one error: {}", e)))
+                if let Some(own) = own {
+                    own.claim(self.inner.name(), id);
                 }
-            }
+                Ok(())
+            };
+            let writer = if own.is_none() && others.is_empty() {
+                writer
+            } else {
+                writer.with_guard(&guard)
+            };
+            let result = operation(&writer);
+            changes.add(self.inner.name(), writer.size_change());
+            result
         })
     }
 
-    pub fn count_documents(&self) -> PyResult<Py<PyAny>> {
-        // Acquire the Python GIL (Global Interpreter Lock)
-        Python::attach(|py| {
-            match self.inner.count_documents() {
-                Ok(result) => Ok(result.into_pyobject(py).unwrap().into()),
-                Err(e) => {
-                    // Raise a Python exception on error
-                    Err(PyRuntimeError::new_err(format!(
-                        "Count documents error: {}",
-                        e
-                    )))
-                }
+    /// Run `operation` in one transaction, committed when it succeeds and rolled back otherwise.
+    /// The database lock is held until then, so two callers can never both claim the same
+    /// document. A collection of a user's transaction runs it there instead, see
+    /// `TransactionSession::run`.
+    fn with_transaction<R>(
+        &self,
+        context: &str,
+        operation: impl FnOnce(&Transaction, &dyn Fn(polodb_core::Error) -> PyErr) -> PyResult<R>,
+    ) -> PyResult<R> {
+        self.write_transaction(context, |txn, modify_error, _| operation(txn, modify_error))
+    }
+
+    /// `with_transaction` for an `operation` that records how it changes the size of
+    /// collections, applied to the database's counters when the transaction commits.
+    fn write_transaction<R>(
+        &self,
+        context: &str,
+        operation: impl FnOnce(
+            &Transaction,
+            &dyn Fn(polodb_core::Error) -> PyErr,
+            &mut SizeChanges,
+        ) -> PyResult<R>,
+    ) -> PyResult<R> {
+        let modify_error = |e: polodb_core::Error| write_error(context, e);
+        let mut changes = SizeChanges::default();
+        self.check_open()?;
+        self.database.with_database(|database| match &self.inner {
+            CollectionHandle::Session(_, session) => session.run(|txn| {
+                let result = operation(txn, &modify_error, &mut changes)?;
+                session.resized(changes);
+                Ok(result)
+            }),
+            CollectionHandle::Standalone(_) => {
+                let result = run_transaction(
+                    database,
+                    |txn| operation(txn, &modify_error, &mut changes),
+                    modify_error,
+                )?;
+                // Still under the database lock, no count can see the commit without the change
+                self.database.sizes.apply(changes);
+                Ok(result)
             }
         })
     }
 
-    pub fn find_one(&self, py: Python, filter: Py<PyDict>) -> PyResult<Option<Py<PyAny>>> {
-        // Convert PyDict to BSON Document
-        let filter_doc = convert_py_obj_to_document(&filter.into_py_any(py).unwrap())?;
+    /// The size of the collection as `txn` sees it, see `SizeCounters`.
+    fn collection_size(
+        &self,
+        txn: &Transaction,
+        modify_error: &dyn Fn(polodb_core::Error) -> PyErr,
+    ) -> PyResult<CollectionSize> {
+        let name = self.inner.name();
+        let count = || measure_collection(txn, name, modify_error);
+        match &self.inner {
+            CollectionHandle::Standalone(_) => self.database.sizes.get_or_count(name, count),
+            CollectionHandle::Session(_, session) => session.collection_size(name, count),
+        }
+    }
 
-        // Call the Rust method `find_one`
-        match self.inner.find_one(filter_doc) {
-            Ok(Some(result_doc)) => {
-                // Convert BSON Document to Python Dict
-                let py_result = document_to_pydict(py, result_doc).unwrap();
-                Ok(Some(py_result.into_py_any(py).unwrap()))
-            }
-            Ok(None) => Ok(None), // Return None if no document is found
-            Err(err) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Find one error: {}",
-                err
-            ))),
+    /// The collection to read from, failing once it is closed or the transaction it belongs to
+    /// has ended.
+    fn reader(&self) -> PyResult<&CollectionHandle> {
+        self.check_open()?;
+        if let CollectionHandle::Session(_, session) = &self.inner {
+            session.check_active()?;
         }
+        Ok(&self.inner)
     }
-    pub fn find(&self, py: Python, filter: Py<PyDict>) -> PyResult<Option<Py<PyAny>>> {
-        // Convert PyDict to BSON Document
-        let filter_doc = convert_py_obj_to_document(&filter.into_py_any(py).unwrap())?;
 
-        // Call the Rust method `find_one`
-        match self.inner.find(filter_doc).run() {
-            Ok(result_doc) => {
-                // Convert BSON Document to Python Dict
-                let py_result: Vec<Py<PyDict>> = result_doc
-                    .map(|x| document_to_pydict(py, x.unwrap()).unwrap())
-                    .collect();
-                // let py_result = document_to_pydict(py, result_doc).unwrap();
-                Ok(Some(py_result.into_py_any(py).unwrap()))
-            }
-            // Ok(None) => Ok(None), // Return None if no document is found
-            Err(err) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Find one error: {}",
-                err
-            ))),
+    /// Fail with `ClosedError` once this handle or its database is closed.
+    fn check_open(&self) -> PyResult<()> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(ClosedError::new_err("Cannot use a closed collection"));
         }
+        self.database.with_database(|_| Ok(()))
     }
-}
-impl From<Collection<Document>> for PyCollection {
-    fn from(collection: Collection<Document>) -> PyCollection {
+
+    /// Clear the cached indexes after changing them.
+    fn indexes_changed(&self) {
+        match &self.inner {
+            CollectionHandle::Standalone(_) => self.indexes.invalidate(),
+            CollectionHandle::Session(_, session) => session.indexes_changed(&self.indexes),
+        }
+    }
+
+    fn optional_document_to_py(
+        &self,
+        py: Python,
+        doc: Option<Document>,
+    ) -> PyResult<Option<Py<PyAny>>> {
+        match doc {
+            Some(doc) => Ok(Some(
+                document_to_pydict(py, &doc, &self.codec_options)?.into_any(),
+            )),
+            None => Ok(None),
+        }
+    }
+
+    fn new(
+        collection: CollectionHandle,
+        database: Arc<SharedDatabase>,
+        codec_options: Arc<CodecOptions>,
+        indexes: Arc<IndexCache>,
+    ) -> PyCollection {
         PyCollection {
-            inner: Arc::new(collection),
+            inner: collection,
+            database,
+            codec_options,
+            indexes,
+            closed: AtomicBool::new(false),
         }
     }
 }
 
 #[pyclass]
 pub struct PyDatabase {
-    inner: Arc<Mutex<Database>>,
+    inner: Arc<SharedDatabase>,
+    codec_options: Arc<CodecOptions>,
+    // Codecs registered per collection name, shared by every handle on that collection
+    collection_codecs: Mutex<HashMap<String, Arc<CodecRegistry>>>,
+    // Index definitions per collection name, likewise shared
+    collection_indexes: Mutex<HashMap<String, Arc<IndexCache>>>,
+    // Removes expired documents of TTL indexes until dropped, None when `ttl_interval` is None
+    ttl_monitor: Mutex<Option<TtlMonitor>>,
 }
 
 #[pymethods]
 impl PyDatabase {
     #[new]
-    fn new(path: &str) -> PyResult<Self> {
-        let db_path = Path::new(path);
-        match Database::open_path(db_path) {
-            Ok(db) => Ok(PyDatabase {
-                inner: Arc::new(Mutex::new(db)),
-            }),
-            Err(e) => Err(PyOSError::new_err(e.to_string())),
-        }
+    #[pyo3(signature = (path, tz_aware=false, tzinfo=None, strict=false, ttl_interval=Some(60.0)))]
+    fn new(
+        path: &str,
+        tz_aware: bool,
+        tzinfo: Option<Py<PyAny>>,
+        strict: bool,
+        ttl_interval: Option<f64>,
+    ) -> PyResult<Self> {
+        Self::open_path(path, tz_aware, tzinfo, strict, ttl_interval)
     }
 
+    /// Open the database at `path`. Every `ttl_interval` seconds a background thread removes
+    /// the documents TTL indexes say have expired; None turns it off, leaving `purge_expired`.
     #[staticmethod]
-    fn open_path(path: &str) -> PyResult<PyDatabase> {
+    #[pyo3(signature = (path, tz_aware=false, tzinfo=None, strict=false, ttl_interval=Some(60.0)))]
+    fn open_path(
+        path: &str,
+        tz_aware: bool,
+        tzinfo: Option<Py<PyAny>>,
+        strict: bool,
+        ttl_interval: Option<f64>,
+    ) -> PyResult<PyDatabase> {
+        let ttl_interval = ttl_interval_from_py(ttl_interval)?;
         let db_path = Path::new(path);
-        Database::open_path(db_path)
-            .map(|db| PyDatabase {
-                inner: Arc::new(Mutex::new(db)),
-            })
-            .map_err(|e| PyOSError::new_err(e.to_string()))
+        let db = Database::open_path(db_path).map_err(|e| PyOSError::new_err(e.to_string()))?;
+        Self::open(
+            SharedDatabase::new(db, db_path.to_path_buf(), false),
+            tz_aware,
+            tzinfo,
+            strict,
+            ttl_interval,
+        )
     }
 
-    pub fn create_collection(&self, name: &str) -> PyResult<()> {
-        let _ = self.inner.lock().unwrap().create_collection(name);
-        Ok(())
+    /// Open a throwaway database with the same API, for tests. It is not held in memory:
+    /// polodb 5 only opens databases on disk (`Database::open_path` and `open_file`, with or
+    /// without a config), so it is written to a private temporary directory that `close()`
+    /// removes. `save` keeps a copy of it.
+    #[staticmethod]
+    #[pyo3(signature = (tz_aware=false, tzinfo=None, strict=false, ttl_interval=Some(60.0)))]
+    fn open_temporary(
+        tz_aware: bool,
+        tzinfo: Option<Py<PyAny>>,
+        strict: bool,
+        ttl_interval: Option<f64>,
+    ) -> PyResult<PyDatabase> {
+        static OPENED: AtomicU64 = AtomicU64::new(0);
+        let ttl_interval = ttl_interval_from_py(ttl_interval)?;
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos())
+            .unwrap_or_default();
+        let temp_dir = std::env::temp_dir().join(format!(
+            "mongo_emb-temporary-{}-{}-{}",
+            std::process::id(),
+            nanos,
+            OPENED.fetch_add(1, Ordering::Relaxed)
+        ));
+        let db = Database::open_path(&temp_dir).map_err(|e| {
+            let _ = std::fs::remove_dir_all(&temp_dir);
+            PyOSError::new_err(e.to_string())
+        })?;
+        Self::open(
+            SharedDatabase::new(db, temp_dir, true),
+            tz_aware,
+            tzinfo,
+            strict,
+            ttl_interval,
+        )
     }
 
-    fn collection(&self, name: &str) -> PyResult<PyCollection> {
-        // Attempt to acquire the lock and fetch/create the collection
-        let guard = self
-            .inner
+    /// Write a copy of the database, with its indexes, to a new database at `path`. Mostly for
+    /// keeping what a temporary database holds; `path` must not exist yet.
+    pub fn save(&self, path: &str) -> PyResult<()> {
+        self.inner
+            .with_database(|database| save_database(database, Path::new(path)))
+    }
+
+    /// Remove the documents TTL indexes say have expired now, instead of waiting for the
+    /// background thread. Returns how many were deleted.
+    pub fn purge_expired(&self) -> PyResult<u64> {
+        purge_expired(&self.inner)
+    }
+
+    /// Close the database: open cursors and transactions end, the data is flushed and the path
+    /// can be opened again. Later calls on it, its collections and cursors raise `ClosedError`;
+    /// closing twice does nothing.
+    pub fn close(&self) -> PyResult<()> {
+        // Stop the TTL thread first, it could be waiting for the database lock
+        let ttl_monitor = self
+            .ttl_monitor
             .lock()
-            .map_err(|e| PyRuntimeError::new_err(format!("Failed to lock: {}", e)))?;
-        let rust_collection = guard.collection::<Document>(name); // Assume this returns a Rust Collection
+            .map_err(|e| PyRuntimeError::new_err(format!("Failed to lock: {}", e)))?
+            .take();
+        drop(ttl_monitor);
+        self.inner.close()
+    }
 
-        //Convert a Rust Collection to a PyCollection
-        let py_collection: PyCollection = PyCollection::from(rust_collection);
-        Ok(py_collection)
+    /// The options values are converted with, for `to_extended_json` and `from_extended_json`.
+    #[getter]
+    fn codec_options(&self) -> PyCodecOptions {
+        self.codec_options.clone().into()
+    }
+
+    /// Register a codec for instances of `python_type`. `encode(obj)` returns a value that can
+    /// be stored. To read values back as `python_type`, give `decode` with either a binary
+    /// `subtype` (128-255, `encode` must return bytes) or a `tag`, stored as
+    /// `{"__type__": tag, "__value__": encoded}`.
+    #[pyo3(signature = (python_type, encode, decode=None, subtype=None, tag=None))]
+    pub fn register_type(
+        &self,
+        python_type: &Bound<'_, PyType>,
+        encode: Py<PyAny>,
+        decode: Option<Py<PyAny>>,
+        subtype: Option<u8>,
+        tag: Option<String>,
+    ) -> PyResult<()> {
+        self.codec_options
+            .codecs
+            .register(python_type, encode, decode, subtype, tag)
+    }
+
+    pub fn create_collection(&self, name: &str) -> PyResult<()> {
+        self.inner.with_database(|database| {
+            let _ = database.create_collection(name);
+            Ok(())
+        })
+    }
+
+    fn collection(&self, py: Python, name: &str) -> PyResult<PyCollection> {
+        self.open_collection(py, name, None)
+    }
+
+    /// Statistics of the database: `fileSize` in bytes, the number of `collections` and of
+    /// documents (`objects`), from the counters `PyCollection.stats` uses. `freePages` is always
+    /// None, polodb's storage keeps no free list.
+    pub fn stats(&self, py: Python) -> PyResult<Py<PyDict>> {
+        let modify_error = |e: polodb_core::Error| write_error("Database stats", e);
+        let stats = self.inner.with_database(|database| {
+            let names = database.list_collection_names().map_err(modify_error)?;
+            run_transaction(
+                database,
+                |txn| {
+                    database_stats(&self.inner.path, &names, |name| {
+                        self.inner
+                            .sizes
+                            .get_or_count(name, || measure_collection(txn, name, modify_error))
+                    })
+                },
+                modify_error,
+            )
+        })?;
+        document_to_pydict(py, &stats, &self.codec_options)
+    }
+
+    /// Drop the collection `name` with its documents and indexes. Dropping a collection that
+    /// does not exist does nothing.
+    pub fn drop_collection(&self, name: &str) -> PyResult<()> {
+        check_collection_name(name)?;
+        let modify_error = |e: polodb_core::Error| write_error("Drop collection", e);
+        self.inner.with_database(|database| {
+            run_transaction(
+                database,
+                |txn| drop_collection(txn, name, modify_error),
+                modify_error,
+            )?;
+            let mut changes = SizeChanges::default();
+            changes.recount(name);
+            self.inner.sizes.apply(changes);
+            Ok(())
+        })?;
+        self.indexes_changed(name)
+    }
+
+    /// Rename the collection `old` to `new`, with its documents and indexes, in one transaction.
+    /// polodb cannot rename a collection, so every document is copied and deleted: it takes
+    /// time and space proportional to the size of `old`. An existing `new` is an error unless
+    /// `drop_target` is set, then it is replaced.
+    #[pyo3(signature = (old, new, drop_target=false))]
+    pub fn rename_collection(&self, old: &str, new: &str, drop_target: bool) -> PyResult<()> {
+        check_collection_name(old)?;
+        check_collection_name(new)?;
+        if old == new {
+            return Err(PyValueError::new_err(format!(
+                "Rename collection error: '{}' cannot be renamed to itself",
+                old
+            )));
+        }
+        let modify_error = |e: polodb_core::Error| write_error("Rename collection", e);
+        self.inner.with_database(|database| {
+            let names = database.list_collection_names().map_err(modify_error)?;
+            if !names.iter().any(|name| name == old) {
+                return Err(PyValueError::new_err(format!(
+                    "Rename collection error: collection '{}' does not exist",
+                    old
+                )));
+            }
+            let target_exists = names.iter().any(|name| name == new);
+            if target_exists && !drop_target {
+                return Err(PyValueError::new_err(format!(
+                    "Rename collection error: target collection '{}' already exists, pass \
+                     drop_target=True to replace it",
+                    new
+                )));
+            }
+            run_transaction(
+                database,
+                |txn| {
+                    if target_exists {
+                        drop_collection(txn, new, modify_error)?;
+                    }
+                    create_collection(txn, new, modify_error)?;
+                    copy_collection(txn, txn, old, new, modify_error)?;
+                    drop_collection(txn, old, modify_error)
+                },
+                modify_error,
+            )?;
+            let mut changes = SizeChanges::default();
+            changes.recount(old);
+            changes.recount(new);
+            self.inner.sizes.apply(changes);
+            Ok(())
+        })?;
+        self.indexes_changed(old)?;
+        self.indexes_changed(new)
+    }
+
+    /// Start a transaction. Used as a context manager it commits when the block exits cleanly
+    /// and aborts when it raises; collections opened from it read and write inside it.
+    fn start_transaction(slf: &Bound<'_, Self>) -> PyResult<PyTransaction> {
+        let database = slf.borrow();
+        let txn = database.inner.with_database(|database| {
+            database
+                .start_transaction()
+                .map_err(|e| PyRuntimeError::new_err(format!("Start transaction error: {}", e)))
+        })?;
+        let session = Arc::new(TransactionSession::new(txn, database.inner.sizes.clone()));
+        database.inner.track_session(&session);
+        Ok(PyTransaction::new(session, slf.clone().unbind()))
     }
 
     pub fn list_collection_names(&self) -> PyResult<Vec<String>> {
-        let collections_names = self.inner.lock().unwrap().list_collection_names();
+        let collections_names = self
+            .inner
+            .with_database(|database| Ok(database.list_collection_names()))?;
         match collections_names {
-            Ok(collection_names) => Ok(collection_names),
+            Ok(collection_names) => Ok(collection_names
+                .into_iter()
+                .filter(|name| name != INDEX_CATALOG)
+                .collect()),
             Err(e) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
                 "Error listing collection names: {}",
                 e
@@ -379,3 +1366,126 @@ impl PyDatabase {
 
     // You can add methods here to interact with the Database
 }
+
+impl PyDatabase {
+    fn open(
+        database: SharedDatabase,
+        tz_aware: bool,
+        tzinfo: Option<Py<PyAny>>,
+        strict: bool,
+        ttl_interval: Option<Duration>,
+    ) -> PyResult<PyDatabase> {
+        let inner = Arc::new(database);
+        let ttl_monitor = ttl_interval
+            .map(|interval| TtlMonitor::start(Arc::downgrade(&inner), interval))
+            .transpose()?;
+        Ok(PyDatabase {
+            inner,
+            codec_options: Arc::new(CodecOptions {
+                tz_aware,
+                tzinfo,
+                strict,
+                codecs: Arc::default(),
+            }),
+            collection_codecs: Mutex::default(),
+            collection_indexes: Mutex::default(),
+            ttl_monitor: Mutex::new(ttl_monitor),
+        })
+    }
+
+    /// Clear the cached indexes of the collection `name` after changing them.
+    fn indexes_changed(&self, name: &str) -> PyResult<()> {
+        if let Some(indexes) = self
+            .collection_indexes
+            .lock()
+            .map_err(|e| PyRuntimeError::new_err(format!("Failed to lock: {}", e)))?
+            .get(name)
+        {
+            indexes.invalidate();
+        }
+        Ok(())
+    }
+
+    /// Open `name` on its own, or inside the transaction of `session`.
+    pub(crate) fn open_collection(
+        &self,
+        py: Python,
+        name: &str,
+        session: Option<Arc<TransactionSession>>,
+    ) -> PyResult<PyCollection> {
+        // Attempt to acquire the lock and fetch/create the collection
+        let rust_collection = self.inner.with_database(|database| {
+            Ok(match session {
+                Some(session) => CollectionHandle::Session(name.to_string(), session),
+                None => CollectionHandle::Standalone(database.collection::<Document>(name)),
+            })
+        })?;
+
+        let codecs = self
+            .collection_codecs
+            .lock()
+            .map_err(|e| PyRuntimeError::new_err(format!("Failed to lock: {}", e)))?
+            .entry(name.to_string())
+            .or_insert_with(|| {
+                Arc::new(CodecRegistry::with_parent(
+                    self.codec_options.codecs.clone(),
+                ))
+            })
+            .clone();
+        let codec_options = Arc::new(self.codec_options.with_codecs(py, codecs));
+        let indexes = self
+            .collection_indexes
+            .lock()
+            .map_err(|e| PyRuntimeError::new_err(format!("Failed to lock: {}", e)))?
+            .entry(name.to_string())
+            .or_default()
+            .clone();
+
+        //Convert a Rust Collection to a PyCollection
+        let py_collection: PyCollection =
+            PyCollection::new(rust_collection, self.inner.clone(), codec_options, indexes);
+        Ok(py_collection)
+    }
+}
+
+/// Fail for the collection holding the index definitions, it is not one of the user's.
+fn check_collection_name(name: &str) -> PyResult<()> {
+    if name.is_empty() || name == INDEX_CATALOG {
+        return Err(PyValueError::new_err(format!(
+            "'{}' is not a valid collection name",
+            name
+        )));
+    }
+    Ok(())
+}
+
+fn ttl_interval_from_py(ttl_interval: Option<f64>) -> PyResult<Option<Duration>> {
+    ttl_interval
+        .map(|seconds| match Duration::try_from_secs_f64(seconds) {
+            Ok(interval) if !interval.is_zero() => Ok(interval),
+            _ => Err(PyValueError::new_err(format!(
+                "ttl_interval: expected a positive number of seconds, got {}",
+                seconds
+            ))),
+        })
+        .transpose()
+}
+
+/// Run `operation` in a new transaction, committed when it succeeds and rolled back otherwise.
+fn run_transaction<R>(
+    database: &Database,
+    operation: impl FnOnce(&Transaction) -> PyResult<R>,
+    modify_error: impl Fn(polodb_core::Error) -> PyErr,
+) -> PyResult<R> {
+    let txn = database.start_transaction().map_err(&modify_error)?;
+    match operation(&txn) {
+        Ok(result) => {
+            txn.commit().map_err(modify_error)?;
+            Ok(result)
+        }
+        Err(err) => {
+            let _ = txn.rollback();
+            Err(err)
+        }
+    }
+}
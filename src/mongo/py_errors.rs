@@ -0,0 +1,56 @@
+use pyo3::PyErr;
+use pyo3::create_exception;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+
+create_exception!(
+    mongo_emb,
+    BulkWriteError,
+    PyRuntimeError,
+    "Raised by `bulk_write` when requests failed. `details` holds the counts of the writes that \
+     were applied and a `writeErrors` entry with the `index` and message of each failed request."
+);
+
+create_exception!(
+    mongo_emb,
+    ClosedError,
+    PyRuntimeError,
+    "Raised when a database, collection, cursor or Rdb is used after `close()`."
+);
+
+create_exception!(
+    mongo_emb,
+    DuplicateKeyError,
+    PyRuntimeError,
+    "Raised when a write would store a value that a unique index already holds."
+);
+
+create_exception!(
+    mongo_emb,
+    WriteConflictError,
+    PyRuntimeError,
+    "Raised when a write touches a document that an open transaction has written. A transaction \
+     that raises it is aborted and can be retried."
+);
+
+/// Turn a polodb error raised by a write into a Python exception, `DuplicateKeyError` for a
+/// unique index violation, `ValueError` for a value an index cannot hold and
+/// `WriteConflictError` for a key another transaction holds.
+pub fn write_error(context: &str, err: polodb_core::Error) -> PyErr {
+    let message = format!("{} error: {}", context, err);
+    match err {
+        polodb_core::Error::DuplicateKey(_) => DuplicateKeyError::new_err(message),
+        // Raised when a written document holds an array or another value polodb cannot index
+        // in an indexed field
+        polodb_core::Error::NotAValidKeyType(_) => PyValueError::new_err(format!(
+            "{}, an indexed field can only hold scalar values",
+            message
+        )),
+        // RocksDB gives up waiting for the lock of a key an open transaction wrote
+        polodb_core::Error::RocksDbErr(e)
+            if e.starts_with("Operation timed out") || e.starts_with("Resource busy") =>
+        {
+            WriteConflictError::new_err(message)
+        }
+        _ => PyRuntimeError::new_err(message),
+    }
+}
@@ -0,0 +1,52 @@
+use crate::mongo::helper_type_translator::{
+    CodecOptions, bson_to_extended_json, bson_to_py_obj, convert_py_obj_to_bson,
+    extended_json_to_bson,
+};
+use crate::mongo::py_codec_options::PyCodecOptions;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+/// Serialize a document, a list of documents or a single value as MongoDB Extended JSON v2,
+/// relaxed unless `canonical` is set. Values are converted with `codec_options`, e.g.
+/// `db.codec_options` to use the codecs registered on a database.
+#[pyfunction]
+#[pyo3(signature = (obj, canonical=false, codec_options=None))]
+pub fn to_extended_json(
+    obj: &Bound<'_, PyAny>,
+    canonical: bool,
+    codec_options: Option<PyRef<'_, PyCodecOptions>>,
+) -> PyResult<String> {
+    let bson = match &codec_options {
+        Some(codec_options) => convert_py_obj_to_bson(obj, "obj", codec_options.options())?,
+        None => convert_py_obj_to_bson(obj, "obj", &CodecOptions::default())?,
+    };
+    Ok(bson_to_extended_json(bson, canonical).to_string())
+}
+
+/// Parse MongoDB Extended JSON v2, canonical or relaxed, back into Python objects. Datetimes
+/// are naive UTC unless `tz_aware` or `tzinfo` is given, or taken from `codec_options`.
+#[pyfunction]
+#[pyo3(signature = (text, tz_aware=false, tzinfo=None, codec_options=None))]
+pub fn from_extended_json(
+    py: Python,
+    text: &str,
+    tz_aware: bool,
+    tzinfo: Option<Py<PyAny>>,
+    codec_options: Option<PyRef<'_, PyCodecOptions>>,
+) -> PyResult<Py<PyAny>> {
+    let bson = extended_json_to_bson(text, "text")?;
+    match codec_options {
+        Some(_) if tz_aware || tzinfo.is_some() => Err(PyValueError::new_err(
+            "Extended JSON error: give either tz_aware/tzinfo or codec_options",
+        )),
+        Some(codec_options) => bson_to_py_obj(py, &bson, codec_options.options()),
+        None => {
+            let opts = CodecOptions {
+                tz_aware,
+                tzinfo,
+                ..CodecOptions::default()
+            };
+            bson_to_py_obj(py, &bson, &opts)
+        }
+    }
+}
@@ -0,0 +1,628 @@
+use crate::mongo::helper_query::{compare_bson, path_values};
+use crate::mongo::py_errors::DuplicateKeyError;
+use crate::mongo::py_results::py_bool;
+use polodb_core::bson::{Bson, Document, doc};
+use polodb_core::{CollectionT, IndexModel, IndexOptions, Transaction, TransactionalCollection};
+use pyo3::exceptions::{PyRuntimeError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::cmp::Ordering;
+use std::sync::{Arc, PoisonError, RwLock};
+
+/// Collection recording the secondary indexes of every collection. polodb persists its indexes
+/// but has no way to list them, so their definitions are kept here as well.
+pub const INDEX_CATALOG: &str = "__mongo_emb_indexes";
+
+/// Name of the index every collection has on `_id`.
+pub const ID_INDEX_NAME: &str = "_id_";
+
+/// An index to create with `create_indexes`.
+#[pyclass(name = "IndexModel", frozen)]
+pub struct PyIndexModel {
+    #[pyo3(get)]
+    keys: Py<PyAny>,
+    #[pyo3(get)]
+    unique: bool,
+    #[pyo3(get)]
+    name: Option<String>,
+    #[pyo3(get)]
+    expire_after_seconds: Option<i64>,
+    #[pyo3(get)]
+    sparse: bool,
+}
+
+#[pymethods]
+impl PyIndexModel {
+    #[new]
+    #[pyo3(signature = (keys, unique=false, name=None, expire_after_seconds=None, sparse=false))]
+    fn new(
+        keys: Py<PyAny>,
+        unique: bool,
+        name: Option<String>,
+        expire_after_seconds: Option<i64>,
+        sparse: bool,
+    ) -> Self {
+        PyIndexModel {
+            keys,
+            unique,
+            name,
+            expire_after_seconds,
+            sparse,
+        }
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!(
+            "IndexModel({}, unique={}, name={}, expire_after_seconds={}, sparse={})",
+            self.keys.bind(py).repr()?,
+            py_bool(self.unique),
+            match &self.name {
+                Some(name) => format!("{:?}", name),
+                None => "None".to_string(),
+            },
+            match self.expire_after_seconds {
+                Some(seconds) => seconds.to_string(),
+                None => "None".to_string(),
+            },
+            py_bool(self.sparse)
+        ))
+    }
+}
+
+impl PyIndexModel {
+    pub fn to_spec(&self, py: Python) -> PyResult<IndexSpec> {
+        IndexSpec::from_py(
+            self.keys.bind(py),
+            self.unique,
+            self.name.clone(),
+            self.expire_after_seconds,
+            self.sparse,
+        )
+    }
+}
+
+/// A secondary index, as recorded in the catalog.
+#[derive(PartialEq)]
+pub struct IndexSpec {
+    pub name: String,
+    pub field: String,
+    pub unique: bool,
+    /// Set on TTL indexes, see `helper_ttl`
+    pub expire_after_seconds: Option<i64>,
+    /// Whether documents without the field are left out of a unique index, see `unique_key`
+    pub sparse: bool,
+}
+
+impl IndexSpec {
+    /// Parse pymongo style index keys: a field name, a list of `(field, direction)` pairs or a
+    /// dict. polodb only indexes a single field in ascending order.
+    pub fn from_py(
+        keys: &Bound<'_, PyAny>,
+        unique: bool,
+        name: Option<String>,
+        expire_after_seconds: Option<i64>,
+        sparse: bool,
+    ) -> PyResult<IndexSpec> {
+        let pairs: Vec<(String, i64)> = if let Ok(field) = keys.extract::<String>() {
+            vec![(field, 1)]
+        } else if let Ok(dict) = keys.cast::<PyDict>() {
+            dict.iter()
+                .map(|(field, direction)| Ok((field.extract()?, direction.extract()?)))
+                .collect::<PyResult<_>>()?
+        } else if let Ok(pairs) = keys.extract::<Vec<(String, i64)>>() {
+            pairs
+        } else {
+            return Err(PyTypeError::new_err(
+                "keys: expected a field name, a list of (field, direction) pairs or a dict",
+            ));
+        };
+        let field = match pairs.as_slice() {
+            [(field, 1)] => field.clone(),
+            [(field, direction)] => {
+                return Err(PyValueError::new_err(format!(
+                    "keys.{}: only ascending (1) indexes are supported, got {}",
+                    field, direction
+                )));
+            }
+            _ => {
+                return Err(PyValueError::new_err(
+                    "keys: only single field indexes are supported",
+                ));
+            }
+        };
+        if let Some(seconds) = expire_after_seconds
+            && seconds < 0
+        {
+            return Err(PyValueError::new_err(format!(
+                "expire_after_seconds: must not be negative, got {}",
+                seconds
+            )));
+        }
+        Ok(IndexSpec {
+            // The name MongoDB would give, with the dots polodb rejects replaced
+            name: name.unwrap_or_else(|| format!("{}_1", field.replace('.', "_"))),
+            field,
+            unique,
+            expire_after_seconds,
+            sparse,
+        })
+    }
+
+    fn from_catalog(entry: &Document) -> Option<IndexSpec> {
+        Some(IndexSpec {
+            name: entry.get_str("name").ok()?.to_string(),
+            field: entry.get_str("field").ok()?.to_string(),
+            unique: entry.get_bool("unique").unwrap_or(false),
+            expire_after_seconds: entry.get_i64("expireAfterSeconds").ok(),
+            sparse: entry.get_bool("sparse").unwrap_or(false),
+        })
+    }
+
+    fn to_catalog(&self, collection: &str) -> Document {
+        let mut entry = doc! {
+            "ns": collection,
+            "name": &self.name,
+            "field": &self.field,
+            "unique": self.unique,
+            "sparse": self.sparse,
+        };
+        if let Some(seconds) = self.expire_after_seconds {
+            entry.insert("expireAfterSeconds", seconds);
+        }
+        entry
+    }
+
+    /// The description `list_indexes` returns, shaped like MongoDB's.
+    pub fn to_document(&self) -> Document {
+        let mut description = doc! {
+            "name": &self.name,
+            "key": { &self.field: 1 },
+        };
+        if self.unique {
+            description.insert("unique", true);
+        }
+        if self.sparse {
+            description.insert("sparse", true);
+        }
+        if let Some(seconds) = self.expire_after_seconds {
+            description.insert("expireAfterSeconds", seconds);
+        }
+        description
+    }
+
+    /// The index on `_id` that every collection has.
+    pub fn id_index() -> IndexSpec {
+        IndexSpec {
+            name: ID_INDEX_NAME.to_string(),
+            field: "_id".to_string(),
+            unique: true,
+            expire_after_seconds: None,
+            sparse: false,
+        }
+    }
+}
+
+/// The secondary indexes of one collection, shared by its handles so writes don't read the
+/// catalog every time. Cleared whenever the indexes change.
+#[derive(Default)]
+pub struct IndexCache(RwLock<Option<Arc<Vec<IndexSpec>>>>);
+
+impl IndexCache {
+    pub fn get_or_load(
+        &self,
+        load: impl FnOnce() -> PyResult<Vec<IndexSpec>>,
+    ) -> PyResult<Arc<Vec<IndexSpec>>> {
+        let lock_error = |e: String| PyRuntimeError::new_err(format!("Failed to lock: {}", e));
+        if let Some(specs) = self
+            .0
+            .read()
+            .map_err(|e| lock_error(e.to_string()))?
+            .as_ref()
+        {
+            return Ok(specs.clone());
+        }
+        let specs = Arc::new(load()?);
+        *self.0.write().map_err(|e| lock_error(e.to_string()))? = Some(specs.clone());
+        Ok(specs)
+    }
+
+    pub fn invalidate(&self) {
+        // An emptied cache is valid whatever a panicking thread left in it
+        *self.0.write().unwrap_or_else(PoisonError::into_inner) = None;
+        self.0.clear_poison();
+    }
+}
+
+/// The value a unique index keys `doc` on. A document without the field is keyed on null, as in
+/// MongoDB, unless the index is sparse and leaves it out. Fails with a `ValueError` on the values
+/// whose holders polodb cannot look up: embedded documents, and values reached through an array.
+pub fn unique_key<'a>(
+    doc: &'a Document,
+    collection: &str,
+    spec: &IndexSpec,
+    context: &str,
+) -> PyResult<Option<&'a Bson>> {
+    let held = match (
+        indexed_value(doc, &spec.field),
+        path_values(doc, &spec.field).as_slice(),
+    ) {
+        (Some(value @ Bson::Array(_)), _) => element_type_name(value),
+        (Some(value), _) => return Ok(Some(value)),
+        (None, []) => return Ok((!spec.sparse).then_some(&Bson::Null)),
+        (None, [Bson::Document(_)]) => "an embedded document",
+        (None, _) => "values inside an array",
+    };
+    Err(PyValueError::new_err(format!(
+        "{} error: collection: {}, index: {}: the document with _id {} holds {} in '{}', \
+         a unique index only checks scalar values",
+        context,
+        collection,
+        spec.name,
+        doc.get("_id").unwrap_or(&Bson::Null),
+        held,
+        spec.field
+    )))
+}
+
+/// How many documents of `collection` a unique index holds under `key`, counting up to two. polodb
+/// neither indexes nor finds documents without the field, so the null key of an index that is
+/// not sparse reads the whole collection for them.
+pub fn count_key_holders(
+    collection: &TransactionalCollection<Document>,
+    spec: &IndexSpec,
+    key: &Bson,
+    modify_error: impl Fn(polodb_core::Error) -> PyErr + Copy,
+) -> PyResult<usize> {
+    let mut holders = 0;
+    let mut cursor = collection
+        .find(doc! {&spec.field: key.clone()})
+        .limit(2)
+        .run()
+        .map_err(modify_error)?;
+    while cursor.advance().map_err(modify_error)? {
+        holders += 1;
+    }
+    if *key != Bson::Null || spec.sparse {
+        return Ok(holders);
+    }
+    let mut cursor = collection.find(doc! {}).run().map_err(modify_error)?;
+    while holders < 2 && cursor.advance().map_err(modify_error)? {
+        let doc = cursor.deserialize_current().map_err(modify_error)?;
+        if path_values(&doc, &spec.field).is_empty() {
+            holders += 1;
+        }
+    }
+    Ok(holders)
+}
+
+/// Create `spec` on `collection` and record it. Creating an index that already exists with the
+/// same definition does nothing.
+pub fn create_index(
+    txn: &Transaction,
+    collection: &str,
+    spec: &IndexSpec,
+    modify_error: impl Fn(polodb_core::Error) -> PyErr + Copy,
+) -> PyResult<()> {
+    if spec.name == ID_INDEX_NAME {
+        return Err(PyValueError::new_err(format!(
+            "name: '{}' is reserved for the _id index",
+            ID_INDEX_NAME
+        )));
+    }
+    for existing in list_indexes(txn, collection, modify_error)? {
+        if existing == *spec {
+            return Ok(());
+        }
+        if existing.name == spec.name {
+            return Err(PyValueError::new_err(format!(
+                "index '{}' already exists with a different definition",
+                spec.name
+            )));
+        }
+        if existing.field == spec.field {
+            return Err(PyValueError::new_err(format!(
+                "an index on '{}' already exists with the name '{}'",
+                spec.field, existing.name
+            )));
+        }
+    }
+
+    check_existing_documents(txn, collection, spec, modify_error)?;
+    // Uniqueness is enforced by `CollectionWriter`, polodb's own check also rejects updates and
+    // deletes of indexed documents
+    let options = IndexOptions {
+        name: Some(spec.name.clone()),
+        unique: Some(false),
+    };
+    txn.collection::<Document>(collection)
+        .create_index(IndexModel {
+            keys: doc! { &spec.field: 1 },
+            options: Some(options),
+        })
+        .map_err(modify_error)?;
+    txn.collection::<Document>(INDEX_CATALOG)
+        .insert_one(spec.to_catalog(collection))
+        .map_err(modify_error)?;
+    Ok(())
+}
+
+/// Fail with a `ValueError` when a document already in `collection` holds a value polodb
+/// cannot index for `spec`, and with `DuplicateKeyError` when documents share a value of a
+/// unique index.
+fn check_existing_documents(
+    txn: &Transaction,
+    collection: &str,
+    spec: &IndexSpec,
+    modify_error: impl Fn(polodb_core::Error) -> PyErr + Copy,
+) -> PyResult<()> {
+    let mut cursor = txn
+        .collection::<Document>(collection)
+        .find(doc! {})
+        .run()
+        .map_err(modify_error)?;
+    let mut keys = Vec::new();
+    while cursor.advance().map_err(modify_error)? {
+        let doc = cursor.deserialize_current().map_err(modify_error)?;
+        check_indexable(&doc, collection, spec, "Create index")?;
+        if spec.unique
+            && let Some(key) = unique_key(&doc, collection, spec, "Create index")?
+        {
+            keys.push(key.clone());
+        }
+    }
+    keys.sort_unstable_by(compare_bson);
+    match keys
+        .windows(2)
+        .find(|pair| compare_bson(&pair[0], &pair[1]) == Ordering::Equal)
+    {
+        Some(pair) => Err(DuplicateKeyError::new_err(format!(
+            "Create index error: duplicate key error collection: {}, index: {}, key: {}",
+            collection, spec.name, pair[0]
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Fail with a `ValueError` when `doc` holds a value polodb cannot index for `spec`. polodb
+/// would only notice after storing the document, leaving it behind without its index entry.
+pub fn check_indexable(
+    doc: &Document,
+    collection: &str,
+    spec: &IndexSpec,
+    context: &str,
+) -> PyResult<()> {
+    match indexed_value(doc, &spec.field) {
+        Some(value) if !is_indexable(value) => Err(PyValueError::new_err(format!(
+            "{} error: collection: {}, index: {}: the document with _id {} holds {} in '{}', \
+             polodb only indexes scalar values",
+            context,
+            collection,
+            spec.name,
+            doc.get("_id").unwrap_or(&Bson::Null),
+            element_type_name(value),
+            spec.field
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// The value polodb indexes `doc` on: the one at `field`, descending through embedded documents
+/// only. An embedded document at the end of the path is not indexed.
+fn indexed_value<'a>(doc: &'a Document, field: &str) -> Option<&'a Bson> {
+    let mut current = doc;
+    let mut segments = field.split('.').peekable();
+    while let Some(segment) = segments.next() {
+        match (current.get(segment)?, segments.peek()) {
+            (Bson::Document(child), _) => current = child,
+            (value, None) => return Some(value),
+            (_, Some(_)) => return None,
+        }
+    }
+    None
+}
+
+/// Whether polodb can build an index key from `value`, see `polodb_core::utils::bson`. The same
+/// goes for `_id`, the primary key.
+pub fn is_indexable(value: &Bson) -> bool {
+    matches!(
+        value,
+        Bson::Double(_)
+            | Bson::String(_)
+            | Bson::Boolean(_)
+            | Bson::Null
+            | Bson::Int32(_)
+            | Bson::Int64(_)
+            | Bson::Timestamp(_)
+            | Bson::ObjectId(_)
+            | Bson::DateTime(_)
+            | Bson::Symbol(_)
+            | Bson::Decimal128(_)
+            | Bson::Undefined
+    )
+}
+
+pub fn element_type_name(value: &Bson) -> &'static str {
+    match value {
+        Bson::Array(_) => "an array",
+        Bson::Binary(_) => "binary data",
+        Bson::RegularExpression(_) => "a regular expression",
+        Bson::JavaScriptCode(_) | Bson::JavaScriptCodeWithScope(_) => "code",
+        Bson::MinKey | Bson::MaxKey => "a min or max key",
+        _ => "a value of another type",
+    }
+}
+
+/// Drop the index called `name` from `collection`.
+pub fn drop_index(
+    txn: &Transaction,
+    collection: &str,
+    name: &str,
+    modify_error: impl Fn(polodb_core::Error) -> PyErr + Copy,
+) -> PyResult<()> {
+    if name == ID_INDEX_NAME {
+        return Err(PyValueError::new_err("cannot drop the _id index"));
+    }
+    let deleted = txn
+        .collection::<Document>(INDEX_CATALOG)
+        .delete_one(doc! {"ns": collection, "name": name})
+        .map_err(modify_error)?;
+    if deleted.deleted_count == 0 {
+        return Err(PyValueError::new_err(format!(
+            "index not found with name '{}'",
+            name
+        )));
+    }
+    txn.collection::<Document>(collection)
+        .drop_index(name)
+        .map_err(modify_error)
+}
+
+/// Create `collection` inside `txn` unless it exists. polodb only offers that on its own
+/// transaction, but creates the collection an insert goes to, even an empty one.
+pub fn create_collection(
+    txn: &Transaction,
+    collection: &str,
+    modify_error: impl Fn(polodb_core::Error) -> PyErr + Copy,
+) -> PyResult<()> {
+    txn.collection::<Document>(collection)
+        .insert_many(Vec::<Document>::new())
+        .map(|_| ())
+        .map_err(modify_error)
+}
+
+/// Drop `collection` with its documents and the record of its indexes. Dropping a collection
+/// that does not exist does nothing.
+pub fn drop_collection(
+    txn: &Transaction,
+    collection: &str,
+    modify_error: impl Fn(polodb_core::Error) -> PyErr + Copy,
+) -> PyResult<()> {
+    txn.collection::<Document>(INDEX_CATALOG)
+        .delete_many(doc! {"ns": collection})
+        .map_err(modify_error)?;
+    txn.collection::<Document>(collection)
+        .drop()
+        .map_err(modify_error)
+}
+
+/// The secondary indexes recorded for `collection`, in creation order.
+pub fn list_indexes(
+    txn: &Transaction,
+    collection: &str,
+    modify_error: impl Fn(polodb_core::Error) -> PyErr + Copy,
+) -> PyResult<Vec<IndexSpec>> {
+    Ok(read_catalog(txn, doc! {"ns": collection}, modify_error)?
+        .into_iter()
+        .map(|(_, spec)| spec)
+        .collect())
+}
+
+/// The TTL indexes of every collection, with the name of their collection.
+pub fn list_ttl_indexes(
+    txn: &Transaction,
+    modify_error: impl Fn(polodb_core::Error) -> PyErr + Copy,
+) -> PyResult<Vec<(String, IndexSpec)>> {
+    Ok(read_catalog(txn, doc! {}, modify_error)?
+        .into_iter()
+        .filter(|(_, spec)| spec.expire_after_seconds.is_some())
+        .collect())
+}
+
+fn read_catalog(
+    txn: &Transaction,
+    filter: Document,
+    modify_error: impl Fn(polodb_core::Error) -> PyErr + Copy,
+) -> PyResult<Vec<(String, IndexSpec)>> {
+    let mut cursor = txn
+        .collection::<Document>(INDEX_CATALOG)
+        .find(filter)
+        .run()
+        .map_err(modify_error)?;
+    let mut specs = Vec::new();
+    while cursor.advance().map_err(modify_error)? {
+        let entry = cursor.deserialize_current().map_err(modify_error)?;
+        if let (Ok(collection), Some(spec)) = (entry.get_str("ns"), IndexSpec::from_catalog(&entry))
+        {
+            specs.push((collection.to_string(), spec));
+        }
+    }
+    Ok(specs)
+}
+
+/// Name of the index described by `index`: a name, or keys as given to `create_index`.
+pub fn index_name_from_py(index: &Bound<'_, PyAny>) -> PyResult<String> {
+    match index.extract::<String>() {
+        Ok(name) => Ok(name),
+        Err(_) => Ok(IndexSpec::from_py(index, false, None, None, false)?.name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polodb_core::bson::Regex;
+
+    #[test]
+    fn indexed_values() {
+        let regex = Regex {
+            pattern: "x".to_string(),
+            options: String::new(),
+        };
+        let doc = doc! {"a": {"b": [1]}, "c": {"d": 1}, "e": [{"f": 1}], "g": regex};
+        assert_eq!(
+            indexed_value(&doc, "a.b"),
+            Some(&Bson::Array(vec![Bson::Int32(1)]))
+        );
+        assert_eq!(indexed_value(&doc, "c.d"), Some(&Bson::Int32(1)));
+        assert_eq!(indexed_value(&doc, "c"), None);
+        assert_eq!(indexed_value(&doc, "e.f"), None);
+        assert_eq!(indexed_value(&doc, "missing"), None);
+
+        let spec = |field: &str| IndexSpec {
+            name: format!("{}_1", field),
+            field: field.to_string(),
+            unique: false,
+            expire_after_seconds: None,
+            sparse: false,
+        };
+        assert!(check_indexable(&doc, "items", &spec("a.b"), "Insert").is_err());
+        assert!(check_indexable(&doc, "items", &spec("g"), "Insert").is_err());
+        assert!(check_indexable(&doc, "items", &spec("c.d"), "Insert").is_ok());
+        assert!(check_indexable(&doc, "items", &spec("c"), "Insert").is_ok());
+    }
+
+    #[test]
+    fn unique_keys_count_missing_as_null_unless_sparse() {
+        let doc = doc! {"_id": 1, "a": 1, "b": null, "d": {"e": 2}, "e": [{"f": 1}]};
+        let spec = |field: &str, sparse: bool| IndexSpec {
+            name: format!("{}_1", field),
+            field: field.to_string(),
+            unique: true,
+            expire_after_seconds: None,
+            sparse,
+        };
+        let key =
+            |field: &str, sparse: bool| unique_key(&doc, "items", &spec(field, sparse), "Insert");
+        assert_eq!(key("a", false).unwrap(), Some(&Bson::Int32(1)));
+        assert_eq!(key("d.e", false).unwrap(), Some(&Bson::Int32(2)));
+        assert_eq!(key("b", true).unwrap(), Some(&Bson::Null));
+        assert_eq!(key("missing", false).unwrap(), Some(&Bson::Null));
+        assert_eq!(key("missing", true).unwrap(), None);
+        assert!(key("d", false).is_err());
+        assert!(key("e.f", true).is_err());
+    }
+
+    #[test]
+    fn poisoned_index_cache() {
+        let cache = Arc::new(IndexCache::default());
+        let poisoner = cache.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = poisoner.0.write().unwrap();
+            panic!("poison the lock");
+        })
+        .join();
+        assert!(cache.get_or_load(|| Ok(Vec::new())).is_err());
+        cache.invalidate();
+        assert!(cache.get_or_load(|| Ok(Vec::new())).unwrap().is_empty());
+    }
+}
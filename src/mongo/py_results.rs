@@ -0,0 +1,229 @@
+use crate::mongo::helper_type_translator::{CodecOptions, bson_to_py_obj};
+use polodb_core::bson::Bson;
+use polodb_core::results;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+/// Result of `insert_one`.
+#[pyclass(name = "InsertOneResult", frozen)]
+pub struct PyInsertOneResult {
+    #[pyo3(get)]
+    inserted_id: Py<PyAny>,
+    /// Always true, every write is acknowledged by the embedded database.
+    #[pyo3(get)]
+    acknowledged: bool,
+}
+
+#[pymethods]
+impl PyInsertOneResult {
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!(
+            "InsertOneResult({}, acknowledged={})",
+            self.inserted_id.bind(py).repr()?,
+            py_bool(self.acknowledged)
+        ))
+    }
+}
+
+impl PyInsertOneResult {
+    pub fn new(
+        py: Python,
+        result: results::InsertOneResult,
+        opts: &CodecOptions,
+    ) -> PyResult<PyInsertOneResult> {
+        Ok(PyInsertOneResult {
+            inserted_id: bson_to_py_obj(py, &result.inserted_id, opts)?,
+            acknowledged: true,
+        })
+    }
+}
+
+/// Result of `insert_many`, `inserted_ids` is in the order the documents were given.
+#[pyclass(name = "InsertManyResult", frozen)]
+pub struct PyInsertManyResult {
+    #[pyo3(get)]
+    inserted_ids: Py<PyList>,
+    #[pyo3(get)]
+    acknowledged: bool,
+}
+
+#[pymethods]
+impl PyInsertManyResult {
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!(
+            "InsertManyResult({}, acknowledged={})",
+            self.inserted_ids.bind(py).repr()?,
+            py_bool(self.acknowledged)
+        ))
+    }
+}
+
+impl PyInsertManyResult {
+    pub fn new(
+        py: Python,
+        result: results::InsertManyResult,
+        opts: &CodecOptions,
+    ) -> PyResult<PyInsertManyResult> {
+        // polodb keys the ids by the index of their document
+        let mut inserted_ids: Vec<(usize, Bson)> = result.inserted_ids.into_iter().collect();
+        inserted_ids.sort_unstable_by_key(|(index, _)| *index);
+        let py_ids = PyList::empty(py);
+        for (_, id) in &inserted_ids {
+            py_ids.append(bson_to_py_obj(py, id, opts)?)?;
+        }
+        Ok(PyInsertManyResult {
+            inserted_ids: py_ids.unbind(),
+            acknowledged: true,
+        })
+    }
+}
+
+/// Result of the update and replace methods. `upserted_id` is the `_id` of the document an
+/// upsert inserted, None when an existing document matched.
+#[pyclass(name = "UpdateResult", frozen)]
+pub struct PyUpdateResult {
+    #[pyo3(get)]
+    matched_count: u64,
+    #[pyo3(get)]
+    modified_count: u64,
+    #[pyo3(get)]
+    upserted_id: Py<PyAny>,
+    #[pyo3(get)]
+    acknowledged: bool,
+}
+
+#[pymethods]
+impl PyUpdateResult {
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!(
+            "UpdateResult(matched_count={}, modified_count={}, upserted_id={}, acknowledged={})",
+            self.matched_count,
+            self.modified_count,
+            self.upserted_id.bind(py).repr()?,
+            py_bool(self.acknowledged)
+        ))
+    }
+}
+
+impl PyUpdateResult {
+    pub fn new(
+        py: Python,
+        result: results::UpdateResult,
+        upserted_id: Option<Bson>,
+        opts: &CodecOptions,
+    ) -> PyResult<PyUpdateResult> {
+        let upserted_id = match upserted_id {
+            Some(id) => bson_to_py_obj(py, &id, opts)?,
+            None => py.None(),
+        };
+        Ok(PyUpdateResult {
+            matched_count: result.matched_count,
+            modified_count: result.modified_count,
+            upserted_id,
+            acknowledged: true,
+        })
+    }
+}
+
+/// Result of `delete_one` and `delete_many`.
+#[pyclass(name = "DeleteResult", frozen)]
+pub struct PyDeleteResult {
+    #[pyo3(get)]
+    deleted_count: u64,
+    #[pyo3(get)]
+    acknowledged: bool,
+}
+
+#[pymethods]
+impl PyDeleteResult {
+    fn __repr__(&self) -> String {
+        format!(
+            "DeleteResult(deleted_count={}, acknowledged={})",
+            self.deleted_count,
+            py_bool(self.acknowledged)
+        )
+    }
+}
+
+impl From<results::DeleteResult> for PyDeleteResult {
+    fn from(result: results::DeleteResult) -> PyDeleteResult {
+        PyDeleteResult {
+            deleted_count: result.deleted_count,
+            acknowledged: true,
+        }
+    }
+}
+
+/// What the requests of a `bulk_write` did, the upserted ids keyed by request index.
+#[derive(Default)]
+pub struct BulkWriteCounts {
+    pub inserted_count: u64,
+    pub matched_count: u64,
+    pub modified_count: u64,
+    pub deleted_count: u64,
+    pub upserted_ids: Vec<(usize, Bson)>,
+}
+
+/// Result of `bulk_write`, `upserted_ids` maps the index of each upserting request to the `_id`
+/// it inserted.
+#[pyclass(name = "BulkWriteResult", frozen)]
+pub struct PyBulkWriteResult {
+    #[pyo3(get)]
+    inserted_count: u64,
+    #[pyo3(get)]
+    matched_count: u64,
+    #[pyo3(get)]
+    modified_count: u64,
+    #[pyo3(get)]
+    deleted_count: u64,
+    #[pyo3(get)]
+    upserted_count: u64,
+    #[pyo3(get)]
+    upserted_ids: Py<PyDict>,
+    #[pyo3(get)]
+    acknowledged: bool,
+}
+
+#[pymethods]
+impl PyBulkWriteResult {
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!(
+            "BulkWriteResult(inserted_count={}, matched_count={}, modified_count={}, \
+             deleted_count={}, upserted_count={}, upserted_ids={}, acknowledged={})",
+            self.inserted_count,
+            self.matched_count,
+            self.modified_count,
+            self.deleted_count,
+            self.upserted_count,
+            self.upserted_ids.bind(py).repr()?,
+            py_bool(self.acknowledged)
+        ))
+    }
+}
+
+impl PyBulkWriteResult {
+    pub fn new(
+        py: Python,
+        counts: BulkWriteCounts,
+        opts: &CodecOptions,
+    ) -> PyResult<PyBulkWriteResult> {
+        let upserted_ids = PyDict::new(py);
+        for (index, id) in &counts.upserted_ids {
+            upserted_ids.set_item(index, bson_to_py_obj(py, id, opts)?)?;
+        }
+        Ok(PyBulkWriteResult {
+            inserted_count: counts.inserted_count,
+            matched_count: counts.matched_count,
+            modified_count: counts.modified_count,
+            deleted_count: counts.deleted_count,
+            upserted_count: counts.upserted_ids.len() as u64,
+            upserted_ids: upserted_ids.unbind(),
+            acknowledged: true,
+        })
+    }
+}
+
+/// How Python spells `value`, for the `__repr__`s.
+pub(crate) fn py_bool(value: bool) -> &'static str {
+    if value { "True" } else { "False" }
+}
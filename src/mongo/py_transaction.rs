@@ -0,0 +1,323 @@
+use crate::mongo::helper_stats::{CollectionSize, SizeChanges, SizeCounters};
+use crate::mongo::py_database::{PyCollection, PyDatabase};
+use crate::mongo::py_errors::{ClosedError, WriteConflictError};
+use crate::mongo::py_index::IndexCache;
+use polodb_core::Transaction;
+use polodb_core::bson::{Bson, doc};
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// Where a transaction is at, it holds the polodb transaction until it ends.
+enum TransactionState {
+    Active(Transaction),
+    Committed,
+    Aborted,
+    Closed,
+}
+
+impl TransactionState {
+    fn transaction(&self) -> PyResult<&Transaction> {
+        match self {
+            TransactionState::Active(txn) => Ok(txn),
+            TransactionState::Committed => Err(PyRuntimeError::new_err(
+                "Transaction error: the transaction has been committed",
+            )),
+            TransactionState::Aborted => Err(PyRuntimeError::new_err(
+                "Transaction error: the transaction has been aborted",
+            )),
+            TransactionState::Closed => Err(ClosedError::new_err(
+                "Transaction error: the database has been closed",
+            )),
+        }
+    }
+}
+
+/// A transaction started by `PyDatabase.start_transaction`, shared by the collections opened
+/// from it.
+pub struct TransactionSession {
+    state: Mutex<TransactionState>,
+    // Index caches of collections whose indexes changed, cleared again when the transaction ends
+    // since other handles may have reloaded them in between
+    changed_indexes: Mutex<Vec<Arc<IndexCache>>>,
+    // The documents written so far, by collection and BSON encoded `_id`. RocksDB keeps them
+    // locked until the transaction ends and makes other writers to them wait for its lock
+    // timeout, so they are refused before reaching it, see `check_write`
+    written: Mutex<HashSet<(String, Vec<u8>)>>,
+    // How the writes changed the size of collections, applied to the database's `sizes` when
+    // the transaction commits
+    size_changes: Mutex<SizeChanges>,
+    sizes: Arc<SizeCounters>,
+}
+
+impl TransactionSession {
+    pub fn new(txn: Transaction, sizes: Arc<SizeCounters>) -> TransactionSession {
+        TransactionSession {
+            state: Mutex::new(TransactionState::Active(txn)),
+            changed_indexes: Mutex::default(),
+            written: Mutex::default(),
+            size_changes: Mutex::default(),
+            sizes,
+        }
+    }
+
+    /// Run `operation` in the transaction. A failed operation may have written part of its
+    /// changes, so it aborts the transaction, like MongoDB does.
+    pub fn run<R>(&self, operation: impl FnOnce(&Transaction) -> PyResult<R>) -> PyResult<R> {
+        let mut state = self.lock_state()?;
+        let result = operation(state.transaction()?);
+        if result.is_err() {
+            self.end(&mut state, TransactionState::Aborted);
+        }
+        result
+    }
+
+    /// Read inside the transaction, failing with `DbIsClosed` once it has ended.
+    pub fn read<R>(
+        &self,
+        operation: impl FnOnce(&Transaction) -> polodb_core::Result<R>,
+    ) -> polodb_core::Result<R> {
+        let state = self
+            .state
+            .lock()
+            .map_err(|_| polodb_core::Error::DbIsClosed)?;
+        match &*state {
+            TransactionState::Active(txn) => operation(txn),
+            _ => Err(polodb_core::Error::DbIsClosed),
+        }
+    }
+
+    /// Fail unless the transaction can still be used.
+    pub fn check_active(&self) -> PyResult<()> {
+        self.lock_state()?.transaction().map(|_| ())
+    }
+
+    /// Clear `indexes` now and again when the transaction ends.
+    pub fn indexes_changed(&self, indexes: &Arc<IndexCache>) {
+        indexes.invalidate();
+        if let Ok(mut changed) = self.changed_indexes.lock() {
+            changed.push(indexes.clone());
+        }
+    }
+
+    /// Record how a write in the transaction changed the size of collections. Called within
+    /// `run`, so the transaction cannot commit before.
+    pub fn resized(&self, changes: SizeChanges) {
+        self.size_changes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .merge(changes);
+    }
+
+    /// The size of `collection` as the transaction sees it, counted by `count` when the
+    /// database does not know it yet. `count` reads inside the transaction.
+    pub fn collection_size(
+        &self,
+        collection: &str,
+        count: impl FnOnce() -> PyResult<CollectionSize>,
+    ) -> PyResult<CollectionSize> {
+        let change = self
+            .size_changes
+            .lock()
+            .map_err(|e| PyRuntimeError::new_err(format!("Failed to lock: {}", e)))?
+            .get(collection);
+        match change {
+            None => self.sizes.get_or_count(collection, count),
+            // Dropped or renamed in the transaction, the database's count no longer applies
+            Some(None) => count(),
+            Some(Some(change)) => {
+                let committed = self
+                    .sizes
+                    .get_or_count(collection, || Ok(count()? - change))?;
+                Ok(committed + change)
+            }
+        }
+    }
+
+    /// Record that this transaction writes the document with `id` in `collection`.
+    pub fn claim(&self, collection: &str, id: &Bson) {
+        if let Ok(mut written) = self.written.lock() {
+            written.insert(document_key(collection, id));
+        }
+    }
+
+    /// Whether this transaction has written a document and not ended yet.
+    pub fn has_writes(&self) -> bool {
+        self.written
+            .lock()
+            .map(|written| !written.is_empty())
+            .unwrap_or(false)
+    }
+
+    /// Whether this transaction has written the document with `id` in `collection`, assumed when
+    /// its record of them cannot be read.
+    pub fn has_written(&self, collection: &str, id: &Bson) -> bool {
+        self.written
+            .lock()
+            .map(|written| written.contains(&document_key(collection, id)))
+            .unwrap_or(true)
+    }
+
+    /// Fail with `WriteConflictError` when this transaction has written the document with `id`
+    /// in `collection`, which another writer then cannot change until it ends.
+    pub fn check_write(&self, collection: &str, id: &Bson, context: &str) -> PyResult<()> {
+        let written = self
+            .written
+            .lock()
+            .map_err(|e| PyRuntimeError::new_err(format!("Failed to lock: {}", e)))?;
+        if written.contains(&document_key(collection, id)) {
+            return Err(WriteConflictError::new_err(format!(
+                "{} error: write conflict, the document with _id {} in '{}' is written by a \
+                 transaction that has not ended",
+                context, id, collection
+            )));
+        }
+        Ok(())
+    }
+
+    /// Roll back and release the transaction because its database is closing.
+    pub fn close(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            self.end(&mut state, TransactionState::Closed);
+        }
+    }
+
+    fn commit(&self) -> PyResult<()> {
+        let mut state = self.lock_state()?;
+        let committed = match &*state {
+            TransactionState::Active(txn) => {
+                let changes = std::mem::take(
+                    &mut *self
+                        .size_changes
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner),
+                );
+                self.sizes.commit(changes, || txn.commit())
+            }
+            TransactionState::Committed => return Ok(()),
+            TransactionState::Aborted => {
+                return Err(PyRuntimeError::new_err(
+                    "Commit transaction error: the transaction has been aborted",
+                ));
+            }
+            TransactionState::Closed => return state.transaction().map(|_| ()),
+        };
+        match committed {
+            Ok(()) => {
+                self.end(&mut state, TransactionState::Committed);
+                Ok(())
+            }
+            Err(e) => {
+                // A failed commit leaves nothing behind
+                self.end(&mut state, TransactionState::Aborted);
+                Err(PyRuntimeError::new_err(format!(
+                    "Commit transaction error: {}",
+                    e
+                )))
+            }
+        }
+    }
+
+    fn abort(&self) -> PyResult<()> {
+        let mut state = self.lock_state()?;
+        match &*state {
+            TransactionState::Active(_) => {
+                self.end(&mut state, TransactionState::Aborted);
+                Ok(())
+            }
+            TransactionState::Committed => Err(PyRuntimeError::new_err(
+                "Abort transaction error: the transaction has been committed",
+            )),
+            TransactionState::Aborted => Ok(()),
+            TransactionState::Closed => state.transaction().map(|_| ()),
+        }
+    }
+
+    /// Move an active transaction to `ended`, rolling back what it did unless it committed.
+    fn end(&self, state: &mut TransactionState, ended: TransactionState) {
+        let TransactionState::Active(txn) = std::mem::replace(state, ended) else {
+            return;
+        };
+        if !matches!(state, TransactionState::Committed) {
+            let _ = txn.rollback();
+        }
+        if let Ok(mut changed) = self.changed_indexes.lock() {
+            for indexes in changed.drain(..) {
+                indexes.invalidate();
+            }
+        }
+        if let Ok(mut written) = self.written.lock() {
+            written.clear();
+        }
+        if let Ok(mut size_changes) = self.size_changes.lock() {
+            *size_changes = SizeChanges::default();
+        }
+    }
+
+    fn lock_state(&self) -> PyResult<MutexGuard<'_, TransactionState>> {
+        self.state
+            .lock()
+            .map_err(|e| PyRuntimeError::new_err(format!("Failed to lock: {}", e)))
+    }
+}
+
+fn document_key(collection: &str, id: &Bson) -> (String, Vec<u8>) {
+    let mut key = Vec::new();
+    let _ = doc! {"_id": id.clone()}.to_writer(&mut key);
+    (collection.to_string(), key)
+}
+
+/// A transaction, used as a context manager: committed when the block exits cleanly and
+/// aborted when it raises. Collections opened from it read and write inside the transaction,
+/// and nothing they write is visible to others until it commits.
+#[pyclass(name = "Transaction")]
+pub struct PyTransaction {
+    session: Arc<TransactionSession>,
+    database: Py<PyDatabase>,
+}
+
+#[pymethods]
+impl PyTransaction {
+    /// Open a collection inside the transaction.
+    fn collection(&self, py: Python, name: &str) -> PyResult<PyCollection> {
+        self.session.check_active()?;
+        self.database
+            .borrow(py)
+            .open_collection(py, name, Some(self.session.clone()))
+    }
+
+    fn commit_transaction(&self) -> PyResult<()> {
+        self.session.commit()
+    }
+
+    fn abort_transaction(&self) -> PyResult<()> {
+        self.session.abort()
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __exit__(
+        &self,
+        exc_type: Option<&Bound<'_, PyAny>>,
+        _exc_value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<()> {
+        // A transaction already ended inside the block is left as it is
+        if self.session.check_active().is_err() {
+            return Ok(());
+        }
+        match exc_type {
+            None => self.session.commit(),
+            Some(_) => self.session.abort(),
+        }
+    }
+}
+
+impl PyTransaction {
+    pub fn new(session: Arc<TransactionSession>, database: Py<PyDatabase>) -> PyTransaction {
+        PyTransaction { session, database }
+    }
+}
@@ -0,0 +1,28 @@
+use polodb_core::Database;
+use std::path::{Path, PathBuf};
+
+/// A database directory for a unit test, removed when dropped. Declare it before the `Database`
+/// opened in it so the database is closed first.
+pub struct TestDir(PathBuf);
+
+impl TestDir {
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("mongo_emb_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        TestDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn open(&self) -> Database {
+        Database::open_path(&self.0).unwrap()
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
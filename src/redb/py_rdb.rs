@@ -1,3 +1,4 @@
+use crate::mongo::py_errors::ClosedError;
 use crate::redb::rdb::Rdb;
 use pyo3::exceptions::PyOSError;
 use pyo3::{IntoPyObjectExt, prelude::*};
@@ -7,7 +8,8 @@ use std::sync::{Arc, Mutex};
 
 #[pyclass]
 pub struct PyRdb {
-    inner: Arc<Mutex<Rdb>>,
+    // None once closed
+    inner: Arc<Mutex<Option<Rdb>>>,
 }
 
 #[pymethods]
@@ -16,15 +18,34 @@ impl PyRdb {
     fn new(dp: &str, tp: &str) -> PyResult<Self> {
         match Rdb::new(dp, tp) {
             Ok(db) => Ok(Self {
-                inner: Arc::new(Mutex::new(db)),
+                inner: Arc::new(Mutex::new(Some(db))),
             }),
             Err(e) => Err(PyOSError::new_err(e.to_string())),
         }
     }
 
+    /// Open a database held in memory only, with the same API. `save` writes it to a file.
+    #[staticmethod]
+    fn open_memory(tp: &str) -> PyResult<Self> {
+        match Rdb::memory(tp) {
+            Ok(db) => Ok(Self {
+                inner: Arc::new(Mutex::new(Some(db))),
+            }),
+            Err(e) => Err(PyOSError::new_err(e.to_string())),
+        }
+    }
+
+    /// Write a copy of every table to a new database file at `dp`.
+    pub fn save(&self, dp: &str) -> PyResult<()> {
+        let db = self.inner.lock().unwrap();
+        Self::open(&db)?
+            .save(dp)
+            .map_err(|e| PyOSError::new_err(format!("Error save db: {}", e)))
+    }
+
     pub fn write(&self, k: &str, v: &str) -> PyResult<String> {
         let db = self.inner.lock().unwrap();
-        let res = db.write(k, v);
+        let res = Self::open(&db)?.write(k, v);
         match res {
             Ok(res) => Ok("success".to_string()),
             Err(e) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
@@ -36,7 +57,7 @@ impl PyRdb {
 
     pub fn delete(&self, k: &str) -> PyResult<String> {
         let db = self.inner.lock().unwrap();
-        let res = db.delete(k);
+        let res = Self::open(&db)?.delete(k);
         match res {
             Ok(res) => Ok("success".to_string()),
             Err(e) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
@@ -48,7 +69,7 @@ impl PyRdb {
 
     pub fn read(&self, k: &str) -> PyResult<HashMap<String, String>> {
         let db = self.inner.lock().unwrap();
-        let res = db.read(k);
+        let res = Self::open(&db)?.read(k);
         match res {
             Ok(m) => Ok(m),
             Err(e) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
@@ -57,9 +78,16 @@ impl PyRdb {
             ))),
         }
     }
+
+    /// Close the database and release its file, later calls raise `ClosedError`. Closing twice
+    /// does nothing.
+    pub fn close(&self) {
+        self.inner.lock().unwrap().take();
+    }
+
     pub fn keys(&self) -> PyResult<Vec<String>> {
         let db = self.inner.lock().unwrap();
-        let res = db.keys();
+        let res = Self::open(&db)?.keys();
         match res {
             Ok(m) => Ok(m),
             Err(e) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
@@ -69,3 +97,10 @@ impl PyRdb {
         }
     }
 }
+
+impl PyRdb {
+    fn open(db: &Option<Rdb>) -> PyResult<&Rdb> {
+        db.as_ref()
+            .ok_or_else(|| ClosedError::new_err("Cannot use a closed Rdb"))
+    }
+}
@@ -1,7 +1,9 @@
 use crate::redb::tool::*;
 use anyhow::{Error, Result, bail};
-use redb::{Database, ReadableDatabase, TableDefinition};
+use redb::backends::InMemoryBackend;
+use redb::{Database, ReadableDatabase, TableDefinition, TableHandle};
 use std::collections::HashMap;
+use std::path::Path;
 
 pub const TAB0: &'static str = "log";
 pub struct Rdb {
@@ -16,6 +18,34 @@ impl Rdb {
         })
     }
 
+    /// A database held in memory only, gone once dropped unless `save` wrote it out.
+    pub fn memory(tname: &str) -> Result<Self> {
+        Ok(Self {
+            db: Database::builder().create_with_backend(InMemoryBackend::new())?,
+            tname: tname.to_string(),
+        })
+    }
+
+    /// Copy every table to a new database file at `dname`, which must not exist yet.
+    pub fn save(&self, dname: &str) -> Result<()> {
+        if Path::new(dname).exists() {
+            bail!("{dname} already exists");
+        }
+        let target = Database::create(dname)?;
+        let read_txn = self.db.begin_read()?;
+        let write_txn = target.begin_write()?;
+        for handle in read_txn.list_tables()? {
+            let tab: TableDefinition<&str, &str> = TableDefinition::new(handle.name());
+            let source = read_txn.open_table(tab)?;
+            let mut table = write_txn.open_table(tab)?;
+            for (k, v) in source.range::<&str>(..)?.flatten() {
+                table.insert(k.value(), v.value())?;
+            }
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
     fn write_value(&self, k: &str, v: &str, tname: &str) -> Result<()> {
         let tab: TableDefinition<&str, &str> = TableDefinition::new(tname);
         let write_txn = self.db.begin_write()?;
@@ -93,7 +123,7 @@ impl Rdb {
 
 #[test]
 fn test_db() -> Result<()> {
-    let rdb = Rdb::new("D://wq/temp/test.rdb", "test")?;
+    let rdb = Rdb::memory("test")?;
     rdb.write("rc", "localhost:9027")?;
     rdb.write("rust", "localhost:7778")?;
     rdb.write("deploy", "localhost:7777")?;
@@ -104,3 +134,19 @@ fn test_db() -> Result<()> {
     dbg!(a,aa, l);
     Ok(())
 }
+
+#[test]
+fn test_save() -> Result<()> {
+    let rdb = Rdb::memory("test")?;
+    rdb.write("rc", "localhost:9027")?;
+    let path = std::env::temp_dir().join(format!("mongo_emb-test-{}.rdb", std::process::id()));
+    let path = path.to_str().unwrap();
+    let _ = std::fs::remove_file(path);
+    rdb.save(path)?;
+    let saved = Rdb::new(path, "test")?;
+    assert_eq!(saved.read("rc")?, rdb.read("rc")?);
+    assert!(rdb.save(path).is_err());
+    drop(saved);
+    std::fs::remove_file(path)?;
+    Ok(())
+}
@@ -0,0 +1,22 @@
+import os
+import tempfile
+import unittest
+
+from mongo_emb import PyMongoEmb
+
+
+class DatabaseTestCase(unittest.TestCase):
+    """Opens `self.db` in a fresh temporary directory, without the TTL purge thread."""
+
+    def setUp(self):
+        self.tmp = tempfile.TemporaryDirectory()
+        self.path = os.path.join(self.tmp.name, "db")
+        self.db = PyMongoEmb(self.path, ttl_interval=None)
+
+    def tearDown(self):
+        self.db.close()
+        self.tmp.cleanup()
+
+    def reopen(self, **options):
+        self.db.close()
+        self.db = PyMongoEmb(self.path, **{"ttl_interval": None, **options})
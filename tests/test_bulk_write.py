@@ -0,0 +1,76 @@
+import unittest
+
+from mongo_emb import (BulkWriteError, BulkWriteResult, DeleteMany, DeleteOne, InsertOne,
+                       ReplaceOne, UpdateMany, UpdateOne)
+
+from support import DatabaseTestCase
+
+
+class BulkWriteTest(DatabaseTestCase):
+
+    def setUp(self):
+        super().setUp()
+        self.col = self.db["bulk"]
+        self.col.insert_many([{"_id": n, "n": n} for n in range(3)])
+
+    def test_counts(self):
+        result = self.col.bulk_write([
+            InsertOne({"_id": 10, "n": 10}),
+            UpdateOne({"_id": 0}, {"$set": {"n": 5}}),
+            UpdateMany({"n": {"$gte": 1}}, {"$inc": {"n": 1}}),
+            ReplaceOne({"_id": 20}, {"n": 20}, upsert=True),
+            DeleteOne({"_id": 1}),
+            DeleteMany({"n": {"$gt": 100}}),
+        ])
+        self.assertIsInstance(result, BulkWriteResult)
+        self.assertEqual((result.inserted_count, result.matched_count, result.modified_count,
+                          result.deleted_count, result.upserted_count),
+                         (1, 5, 5, 1, 1))
+        self.assertEqual(result.upserted_ids, {3: 20})
+        self.assertEqual(self.col.find_one({"_id": 0})["n"], 6)
+
+    def test_ordered_stops_at_the_first_failure(self):
+        with self.assertRaises(BulkWriteError) as raised:
+            self.col.bulk_write([
+                InsertOne({"_id": 10}),
+                UpdateMany({}, {"$inc": {"n": 1}}),
+                InsertOne({"_id": 1}),
+                InsertOne({"_id": 11}),
+            ])
+        details = raised.exception.details
+        self.assertEqual([error["index"] for error in details["writeErrors"]], [2])
+        self.assertIn("duplicate key", details["writeErrors"][0]["errmsg"])
+        self.assertIsInstance(details["writeErrors"][0]["op"], InsertOne)
+        self.assertEqual((details["nInserted"], details["nMatched"], details["nModified"]),
+                         (1, 4, 4))
+        self.assertIsNotNone(self.col.find_one({"_id": 10}))
+        self.assertIsNone(self.col.find_one({"_id": 11}))
+        self.assertEqual(self.col.find_one({"_id": 2})["n"], 3)
+
+    def test_unordered_carries_on(self):
+        self.col.insert_one({"_id": "text", "n": "text"})
+        with self.assertRaises(BulkWriteError) as raised:
+            self.col.bulk_write([
+                InsertOne({"_id": 0}),
+                UpdateMany({}, {"$inc": {"n": 1}}),
+                UpdateOne({"_id": 5}, {"$set": {"n": 5}}, upsert=True),
+                InsertOne({"_id": 2}),
+            ], ordered=False)
+        details = raised.exception.details
+        self.assertEqual([error["index"] for error in details["writeErrors"]], [0, 1, 3])
+        self.assertEqual(details["nUpserted"], 1)
+        self.assertEqual(details["upserted"], [{"index": 2, "_id": 5}])
+        # The failed update_many changed some documents before failing, all are restored
+        self.assertEqual([doc["n"] for doc in self.col.find({}, sort="_id")][:3], [0, 1, 2])
+        self.assertEqual(self.col.count_documents({}), 5)
+
+    def test_invalid_requests_write_nothing(self):
+        with self.assertRaises(TypeError):
+            self.col.bulk_write([InsertOne({"_id": 10}), {"insertOne": {}}])
+        with self.assertRaises(ValueError):
+            self.col.bulk_write([InsertOne({"_id": 10}), UpdateOne({}, {"n": 1})])
+        self.assertIsNone(self.col.find_one({"_id": 10}))
+
+
+if __name__ == "__main__":
+    unittest.main()
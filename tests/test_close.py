@@ -0,0 +1,75 @@
+import os
+import tempfile
+import unittest
+
+from mongo_emb import ClosedError, PyMongoEmb, PyRedb
+
+
+class CloseTest(unittest.TestCase):
+    def setUp(self):
+        self.tmp = tempfile.TemporaryDirectory()
+        self.path = os.path.join(self.tmp.name, "db")
+
+    def tearDown(self):
+        self.tmp.cleanup()
+
+    def test_context_manager_releases_the_path(self):
+        with PyMongoEmb(self.path) as db:
+            db.collection("items").insert_one({"_id": 1})
+        # The path is free again, the data was flushed
+        with PyMongoEmb(self.path) as db:
+            self.assertEqual(db.collection("items").find_one({"_id": 1}), {"_id": 1})
+
+    def test_closed_database_raises(self):
+        db = PyMongoEmb(self.path)
+        items = db.collection("items")
+        items.insert_one({"_id": 1})
+        db.close()
+        db.close()
+        with self.assertRaises(ClosedError):
+            db.list_collection_names()
+        with self.assertRaises(ClosedError):
+            items.insert_one({"_id": 2})
+        with self.assertRaises(ClosedError):
+            items.find_one({})
+
+    def test_close_ends_cursors_and_transactions(self):
+        db = PyMongoEmb(self.path)
+        items = db.collection("items")
+        items.insert_many([{"_id": i} for i in range(3)])
+        cursor = items.find({}).batch_size(1)
+        next(cursor)
+        txn = db.start_transaction()
+        txn.collection("items").insert_one({"_id": 9})
+        db.close()
+        with self.assertRaises(ClosedError):
+            next(cursor)
+        with self.assertRaises(ClosedError):
+            txn.commit_transaction()
+        with PyMongoEmb(self.path) as db:
+            # The transaction was rolled back
+            self.assertEqual(db.collection("items").count_documents({}), 3)
+
+    def test_closed_collection_handle(self):
+        with PyMongoEmb(self.path) as db:
+            items = db.collection("items")
+            items.close()
+            with self.assertRaises(ClosedError):
+                items.insert_one({"_id": 1})
+            # Other handles and the database stay open
+            db.collection("items").insert_one({"_id": 1})
+            self.assertEqual(db.collection("items").count_documents({}), 1)
+
+    def test_redb_close(self):
+        path = os.path.join(self.tmp.name, "kv.redb")
+        with PyRedb(path, "table") as rdb:
+            rdb.write("k", "v")
+        with self.assertRaises(ClosedError):
+            rdb.read("k")
+        rdb.close()
+        with PyRedb(path, "table") as rdb:
+            self.assertEqual(rdb.read("k")["value"], "v")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,62 @@
+import enum
+import unittest
+
+from support import DatabaseTestCase
+
+
+class Color(enum.Enum):
+    RED = 1
+    BLUE = 2
+
+
+class Point:
+
+    def __init__(self, x, y):
+        self.x = x
+        self.y = y
+
+    def __eq__(self, other):
+        return isinstance(other, Point) and (self.x, self.y) == (other.x, other.y)
+
+
+def encode_point(point):
+    return f"{point.x},{point.y}".encode()
+
+
+def decode_point(data):
+    return Point(*map(int, data.decode().split(",")))
+
+
+class CodecsTest(DatabaseTestCase):
+
+    def test_tagged_codec_round_trip(self):
+        self.db.register_type(Color, lambda c: c.name, lambda name: Color[name], tag="Color")
+        col = self.db["shapes"]
+        col.insert_one({"color": Color.RED})
+        self.assertEqual(col.find_one({})["color"], Color.RED)
+        self.assertEqual(col.count_documents({"color.__value__": "RED"}), 1)
+
+    def test_subtype_codec_on_a_collection(self):
+        col = self.db["shapes"]
+        col.register_type(Point, encode_point, decode_point, subtype=0x80)
+        col.insert_one({"at": Point(1, 2)})
+        self.assertEqual(col.find_one({})["at"], Point(1, 2))
+        self.assertEqual(col.count_documents({"at": Point(1, 2)}), 1)
+        with self.assertRaises(TypeError):
+            self.db["other"].insert_one({"at": Point(1, 2)})
+
+    def test_plain_codec_cannot_be_read_back(self):
+        self.db.register_type(Point, lambda p: [p.x, p.y])
+        col = self.db["shapes"]
+        col.insert_one({"at": Point(3, 4)})
+        self.assertEqual(col.find_one({})["at"], [3, 4])
+
+    def test_invalid_registrations(self):
+        with self.assertRaises(ValueError):
+            self.db.register_type(Point, encode_point, decode_point, subtype=5)
+        with self.assertRaises(ValueError):
+            self.db.register_type(Point, encode_point, decode_point)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,61 @@
+import unittest
+
+from mongo_emb import PyMongoEmb
+
+
+class CollectionsTest(unittest.TestCase):
+    def setUp(self):
+        self.db = PyMongoEmb.open_temporary(ttl_interval=None)
+
+    def tearDown(self):
+        self.db.close()
+
+    def test_drop(self):
+        self.db["a"].insert_one({"_id": 1})
+        self.db["a"].create_index("x")
+        self.db["b"].insert_one({"_id": 1})
+        self.db.drop_collection("a")
+        self.db["b"].drop()
+        self.assertEqual(self.db.list_collection_names(), [])
+        # Dropped with its indexes
+        self.assertEqual([index["name"] for index in self.db["a"].list_indexes()], ["_id_"])
+        self.db.drop_collection("missing")
+
+    def test_rename_moves_documents_and_indexes(self):
+        self.db["events"].insert_many([{"_id": 1, "kind": "a"}, {"_id": 2, "kind": "b"}])
+        self.db["events"].create_index("kind", unique=True)
+        self.db.rename_collection("events", "archive")
+        self.assertEqual(self.db.list_collection_names(), ["archive"])
+        archive = self.db["archive"]
+        self.assertEqual(archive.count_documents({}), 2)
+        self.assertEqual([index["name"] for index in archive.list_indexes()], ["_id_", "kind_1"])
+        self.assertEqual([index["name"] for index in self.db["events"].list_indexes()], ["_id_"])
+
+    def test_rename_an_empty_collection(self):
+        self.db["empty"].insert_one({"_id": 1})
+        self.db["empty"].delete_many({})
+        self.db.rename_collection("empty", "still_empty")
+        self.assertEqual(self.db.list_collection_names(), ["still_empty"])
+
+    def test_rename_errors(self):
+        self.db["a"].insert_one({"_id": 1})
+        self.db["b"].insert_one({"_id": 2})
+        with self.assertRaises(ValueError):
+            self.db.rename_collection("a", "b")
+        with self.assertRaises(ValueError):
+            self.db.rename_collection("missing", "c")
+        with self.assertRaises(ValueError):
+            self.db.rename_collection("a", "a")
+        self.assertEqual(sorted(self.db.list_collection_names()), ["a", "b"])
+
+    def test_rename_replaces_the_target(self):
+        self.db["staging"].insert_one({"_id": 1, "v": "new"})
+        self.db["live"].insert_many([{"_id": 1, "v": "old"}, {"_id": 2, "v": "old"}])
+        self.db["live"].create_index("v")
+        self.db.rename_collection("staging", "live", drop_target=True)
+        self.assertEqual(self.db["live"].find({}).to_list(), [{"_id": 1, "v": "new"}])
+        self.assertEqual([index["name"] for index in self.db["live"].list_indexes()], ["_id_"])
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,79 @@
+import unittest
+
+from support import DatabaseTestCase
+
+
+class CountTest(DatabaseTestCase):
+
+    def setUp(self):
+        super().setUp()
+        self.col = self.db["count"]
+        self.col.insert_many([{"_id": n, "even": n % 2 == 0} for n in range(10)])
+
+    def test_count_documents(self):
+        self.assertEqual(self.col.count_documents({}), 10)
+        self.assertEqual(self.col.count_documents({"even": True}), 5)
+        self.assertEqual(self.col.count_documents({"_id": {"$gte": 8}}), 2)
+        self.assertEqual(self.col.count_documents({"missing": 1}), 0)
+
+    def test_skip_and_limit(self):
+        self.assertEqual(self.col.count_documents({"even": True}, skip=2), 3)
+        self.assertEqual(self.col.count_documents({"even": True}, skip=7), 0)
+        self.assertEqual(self.col.count_documents({}, limit=4), 4)
+        self.assertEqual(self.col.count_documents({}, skip=8, limit=4), 2)
+        self.assertEqual(self.col.count_documents({}, limit=0), 10)
+
+    def test_estimated_document_count(self):
+        self.assertEqual(self.col.estimated_document_count(), 10)
+        self.assertEqual(self.col.len(), 10)
+        self.col.delete_many({"even": True})
+        self.assertEqual(self.col.estimated_document_count(), 5)
+        self.assertEqual(self.db["empty"].estimated_document_count(), 0)
+
+    def test_estimated_count_follows_writes(self):
+        self.assertEqual(self.col.estimated_document_count(), 10)
+        self.col.insert_one({"_id": 10})
+        self.col.update_many({}, {"$set": {"seen": True}})
+        self.col.update_one({"_id": 11}, {"$set": {"seen": True}}, upsert=True)
+        self.col.replace_one({"_id": 0}, {"replaced": True})
+        self.col.find_one_and_delete({"_id": 1})
+        with self.assertRaises(Exception):
+            self.col.insert_many([{"_id": 20}, {"_id": 2}])
+        self.assertEqual(self.col.estimated_document_count(), 11)
+        self.assertEqual(self.col.estimated_document_count(), self.col.count_documents({}))
+
+    def test_estimated_count_in_a_transaction(self):
+        self.assertEqual(self.col.estimated_document_count(), 10)
+        with self.db.start_transaction() as txn:
+            col = txn.collection("count")
+            col.insert_many([{"_id": 10}, {"_id": 11}])
+            col.delete_one({"_id": 0})
+            self.assertEqual(col.estimated_document_count(), 11)
+            self.assertEqual(self.col.estimated_document_count(), 10)
+        self.assertEqual(self.col.estimated_document_count(), 11)
+        with self.assertRaises(RuntimeError):
+            with self.db.start_transaction() as txn:
+                txn.collection("count").delete_many({})
+                raise RuntimeError("abort")
+        self.assertEqual(self.col.estimated_document_count(), 11)
+
+    def test_estimated_count_after_drop_and_rename(self):
+        self.assertEqual(self.col.estimated_document_count(), 10)
+        self.db.rename_collection("count", "renamed")
+        self.assertEqual(self.col.estimated_document_count(), 0)
+        self.assertEqual(self.db["renamed"].estimated_document_count(), 10)
+        self.db["renamed"].drop()
+        self.assertEqual(self.db["renamed"].estimated_document_count(), 0)
+
+    def test_estimated_count_after_reopen(self):
+        self.col.delete_one({"_id": 0})
+        self.reopen()
+        self.assertEqual(self.db["count"].estimated_document_count(), 9)
+
+    def test_invalid_filter(self):
+        with self.assertRaises(TypeError):
+            self.col.count_documents([1])
+
+
+if __name__ == "__main__":
+    unittest.main()
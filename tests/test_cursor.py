@@ -0,0 +1,54 @@
+import unittest
+
+from mongo_emb import ClosedError, Cursor
+
+from support import DatabaseTestCase
+
+
+class CursorTest(DatabaseTestCase):
+
+    def setUp(self):
+        super().setUp()
+        self.col = self.db["cursor"]
+        self.col.insert_many([{"_id": n, "n": n} for n in range(250)])
+
+    def test_iteration(self):
+        cursor = self.col.find({})
+        self.assertIsInstance(cursor, Cursor)
+        self.assertEqual([doc["_id"] for doc in cursor], list(range(250)))
+        self.assertFalse(cursor.alive)
+        with self.assertRaises(StopIteration):
+            next(cursor)
+
+    def test_batch_size(self):
+        for cursor in (self.col.find({}, batch_size=7), self.col.find({}).batch_size(7),
+                       self.col.find({}, sort="n", batch_size=7)):
+            self.assertEqual(next(cursor)["_id"], 0)
+            self.assertEqual(len(cursor.to_list()), 249)
+
+    def test_to_list(self):
+        cursor = self.col.find({"n": {"$lt": 10}})
+        self.assertEqual([doc["_id"] for doc in cursor.to_list(3)], [0, 1, 2])
+        self.assertEqual(len(cursor.to_list()), 7)
+        self.assertEqual(cursor.to_list(), [])
+
+    def test_aggregate(self):
+        cursor = self.col.aggregate([{"$match": {"n": {"$gte": 248}}}])
+        self.assertEqual([doc["_id"] for doc in cursor], [248, 249])
+
+    def test_close(self):
+        with self.col.find({}) as cursor:
+            next(cursor)
+        self.assertFalse(cursor.alive)
+        self.assertEqual(list(cursor), [])
+
+    def test_closing_the_database_ends_cursors(self):
+        cursor = self.col.find({}, batch_size=10)
+        self.assertEqual(len(cursor.to_list(10)), 10)
+        self.db.close()
+        with self.assertRaises(ClosedError):
+            cursor.to_list()
+
+
+if __name__ == "__main__":
+    unittest.main()
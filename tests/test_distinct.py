@@ -0,0 +1,33 @@
+import unittest
+
+from support import DatabaseTestCase
+
+
+class DistinctTest(DatabaseTestCase):
+
+    def setUp(self):
+        super().setUp()
+        self.col = self.db["distinct"]
+        self.col.insert_many([
+            {"city": "Paris", "tags": ["a", "b"], "address": {"zip": 75001}},
+            {"city": "Lyon", "tags": ["b", "c"], "address": {"zip": 69001}},
+            {"city": "Paris", "tags": [], "address": {"zip": 75001.0}},
+            {"city": None, "items": [{"sku": 1}, {"sku": 2}]},
+        ])
+
+    def test_distinct(self):
+        self.assertEqual(self.col.distinct("city"), [None, "Lyon", "Paris"])
+        self.assertEqual(self.col.distinct("missing"), [])
+
+    def test_arrays_and_dotted_paths(self):
+        self.assertEqual(self.col.distinct("tags"), ["a", "b", "c"])
+        self.assertEqual(self.col.distinct("address.zip"), [69001, 75001])
+        self.assertEqual(self.col.distinct("items.sku"), [1, 2])
+
+    def test_filter(self):
+        self.assertEqual(self.col.distinct("tags", {"city": "Lyon"}), ["b", "c"])
+        self.assertEqual(self.col.distinct("city", {"address.zip": 75001}), ["Paris"])
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,68 @@
+import datetime
+import decimal
+import json
+import unittest
+
+from mongo_emb import (CodecOptions, Int32, ObjectId, from_extended_json, to_extended_json)
+
+from support import DatabaseTestCase
+
+
+class Point:
+
+    def __init__(self, x, y):
+        self.x = x
+        self.y = y
+
+    def __eq__(self, other):
+        return isinstance(other, Point) and (self.x, self.y) == (other.x, other.y)
+
+
+class ExtendedJsonTest(DatabaseTestCase):
+
+    def setUp(self):
+        super().setUp()
+        self.col = self.db["json"]
+
+    def test_round_trip(self):
+        doc = {"_id": ObjectId(), "n": 5, "d": decimal.Decimal("1.10"), "at": datetime.datetime(2024, 1, 1),
+               "nested": [b"\x01", None, 1.5]}
+        self.assertEqual(from_extended_json(to_extended_json(doc, canonical=True)), doc)
+
+    def test_relaxed_and_canonical(self):
+        self.assertEqual(json.loads(to_extended_json({"n": 5})), {"n": 5})
+        self.assertEqual(json.loads(to_extended_json({"n": 5}, canonical=True)),
+                         {"n": {"$numberLong": "5"}})
+
+    def test_tz_aware(self):
+        text = '{"at": {"$date": "2024-01-01T00:00:00Z"}}'
+        self.assertIsNone(from_extended_json(text)["at"].tzinfo)
+        self.assertEqual(from_extended_json(text, tz_aware=True)["at"],
+                         datetime.datetime(2024, 1, 1, tzinfo=datetime.timezone.utc))
+        tokyo = datetime.timezone(datetime.timedelta(hours=9))
+        self.assertEqual(from_extended_json(text, tzinfo=tokyo)["at"].hour, 9)
+
+    def test_codec_options(self):
+        options = CodecOptions(tz_aware=True, strict=True)
+        self.assertEqual(from_extended_json('{"n": {"$numberInt": "5"}}', codec_options=options),
+                         {"n": Int32(5)})
+        with self.assertRaises(TypeError):
+            to_extended_json({"t": (1, 2)}, codec_options=options)
+        with self.assertRaises(ValueError):
+            from_extended_json("{}", tz_aware=True, codec_options=options)
+
+    def test_database_codec_options(self):
+        self.col.register_type(Point, lambda p: f"{p.x},{p.y}".encode(),
+                               lambda b: Point(*map(int, b.decode().split(","))), subtype=0x80)
+        text = to_extended_json({"at": Point(1, 2)}, codec_options=self.col.codec_options)
+        self.assertEqual(from_extended_json(text, codec_options=self.col.codec_options),
+                         {"at": Point(1, 2)})
+        with self.assertRaises(TypeError):
+            to_extended_json({"at": Point(1, 2)}, codec_options=self.db.codec_options)
+        self.reopen(tz_aware=True)
+        self.assertTrue(self.db.codec_options.tz_aware)
+        self.assertNotIn("codec_options", self.db.list_collection_names())
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,52 @@
+import unittest
+
+from support import DatabaseTestCase
+
+
+class FindTest(DatabaseTestCase):
+
+    def setUp(self):
+        super().setUp()
+        self.col = self.db["find"]
+        self.col.insert_many([
+            {"_id": n, "group": n % 2, "score": 10 - n, "info": {"name": f"n{n}", "tag": "t"}}
+            for n in range(6)
+        ])
+
+    def ids(self, docs):
+        return [doc["_id"] for doc in docs]
+
+    def test_sort(self):
+        self.assertEqual(self.ids(self.col.find({}, sort="score")), [5, 4, 3, 2, 1, 0])
+        self.assertEqual(self.ids(self.col.find({}, sort=[("group", 1), ("score", -1)])),
+                         [0, 2, 4, 1, 3, 5])
+        self.assertEqual(self.ids(self.col.find({}, sort={"info.name": -1})), [5, 4, 3, 2, 1, 0])
+        with self.assertRaises(ValueError):
+            self.col.find({}, sort={"score": 2})
+
+    def test_skip_and_limit(self):
+        self.assertEqual(self.ids(self.col.find({}, sort="_id", skip=1, limit=2)), [1, 2])
+        self.assertEqual(self.ids(self.col.find({"group": 1}, skip=1)), [3, 5])
+        self.assertEqual(self.ids(self.col.find({}, limit=0)), list(range(6)))
+        self.assertEqual(self.ids(self.col.find({}, skip=10)), [])
+
+    def test_projection(self):
+        self.assertEqual(list(self.col.find({"_id": 1}, projection={"info.name": 1})),
+                         [{"_id": 1, "info": {"name": "n1"}}])
+        self.assertEqual(list(self.col.find({"_id": 1}, projection=["score"])),
+                         [{"_id": 1, "score": 9}])
+        self.assertEqual(list(self.col.find({"_id": 1}, projection={"info": 0, "_id": 0})),
+                         [{"group": 1, "score": 9}])
+        with self.assertRaises(ValueError):
+            self.col.find({}, projection={"score": 1, "group": 0})
+
+    def test_find_one_options(self):
+        self.assertEqual(self.col.find_one({}, sort=[("score", -1)])["_id"], 0)
+        self.assertEqual(self.col.find_one({}, sort="score", skip=2)["_id"], 3)
+        self.assertEqual(self.col.find_one({"_id": 2}, projection={"score": 1}),
+                         {"_id": 2, "score": 8})
+        self.assertIsNone(self.col.find_one({"group": 0}, skip=3))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,72 @@
+import threading
+import unittest
+
+from mongo_emb import ReturnDocument
+
+from support import DatabaseTestCase
+
+
+class FindOneAndTest(DatabaseTestCase):
+
+    def setUp(self):
+        super().setUp()
+        self.col = self.db["jobs"]
+        self.col.insert_many([{"_id": n, "priority": n % 3, "state": "new"} for n in range(6)])
+
+    def test_update_returns_before_or_after(self):
+        before = self.col.find_one_and_update({"_id": 1}, {"$set": {"state": "taken"}})
+        self.assertEqual(before["state"], "new")
+        after = self.col.find_one_and_update({"_id": 2}, {"$set": {"state": "taken"}},
+                                             return_document=ReturnDocument.AFTER)
+        self.assertEqual(after["state"], "taken")
+        self.assertEqual(self.col.count_documents({"state": "taken"}), 2)
+        self.assertIsNone(self.col.find_one_and_update({"_id": 99}, {"$set": {"state": "x"}}))
+
+    def test_sort_and_projection(self):
+        doc = self.col.find_one_and_update({"state": "new"}, {"$set": {"state": "taken"}},
+                                           sort=[("priority", -1), ("_id", -1)],
+                                           projection={"state": 1}, return_document="after")
+        self.assertEqual(doc, {"_id": 5, "state": "taken"})
+
+    def test_upsert(self):
+        doc = self.col.find_one_and_update({"_id": 10}, {"$set": {"state": "new"}}, upsert=True,
+                                           return_document=ReturnDocument.AFTER)
+        self.assertEqual(doc, {"_id": 10, "state": "new"})
+        self.assertIsNone(self.col.find_one_and_replace({"_id": 11}, {"state": "x"}, upsert=True))
+        self.assertEqual(self.col.find_one({"_id": 11}), {"_id": 11, "state": "x"})
+
+    def test_replace_keeps_id(self):
+        doc = self.col.find_one_and_replace({"_id": 3}, {"state": "done"},
+                                            return_document=ReturnDocument.AFTER)
+        self.assertEqual(doc, {"_id": 3, "state": "done"})
+        with self.assertRaises(ValueError):
+            self.col.find_one_and_replace({"_id": 3}, {"$set": {"state": "x"}})
+        with self.assertRaises(ValueError):
+            self.col.find_one_and_replace({"_id": 3}, {"_id": 4})
+
+    def test_delete(self):
+        doc = self.col.find_one_and_delete({"priority": 2}, sort={"_id": 1})
+        self.assertEqual(doc["_id"], 2)
+        self.assertIsNone(self.col.find_one({"_id": 2}))
+        self.assertIsNone(self.col.find_one_and_delete({"_id": 2}))
+
+    def test_no_job_is_claimed_twice(self):
+        claimed = []
+
+        def worker():
+            while True:
+                job = self.col.find_one_and_update({"state": "new"}, {"$set": {"state": "taken"}})
+                if job is None:
+                    return
+                claimed.append(job["_id"])
+
+        threads = [threading.Thread(target=worker) for _ in range(4)]
+        for thread in threads:
+            thread.start()
+        for thread in threads:
+            thread.join()
+        self.assertEqual(sorted(claimed), list(range(6)))
+
+
+if __name__ == "__main__":
+    unittest.main()
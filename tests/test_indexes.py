@@ -0,0 +1,157 @@
+import unittest
+
+from mongo_emb import (BulkWriteError, DuplicateKeyError, IndexModel, InsertOne, UpdateOne)
+
+from support import DatabaseTestCase
+
+
+class IndexesTest(DatabaseTestCase):
+
+    def setUp(self):
+        super().setUp()
+        self.col = self.db["users"]
+
+    def names(self):
+        return [index["name"] for index in self.col.list_indexes()]
+
+    def test_create_list_and_drop(self):
+        self.assertEqual(self.col.create_index("email", unique=True), "email_1")
+        self.assertEqual(self.col.create_indexes([IndexModel([("address.city", 1)]),
+                                                  IndexModel("age", name="by_age")]),
+                         ["address.city_1".replace(".", "_"), "by_age"])
+        self.assertEqual(self.names(), ["_id_", "email_1", "address_city_1", "by_age"])
+        self.col.drop_index("by_age")
+        self.col.drop_index([("address.city", 1)])
+        self.assertEqual(self.names(), ["_id_", "email_1"])
+        with self.assertRaises(ValueError):
+            self.col.drop_index("_id_")
+        with self.assertRaises(ValueError):
+            self.col.drop_index("missing")
+        self.col.drop_indexes()
+        self.assertEqual(self.names(), ["_id_"])
+
+    def test_indexes_survive_reopen(self):
+        self.col.create_index("email", unique=True)
+        self.reopen()
+        self.col = self.db["users"]
+        self.assertEqual(self.names(), ["_id_", "email_1"])
+        self.col.insert_one({"email": "a@x"})
+        with self.assertRaises(DuplicateKeyError):
+            self.col.insert_one({"email": "a@x"})
+
+    def test_invalid_definitions(self):
+        with self.assertRaises(ValueError):
+            self.col.create_index([("a", 1), ("b", 1)])
+        with self.assertRaises(ValueError):
+            self.col.create_index([("a", -1)])
+        with self.assertRaises(ValueError):
+            self.col.create_index("a", name="_id_")
+        self.col.create_index("a")
+        self.col.create_index("a")
+        with self.assertRaises(ValueError):
+            self.col.create_index("a", unique=True)
+
+    def test_unique_index(self):
+        self.col.create_index("email", unique=True)
+        self.col.insert_many([{"_id": 1, "email": "a@x"}, {"_id": 2, "email": "b@x"}, {"_id": 3}])
+        with self.assertRaises(DuplicateKeyError):
+            self.col.insert_one({"email": "a@x"})
+        with self.assertRaises(DuplicateKeyError):
+            self.col.update_one({"_id": 2}, {"$set": {"email": "a@x"}})
+        with self.assertRaises(DuplicateKeyError):
+            self.col.insert_many([{"email": "c@x"}, {"email": "c@x"}])
+        # A missing value counts as null, and the document with _id 3 already has it
+        with self.assertRaises(DuplicateKeyError):
+            self.col.insert_one({"email": None})
+        with self.assertRaises(DuplicateKeyError):
+            self.col.insert_one({"name": "b"})
+        # Updating an indexed document works
+        self.col.update_one({"_id": 1}, {"$set": {"name": "a"}})
+        self.col.delete_one({"_id": 2})
+        self.col.insert_one({"email": "b@x"})
+        self.col.update_one({"_id": 3}, {"$set": {"email": None}})
+        self.assertEqual(self.col.count_documents({"email": "c@x"}), 0)
+
+    def test_sparse_unique_index(self):
+        self.col.create_index("email", unique=True, sparse=True)
+        self.assertEqual(self.col.list_indexes()[1],
+                         {"name": "email_1", "key": {"email": 1}, "unique": True, "sparse": True})
+        self.col.insert_many([{"_id": 1}, {"_id": 2}, {"_id": 3, "email": None}])
+        # Explicit nulls are still indexed
+        with self.assertRaises(DuplicateKeyError):
+            self.col.insert_one({"email": None})
+        self.assertEqual(self.col.count_documents({}), 3)
+
+    def test_unique_index_on_missing_values(self):
+        self.col.insert_many([{"_id": 1}, {"_id": 2, "email": None}])
+        with self.assertRaises(DuplicateKeyError):
+            self.col.create_index("email", unique=True)
+        self.col.create_indexes([IndexModel("email", unique=True, sparse=True)])
+        self.assertEqual(self.names(), ["_id_", "email_1"])
+
+    def test_unique_index_refuses_what_it_cannot_check(self):
+        self.col.create_index("address.city", unique=True)
+        with self.assertRaises(ValueError) as raised:
+            self.col.insert_one({"_id": 1, "address": [{"city": "Oslo"}]})
+        self.assertIn("_id 1 holds values inside an array in 'address.city'",
+                      str(raised.exception))
+        self.col.create_index("owner", unique=True, sparse=True)
+        with self.assertRaises(ValueError) as raised:
+            self.col.insert_one({"_id": 2, "owner": {"name": "a"}})
+        self.assertIn("_id 2 holds an embedded document in 'owner'", str(raised.exception))
+        self.assertEqual(self.col.count_documents({}), 0)
+
+    def test_unique_index_on_duplicate_data(self):
+        self.col.insert_many([{"email": "a@x"}, {"email": "a@x"}])
+        with self.assertRaises(DuplicateKeyError):
+            self.col.create_index("email", unique=True)
+        self.assertEqual(self.names(), ["_id_"])
+
+    def test_id_is_unique(self):
+        self.col.insert_one({"_id": 1, "v": 1})
+        with self.assertRaises(DuplicateKeyError):
+            self.col.insert_one({"_id": 1, "v": 2})
+        with self.assertRaises(DuplicateKeyError):
+            self.col.insert_many([{"_id": 2}, {"_id": 2}])
+        with self.assertRaises(ValueError):
+            self.col.insert_one({"_id": [1]})
+        with self.assertRaises(BulkWriteError):
+            self.col.bulk_write([InsertOne({"_id": 1})])
+        with self.assertRaises(DuplicateKeyError):
+            self.col.update_one({"_id": 1, "v": 5}, {"$set": {"w": 1}}, upsert=True)
+        self.assertEqual(self.col.find_one({"_id": 1}), {"_id": 1, "v": 1})
+        self.assertEqual(self.col.count_documents({}), 1)
+
+    def test_arrays_cannot_be_indexed(self):
+        self.col.insert_one({"_id": 1, "tags": ["a", "b"]})
+        with self.assertRaises(ValueError) as raised:
+            self.col.create_index("tags")
+        self.assertIn("_id 1 holds an array in 'tags'", str(raised.exception))
+        self.assertEqual(self.names(), ["_id_"])
+        self.col.delete_many({})
+
+        self.col.create_index("tags")
+        with self.assertRaises(ValueError) as raised:
+            self.col.insert_one({"tags": ["a"]})
+        self.assertIn("holds an array in 'tags'", str(raised.exception))
+        self.col.insert_one({"_id": 2, "tags": "a"})
+        with self.assertRaises(ValueError):
+            self.col.update_one({"_id": 2}, {"$set": {"tags": ["b"]}})
+        self.assertEqual(self.col.find({}).to_list(), [{"_id": 2, "tags": "a"}])
+
+    def test_arrays_in_bulk_write(self):
+        self.col.create_index("tags")
+        with self.assertRaises(BulkWriteError) as raised:
+            self.col.bulk_write([InsertOne({"_id": 1, "tags": ["a"]}),
+                                 InsertOne({"_id": 2, "tags": "b"})], ordered=False)
+        self.assertEqual([error["index"] for error in raised.exception.details["writeErrors"]], [0])
+        self.assertEqual(self.col.find({}).to_list(), [{"_id": 2, "tags": "b"}])
+        # polodb cannot take back an update that stored an array, the whole bulk write fails
+        with self.assertRaises(ValueError):
+            self.col.bulk_write([InsertOne({"_id": 3, "tags": "c"}),
+                                 UpdateOne({"_id": 2}, {"$set": {"tags": ["c"]}})], ordered=False)
+        self.assertEqual(self.col.find({}).to_list(), [{"_id": 2, "tags": "b"}])
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,53 @@
+import unittest
+
+from mongo_emb import DuplicateKeyError, RawDocument
+
+from support import DatabaseTestCase
+
+
+class RawTest(DatabaseTestCase):
+
+    def setUp(self):
+        super().setUp()
+        self.col = self.db["raw"]
+
+    def test_raw_round_trip(self):
+        self.col.insert_many([{"_id": n, "nested": {"n": n}} for n in range(3)])
+        blobs = self.col.find_raw({})
+        self.assertEqual(len(blobs), 3)
+        copy = self.db["copy"]
+        self.assertEqual(copy.insert_raw_many(blobs).inserted_ids, [0, 1, 2])
+        self.assertEqual(copy.find_one({"_id": 2}), {"_id": 2, "nested": {"n": 2}})
+
+    def test_raw_document_reads_fields_lazily(self):
+        self.col.insert_one({"_id": 1, "name": "a", "nested": {"field": [1, 2]}})
+        doc = RawDocument(self.col.find_raw({})[0])
+        self.assertEqual(doc["name"], "a")
+        self.assertIsInstance(doc["nested"], RawDocument)
+        self.assertEqual(doc["nested"]["field"], [1, 2])
+        self.assertEqual(doc.to_dict(), {"_id": 1, "name": "a", "nested": {"field": [1, 2]}})
+        self.assertEqual(list(doc), ["_id", "name", "nested"])
+        with self.assertRaises(KeyError):
+            doc["missing"]
+
+    def test_duplicate_id_is_refused(self):
+        self.col.insert_one({"_id": 1, "v": "first"})
+        blob = self.col.find_raw({})[0]
+        new = self.db["other"]
+        new.insert_one({"_id": 2})
+        with self.assertRaises(DuplicateKeyError):
+            self.col.insert_raw_many([blob])
+        with self.assertRaises(DuplicateKeyError):
+            new.insert_raw_many([new.find_raw({})[0], blob, blob])
+        self.assertEqual(self.col.find_one({"_id": 1})["v"], "first")
+        self.assertEqual(new.count_documents({}), 1)
+
+    def test_invalid_bson_is_refused(self):
+        with self.assertRaises(ValueError):
+            self.col.insert_raw_many([b"\x05\x00\x00"])
+        with self.assertRaises(TypeError):
+            self.col.insert_raw_many(["not bytes"])
+
+
+if __name__ == "__main__":
+    unittest.main()
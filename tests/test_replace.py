@@ -0,0 +1,49 @@
+import unittest
+
+from mongo_emb import ObjectId
+
+from support import DatabaseTestCase
+
+
+class ReplaceTest(DatabaseTestCase):
+
+    def setUp(self):
+        super().setUp()
+        self.col = self.db["replace"]
+        self.col.insert_many([{"_id": 1, "name": "a", "n": 1}, {"_id": 2, "name": "b", "n": 2}])
+
+    def test_replace_keeps_id(self):
+        result = self.col.replace_one({"name": "a"}, {"name": "c"})
+        self.assertEqual((result.matched_count, result.modified_count), (1, 1))
+        self.assertIsNone(result.upserted_id)
+        self.assertEqual(self.col.find_one({"_id": 1}), {"_id": 1, "name": "c"})
+        self.assertEqual(self.col.count_documents({}), 2)
+
+    def test_unchanged_document_is_not_modified(self):
+        result = self.col.replace_one({"_id": 2}, {"n": 2, "name": "b"})
+        self.assertEqual((result.matched_count, result.modified_count), (1, 0))
+
+    def test_no_match(self):
+        result = self.col.replace_one({"_id": 3}, {"name": "x"})
+        self.assertEqual((result.matched_count, result.modified_count), (0, 0))
+        self.assertIsNone(result.upserted_id)
+        self.assertEqual(self.col.count_documents({}), 2)
+
+    def test_upsert(self):
+        result = self.col.replace_one({"_id": 3}, {"name": "x"}, upsert=True)
+        self.assertEqual(result.upserted_id, 3)
+        self.assertEqual(self.col.find_one({"_id": 3}), {"_id": 3, "name": "x"})
+        result = self.col.replace_one({"name": "missing"}, {"name": "y"}, upsert=True)
+        self.assertIsInstance(result.upserted_id, ObjectId)
+        self.assertEqual(self.col.find_one({"name": "y"})["_id"], result.upserted_id)
+
+    def test_invalid_replacements(self):
+        with self.assertRaises(ValueError):
+            self.col.replace_one({"_id": 1}, {"$set": {"name": "x"}})
+        with self.assertRaises(ValueError):
+            self.col.replace_one({"_id": 1}, {"_id": 5, "name": "x"})
+        self.assertEqual(self.col.find_one({"_id": 1})["name"], "a")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,62 @@
+import unittest
+
+from mongo_emb import (DeleteResult, InsertManyResult, InsertOneResult, ObjectId,
+                       UpdateResult)
+
+from support import DatabaseTestCase
+
+
+class ResultsTest(DatabaseTestCase):
+
+    def setUp(self):
+        super().setUp()
+        self.col = self.db["results"]
+
+    def test_insert_one(self):
+        result = self.col.insert_one({"a": 1})
+        self.assertIsInstance(result, InsertOneResult)
+        self.assertIsInstance(result.inserted_id, ObjectId)
+        self.assertTrue(result.acknowledged)
+        self.assertEqual(self.col.insert_one({"_id": "x"}).inserted_id, "x")
+        self.assertEqual(repr(self.col.insert_one({"_id": 5})),
+                         "InsertOneResult(5, acknowledged=True)")
+
+    def test_insert_many_keeps_the_document_order(self):
+        ids = [f"id{n}" for n in range(40, 0, -1)]
+        result = self.col.insert_many([{"_id": id} for id in ids])
+        self.assertIsInstance(result, InsertManyResult)
+        self.assertEqual(result.inserted_ids, ids)
+        self.assertTrue(result.acknowledged)
+
+    def test_update(self):
+        self.col.insert_many([{"_id": n, "n": n} for n in range(3)])
+        result = self.col.update_many({"n": {"$gte": 1}}, {"$set": {"n": 1}})
+        self.assertIsInstance(result, UpdateResult)
+        self.assertEqual((result.matched_count, result.modified_count), (2, 2))
+        self.assertIsNone(result.upserted_id)
+        result = self.col.update_one({"_id": 0}, {"$set": {"n": 9}})
+        self.assertEqual((result.matched_count, result.modified_count), (1, 1))
+        self.assertTrue(result.acknowledged)
+
+    def test_upserted_id(self):
+        result = self.col.update_one({"_id": 7}, {"$set": {"n": 1}}, upsert=True)
+        self.assertEqual((result.matched_count, result.upserted_id), (0, 7))
+        result = self.col.update_many({"kind": "new"}, {"$set": {"n": 2}}, upsert=True)
+        self.assertIsInstance(result.upserted_id, ObjectId)
+        self.assertEqual(self.col.find_one({"kind": "new"}),
+                         {"_id": result.upserted_id, "kind": "new", "n": 2})
+        result = self.col.update_many({"kind": "new"}, {"$set": {"n": 3}}, upsert=True)
+        self.assertEqual((result.matched_count, result.upserted_id), (1, None))
+
+    def test_delete(self):
+        self.col.insert_many([{"n": n} for n in range(4)])
+        result = self.col.delete_one({"n": {"$lt": 2}})
+        self.assertIsInstance(result, DeleteResult)
+        self.assertEqual(result.deleted_count, 1)
+        self.assertEqual(self.col.delete_many({}).deleted_count, 3)
+        self.assertEqual(repr(self.col.delete_many({})),
+                         "DeleteResult(deleted_count=0, acknowledged=True)")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,55 @@
+import unittest
+
+from support import DatabaseTestCase
+
+
+class StatsTest(DatabaseTestCase):
+
+    def test_collection_stats(self):
+        users = self.db["users"]
+        users.insert_many([{"_id": i, "email": "u%d" % i} for i in range(10)])
+        users.create_index("email", unique=True)
+        stats = users.stats()
+        self.assertEqual(stats["ns"], "users")
+        self.assertEqual(stats["count"], 10)
+        self.assertEqual(stats["avgObjSize"], stats["size"] // 10)
+        self.assertEqual(stats["nindexes"], 2)
+        # polodb cannot measure an index
+        self.assertEqual(stats["indexSizes"], {"_id_": None, "email_1": None})
+        self.assertIsNone(stats["totalIndexSize"])
+        # Kept current by the writes, as measured again after reopening
+        users.delete_one({"_id": 0})
+        users.update_one({"_id": 1}, {"$set": {"email": "a longer address"}})
+        stats = users.stats()
+        self.assertEqual(stats["count"], 9)
+        self.reopen()
+        self.assertEqual(self.db["users"].stats(), stats)
+
+    def test_stats_in_a_transaction(self):
+        users = self.db["users"]
+        users.insert_one({"_id": 1, "name": "a"})
+        before = users.stats()
+        with self.db.start_transaction() as txn:
+            inside = txn.collection("users")
+            inside.insert_one({"_id": 2, "name": "b"})
+            inside.update_one({"_id": 1}, {"$set": {"name": "renamed"}})
+            self.assertEqual(inside.stats()["count"], 2)
+            self.assertEqual(users.stats(), before)
+            stats = inside.stats()
+        self.assertEqual(users.stats(), stats)
+        self.reopen()
+        self.assertEqual(self.db["users"].stats(), stats)
+
+    def test_database_stats(self):
+        self.db["a"].insert_many([{"_id": 1}, {"_id": 2}])
+        self.db["b"].insert_one({"_id": 1})
+        self.db["b"].create_index("x")
+        stats = self.db.stats()
+        self.assertEqual(stats["collections"], 2)
+        self.assertEqual(stats["objects"], 3)
+        self.assertGreater(stats["fileSize"], 0)
+        self.assertIsNone(stats["freePages"])
+
+
+if __name__ == "__main__":
+    unittest.main()
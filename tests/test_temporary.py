@@ -0,0 +1,56 @@
+import os
+import tempfile
+import unittest
+
+from mongo_emb import ClosedError, PyMongoEmb, PyRedb
+
+
+class TemporaryDatabaseTest(unittest.TestCase):
+    def setUp(self):
+        self.tmp = tempfile.TemporaryDirectory()
+
+    def tearDown(self):
+        self.tmp.cleanup()
+
+    def test_directory_removed_on_close(self):
+        db = PyMongoEmb.open_temporary(ttl_interval=None)
+        db["items"].insert_one({"_id": 1})
+        path = db.stats()["path"]
+        self.assertTrue(os.path.isdir(path))
+        db.close()
+        self.assertFalse(os.path.exists(path))
+        with self.assertRaises(ClosedError):
+            db["items"].find_one({})
+
+    def test_databases_are_separate(self):
+        with PyMongoEmb.open_temporary() as first, PyMongoEmb.open_temporary() as second:
+            first["items"].insert_one({"_id": 1})
+            self.assertEqual(second["items"].count_documents({}), 0)
+
+    def test_save_keeps_a_copy(self):
+        saved = os.path.join(self.tmp.name, "saved")
+        with PyMongoEmb.open_temporary() as db:
+            db["items"].insert_many([{"_id": 1, "email": "a"}, {"_id": 2, "email": "b"}])
+            db["items"].create_index("email", unique=True)
+            db.save(saved)
+            with self.assertRaises(FileExistsError):
+                db.save(saved)
+        with PyMongoEmb(saved) as db:
+            self.assertEqual(db["items"].count_documents({}), 2)
+            self.assertIn("email_1", [index["name"] for index in db["items"].list_indexes()])
+
+
+class MemoryRedbTest(unittest.TestCase):
+    def test_memory_rdb_and_save(self):
+        with tempfile.TemporaryDirectory() as tmp:
+            path = os.path.join(tmp, "kv.redb")
+            with PyRedb.open_memory("table") as rdb:
+                rdb.write("k", "v")
+                self.assertEqual(rdb.keys(), ["k"])
+                rdb.save(path)
+            with PyRedb(path, "table") as rdb:
+                self.assertEqual(rdb.read("k")["value"], "v")
+
+
+if __name__ == "__main__":
+    unittest.main()
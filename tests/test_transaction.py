@@ -0,0 +1,107 @@
+import threading
+import unittest
+
+from mongo_emb import WriteConflictError
+
+from support import DatabaseTestCase
+
+
+class TransactionTest(DatabaseTestCase):
+    def setUp(self):
+        super().setUp()
+        self.a = self.db.collection("stock_a")
+        self.b = self.db.collection("stock_b")
+        self.a.insert_one({"_id": "kp", "qty": 10})
+        self.b.insert_one({"_id": "kp", "qty": 0})
+
+    def qty(self, collection):
+        return collection.find_one({"_id": "kp"})["qty"]
+
+    def test_commit_on_clean_exit(self):
+        with self.db.start_transaction() as txn:
+            txn.collection("stock_a").update_one({"_id": "kp"}, {"$inc": {"qty": -3}})
+            txn.collection("stock_b").update_one({"_id": "kp"}, {"$inc": {"qty": 3}})
+            # Others see nothing before the commit
+            self.assertEqual(self.qty(self.a), 10)
+        self.assertEqual((self.qty(self.a), self.qty(self.b)), (7, 3))
+
+    def test_abort_on_exception(self):
+        with self.assertRaises(KeyError):
+            with self.db.start_transaction() as txn:
+                txn.collection("stock_a").update_one({"_id": "kp"}, {"$inc": {"qty": -3}})
+                raise KeyError("stop")
+        self.assertEqual((self.qty(self.a), self.qty(self.b)), (10, 0))
+
+    def test_explicit_commit_and_abort(self):
+        txn = self.db.start_transaction()
+        txn.collection("stock_a").insert_one({"_id": "new"})
+        txn.abort_transaction()
+        self.assertIsNone(self.a.find_one({"_id": "new"}))
+        with self.assertRaises(RuntimeError):
+            txn.collection("stock_a")
+
+        txn = self.db.start_transaction()
+        txn.collection("stock_a").insert_one({"_id": "new"})
+        txn.commit_transaction()
+        self.assertIsNotNone(self.a.find_one({"_id": "new"}))
+        with self.assertRaises(RuntimeError):
+            txn.abort_transaction()
+
+    def test_failed_write_aborts_the_transaction(self):
+        txn = self.db.start_transaction()
+        inside = txn.collection("stock_a")
+        inside.update_one({"_id": "kp"}, {"$set": {"qty": 1}})
+        with self.assertRaises(RuntimeError):
+            inside.insert_one({"_id": "kp"})
+        with self.assertRaises(RuntimeError):
+            inside.find_one({"_id": "kp"})
+        self.assertEqual(self.qty(self.a), 10)
+
+    def test_outside_write_conflicts_without_waiting(self):
+        txn = self.db.start_transaction()
+        txn.collection("stock_a").update_one({"_id": "kp"}, {"$inc": {"qty": -3}})
+        done = threading.Event()
+
+        def write():
+            with self.assertRaises(WriteConflictError):
+                self.a.update_one({"_id": "kp"}, {"$set": {"qty": 0}})
+            with self.assertRaises(WriteConflictError):
+                self.a.delete_many({})
+            done.set()
+
+        thread = threading.Thread(target=write)
+        thread.start()
+        # RocksDB would have waited a second for the lock
+        self.assertTrue(done.wait(0.5))
+        thread.join()
+        # Other documents and collections can still be written
+        self.a.insert_one({"_id": "other"})
+        self.b.update_one({"_id": "kp"}, {"$set": {"qty": 1}})
+        txn.commit_transaction()
+        self.assertEqual(self.qty(self.a), 7)
+        # Once the transaction ended its documents are free again
+        self.a.update_one({"_id": "kp"}, {"$set": {"qty": 0}})
+        self.assertEqual(self.qty(self.a), 0)
+
+    def test_conflict_between_transactions_aborts_the_second(self):
+        first = self.db.start_transaction()
+        first.collection("stock_a").update_one({"_id": "kp"}, {"$inc": {"qty": -3}})
+        second = self.db.start_transaction()
+        with self.assertRaises(WriteConflictError):
+            second.collection("stock_a").update_one({"_id": "kp"}, {"$inc": {"qty": -1}})
+        with self.assertRaises(RuntimeError):
+            second.commit_transaction()
+        first.commit_transaction()
+        self.assertEqual(self.qty(self.a), 7)
+
+    def test_insert_conflicts_with_an_uncommitted_insert(self):
+        txn = self.db.start_transaction()
+        txn.collection("stock_a").insert_one({"_id": "new"})
+        with self.assertRaises(WriteConflictError):
+            self.a.insert_one({"_id": "new"})
+        txn.abort_transaction()
+        self.a.insert_one({"_id": "new"})
+
+
+if __name__ == "__main__":
+    unittest.main()
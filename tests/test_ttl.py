@@ -0,0 +1,59 @@
+import datetime
+import time
+import unittest
+
+from support import DatabaseTestCase
+
+
+class TtlTest(DatabaseTestCase):
+
+    def setUp(self):
+        super().setUp()
+        self.col = self.db["sessions"]
+
+    def test_purge_expired(self):
+        self.col.create_index("createdAt", expire_after_seconds=60)
+        now = datetime.datetime.now(datetime.timezone.utc)
+        self.col.insert_many([
+            {"_id": "old", "createdAt": now - datetime.timedelta(minutes=5)},
+            {"_id": "new", "createdAt": now},
+            {"_id": "text", "createdAt": "yesterday"},
+            {"_id": "missing"},
+        ])
+        self.assertEqual(self.col.estimated_document_count(), 4)
+        self.assertEqual(self.db.purge_expired(), 1)
+        self.assertEqual(self.col.estimated_document_count(), 3)
+        self.assertEqual(sorted(doc["_id"] for doc in self.col.find({})), ["missing", "new", "text"])
+        self.assertEqual(self.db.purge_expired(), 0)
+
+    def test_purge_skips_documents_of_open_transactions(self):
+        self.col.create_index("createdAt", expire_after_seconds=0)
+        old = datetime.datetime(2000, 1, 1)
+        self.col.insert_many([{"_id": i, "createdAt": old} for i in range(2500)])
+        with self.db.start_transaction() as txn:
+            txn["sessions"].update_one({"_id": 0}, {"$set": {"seen": True}})
+            self.assertEqual(self.db.purge_expired(), 2499)
+        self.assertEqual(self.col.find({}).to_list(), [{"_id": 0, "createdAt": old, "seen": True}])
+        self.assertEqual(self.db.purge_expired(), 1)
+        self.assertEqual(self.col.estimated_document_count(), 0)
+
+    def test_ttl_index_is_listed(self):
+        self.col.create_index("createdAt", expire_after_seconds=3600)
+        self.assertEqual(self.col.list_indexes()[1],
+                         {"name": "createdAt_1", "key": {"createdAt": 1}, "expireAfterSeconds": 3600})
+        with self.assertRaises(ValueError):
+            self.col.create_index("other", expire_after_seconds=-1)
+
+    def test_background_monitor(self):
+        self.reopen(ttl_interval=0.05)
+        col = self.db["sessions"]
+        col.create_index("createdAt", expire_after_seconds=0)
+        col.insert_one({"createdAt": datetime.datetime(2000, 1, 1)})
+        deadline = time.monotonic() + 5
+        while col.count_documents({}) and time.monotonic() < deadline:
+            time.sleep(0.05)
+        self.assertEqual(col.count_documents({}), 0)
+
+
+if __name__ == "__main__":
+    unittest.main()
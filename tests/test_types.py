@@ -0,0 +1,194 @@
+import datetime
+import decimal
+import re
+import unittest
+import uuid
+
+from mongo_emb import (Binary, Code, Decimal128, Int32, MaxKey, MinKey, ObjectId, Symbol,
+                       Timestamp, Undefined)
+
+from support import DatabaseTestCase
+
+
+class TypesTest(DatabaseTestCase):
+
+    def setUp(self):
+        super().setUp()
+        self.col = self.db["types"]
+
+    def reopen(self, **options):
+        super().reopen(**options)
+        self.col = self.db["types"]
+
+    def round_trip(self, value):
+        self.col.delete_many({})
+        self.col.insert_one({"_id": 1, "value": value})
+        return self.col.find_one({"_id": 1})["value"]
+
+    def test_datetime_round_trip(self):
+        value = datetime.datetime(2024, 5, 1, 12, 30, 15, 123456)
+        self.assertEqual(self.round_trip(value), value.replace(microsecond=123000))
+
+    def test_date_is_stored_as_midnight(self):
+        self.assertEqual(self.round_trip(datetime.date(2024, 5, 1)),
+                         datetime.datetime(2024, 5, 1))
+
+    def test_aware_datetime_is_normalized_to_utc(self):
+        tz = datetime.timezone(datetime.timedelta(hours=2))
+        value = datetime.datetime(2024, 5, 1, 14, 0, tzinfo=tz)
+        self.assertEqual(self.round_trip(value), datetime.datetime(2024, 5, 1, 12, 0))
+
+    def test_tz_aware_read(self):
+        self.reopen(tzinfo=datetime.timezone(datetime.timedelta(hours=9)))
+        value = self.round_trip(datetime.datetime(2024, 5, 1, 12, 0))
+        self.assertEqual(value.utcoffset(), datetime.timedelta(hours=9))
+        self.assertEqual(value.hour, 21)
+
+    def test_none_and_bytes_round_trip(self):
+        self.assertIsNone(self.round_trip(None))
+        self.assertEqual(self.round_trip(b"\x00ab"), b"\x00ab")
+        self.assertEqual(self.round_trip(bytearray(b"ab")), b"ab")
+        self.assertEqual(self.round_trip(memoryview(b"ab")), b"ab")
+
+    def test_tuples_and_sets_are_stored_as_arrays(self):
+        self.assertEqual(self.round_trip((1, "a")), [1, "a"])
+        self.assertEqual(self.round_trip({1}), [1])
+        self.assertEqual(self.round_trip(frozenset({2})), [2])
+
+    def test_found_document_can_be_reinserted(self):
+        self.col.insert_one({"a": None, "b": b"x", "c": (1, 2), "d": {"e": None}})
+        doc = self.col.find_one({})
+        del doc["_id"]
+        copy = self.db["copy"]
+        copy.insert_one(doc)
+        copied = copy.find_one({})
+        del copied["_id"]
+        self.assertEqual(copied, doc)
+
+    def test_object_id(self):
+        oid = ObjectId("68f73fe7790a4a5d60d08dba")
+        self.assertEqual(str(oid), "68f73fe7790a4a5d60d08dba")
+        self.assertEqual(oid, ObjectId(oid.binary))
+        self.assertEqual(hash(oid), hash(ObjectId(str(oid))))
+        self.assertTrue(ObjectId.is_valid(str(oid)))
+        self.assertFalse(ObjectId.is_valid("xyz"))
+        with self.assertRaises(ValueError):
+            ObjectId("xyz")
+        self.assertEqual(self.round_trip(oid), oid)
+
+    def test_inserted_id_is_an_object_id(self):
+        inserted_id = self.col.insert_one({"a": 1}).inserted_id
+        self.assertIsInstance(inserted_id, ObjectId)
+        self.assertEqual(self.col.find_one({"_id": inserted_id})["a"], 1)
+
+    def test_decimal_round_trip(self):
+        value = decimal.Decimal("1234.5600")
+        stored = self.round_trip(value)
+        self.assertIsInstance(stored, decimal.Decimal)
+        self.assertEqual(str(stored), "1234.5600")
+
+    def test_uuid_round_trip(self):
+        value = uuid.uuid4()
+        self.assertEqual(self.round_trip(value), value)
+
+    def test_int32_is_stored_as_int32(self):
+        value = self.round_trip(Int32(7))
+        self.assertIsInstance(value, Int32)
+        self.assertIsInstance(value, int)
+        self.assertEqual(value, 7)
+        self.assertEqual(repr(value), "Int32(7)")
+        self.col.insert_one({"copy": value})
+        self.assertIsInstance(self.col.find_one({"copy": 7})["copy"], Int32)
+        self.reopen(strict=True)
+        self.assertIsInstance(self.col.find_one({"value": 7})["value"], Int32)
+
+    def test_int32_out_of_range_raises_overflow_error(self):
+        with self.assertRaises(OverflowError):
+            Int32(2 ** 31)
+
+    def test_wide_int_raises_overflow_error(self):
+        with self.assertRaises(OverflowError):
+            self.col.insert_one({"value": 2 ** 64})
+        self.assertEqual(self.col.count_documents({}), 0)
+
+    def test_regex_round_trip_keeps_flags(self):
+        value = self.round_trip(re.compile("^ab.c", re.I | re.M | re.S | re.X))
+        self.assertEqual(value.pattern, "^ab.c")
+        self.assertEqual(value.flags & (re.I | re.M | re.S | re.X), re.I | re.M | re.S | re.X)
+
+    def test_regex_is_stored_without_unicode_option(self):
+        self.col.insert_one({"_id": 1, "value": re.compile("a", re.I)})
+        self.assertIn('"options":"i"', self.col.find({}, as_json=True))
+
+    def test_regex_filters(self):
+        self.col.insert_many([{"name": name} for name in ["Alice", "alfred", "Bob"]])
+
+        def names(filter):
+            return sorted(doc["name"] for doc in self.col.find(filter))
+
+        self.assertEqual(names({"name": re.compile("al", re.I)}), ["Alice", "alfred"])
+        self.assertEqual(names({"name": {"$regex": "AL", "$options": "i"}}), ["Alice", "alfred"])
+        self.assertEqual(names({"name": {"$not": re.compile("al", re.I)}}), ["Bob"])
+        self.assertEqual(names({"$or": [{"name": re.compile("B")}, {"name": "alfred"}]}),
+                         ["Bob", "alfred"])
+        self.assertEqual(names({"name": {"$in": [re.compile("al", re.I), "Bob"]}}),
+                         ["Alice", "Bob", "alfred"])
+        self.assertEqual(names({"name": {"$nin": [re.compile("AL", re.I)]}}), ["Bob"])
+
+    def test_every_bson_wrapper_round_trips(self):
+        values = [Timestamp(5, 1), MinKey(), MaxKey(), Code("f()"), Code("f(x)", {"x": 1}),
+                  Symbol("s"), Undefined(), Binary(b"ab", 0x80)]
+        for value in values:
+            with self.subTest(value=value):
+                self.col.delete_many({})
+                self.assertEqual(self.round_trip(value), value)
+
+    def test_decimal128(self):
+        value = Decimal128(decimal.Decimal("1.50"))
+        self.assertEqual(str(value), "1.50")
+        self.assertEqual(value.to_decimal(), decimal.Decimal("1.50"))
+        self.assertEqual(len(value.bid), 16)
+        self.assertEqual(self.round_trip(value), decimal.Decimal("1.50"))
+        self.reopen(strict=True)
+        self.assertEqual(self.col.find_one({})["value"], value)
+
+    def test_strict_mode_refuses_lossy_values(self):
+        self.reopen(strict=True)
+        for value in [(1, 2), {1}, datetime.date(2024, 1, 1), bytearray(b"a"),
+                      type("Name", (str,), {})("a")]:
+            with self.subTest(value=value), self.assertRaises(TypeError):
+                self.col.insert_one({"value": value})
+        with self.assertRaises(ValueError):
+            self.col.insert_one({"value": datetime.datetime(2024, 1, 1, microsecond=1)})
+        self.assertEqual(self.col.count_documents({}), 0)
+
+    def test_conversion_errors_name_the_field(self):
+        class Foo:
+            pass
+
+        with self.assertRaises(TypeError) as raised:
+            self.col.insert_many([{"a": 1}, {"address": {"zip": Foo()}}])
+        self.assertEqual(str(raised.exception), "docs[1].address.zip: unsupported type 'Foo'")
+        self.assertIsInstance(raised.exception.__cause__, TypeError)
+        self.assertEqual(self.col.count_documents({}), 0)
+        with self.assertRaises(TypeError):
+            self.col.insert_many({"a": 1})
+        with self.assertRaises(TypeError) as raised:
+            self.col.insert_one({"a": {"b": Foo()}})
+        self.assertEqual(str(raised.exception), "doc.a.b: unsupported type 'Foo'")
+        for delete in [self.col.delete_one, self.col.delete_many]:
+            with self.assertRaises(TypeError) as raised:
+                delete({"x": Foo()})
+            self.assertEqual(str(raised.exception), "filter.x: unsupported type 'Foo'")
+
+    def test_datetime_range_query(self):
+        start = datetime.datetime(2024, 1, 1)
+        self.col.insert_many([{"n": n, "at": start + datetime.timedelta(days=n)}
+                              for n in range(10)])
+        found = self.col.find({"at": {"$gt": start + datetime.timedelta(days=2),
+                                      "$lt": start + datetime.timedelta(days=6)}})
+        self.assertEqual(sorted(doc["n"] for doc in found), [3, 4, 5])
+
+
+if __name__ == "__main__":
+    unittest.main()